@@ -0,0 +1,109 @@
+use crate::{db::collections, errors::Result, runtime};
+use serde_json::{json, Value};
+
+pub fn list(args: Value) -> Result<Value> {
+    let tree = args.get("tree").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    if tree {
+        let tree = runtime::block_on(async { collections::collection_tree().await })?;
+        return Ok(json!({ "collections": tree }));
+    }
+
+    let collections = runtime::block_on(async { collections::list_collections().await })?;
+    Ok(json!({ "collections": collections }))
+}
+
+pub fn create(args: Value) -> Result<Value> {
+    let name = args
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing name")?;
+    let parent_id = args.get("parent_id").and_then(|v| v.as_str());
+
+    let collection =
+        runtime::block_on(async { collections::create_collection(name, parent_id).await })?;
+    Ok(json!(collection))
+}
+
+pub fn rename(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let name = args
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing name")?;
+
+    runtime::block_on(async { collections::rename_collection(id, name).await })?;
+    Ok(json!({ "success": true }))
+}
+
+pub fn move_collection(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let parent_id = args.get("parent_id").and_then(|v| v.as_str());
+    let position = position(&args);
+
+    runtime::block_on(async { collections::move_collection(id, parent_id, position).await })?;
+    Ok(json!({ "success": true }))
+}
+
+pub fn delete(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+
+    let moved = runtime::block_on(async { collections::delete_collection(id).await })?;
+    Ok(json!({ "success": true, "moved_prompts": moved }))
+}
+
+pub fn reorder(args: Value) -> Result<Value> {
+    let parent_id = args.get("parent_id").and_then(|v| v.as_str());
+    let ids = ids(&args)?;
+
+    runtime::block_on(async { collections::reorder_collections(parent_id, &ids).await })?;
+    Ok(json!({ "success": true }))
+}
+
+pub fn move_prompt(args: Value) -> Result<Value> {
+    let prompt_id = args
+        .get("prompt_id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing prompt_id")?;
+    let collection_id = args.get("collection_id").and_then(|v| v.as_str());
+    let position = position(&args);
+
+    runtime::block_on(async {
+        collections::move_prompt(prompt_id, collection_id, position).await
+    })?;
+    Ok(json!({ "success": true }))
+}
+
+pub fn reorder_prompts(args: Value) -> Result<Value> {
+    let collection_id = args.get("collection_id").and_then(|v| v.as_str());
+    let ids = ids(&args)?;
+
+    runtime::block_on(async { collections::reorder_prompts(collection_id, &ids).await })?;
+    Ok(json!({ "success": true }))
+}
+
+/// Optional 0-based position argument (missing -> append)
+fn position(args: &Value) -> Option<usize> {
+    args.get("position")
+        .and_then(|v| v.as_u64())
+        .map(|p| p as usize)
+}
+
+fn ids(args: &Value) -> Result<Vec<String>> {
+    Ok(args
+        .get("ids")
+        .and_then(|v| v.as_array())
+        .ok_or("Missing ids")?
+        .iter()
+        .filter_map(|v| v.as_str().map(String::from))
+        .collect())
+}
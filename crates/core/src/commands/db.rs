@@ -0,0 +1,19 @@
+use crate::{db::Db, errors::Result, runtime};
+use serde_json::{json, Value};
+
+pub fn status(_args: Value) -> Result<Value> {
+    let status = runtime::block_on(async { Db::status().await })?;
+
+    Ok(json!({
+        "path": Db::path(),
+        "current_version": status.current_version,
+        "latest_version": status.latest_version,
+        "up_to_date": status.pending.is_empty(),
+        "applied": status.applied,
+        "pending": status
+            .pending
+            .iter()
+            .map(|(version, name)| json!({ "version": version, "name": name }))
+            .collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,118 @@
+use crate::{
+    errors::{AmpError, Result},
+    mcp::{
+        model::{McpAction, McpServer},
+        store::{McpConfig, McpStore},
+    },
+};
+use serde_json::{json, Value};
+
+/// All servers, enabled or not, with their previews and the rules deciding
+/// whether Amp may start them
+pub fn list(_args: Value) -> Result<Value> {
+    let store = McpStore::default_paths();
+    let config = store.load()?;
+    Ok(response(&store, &config))
+}
+
+/// Add a server
+///
+/// Args: `name`, `server` (a server config), optional `enabled` (default
+/// true) and `permission` (`allow` or `reject`) to add a rule for exactly
+/// this server.
+pub fn add(args: Value) -> Result<Value> {
+    let name = name(&args)?;
+    let server = args.get("server").ok_or("Missing server")?;
+    let server = McpServer::from_value(server)?;
+    let enabled = args
+        .get("enabled")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let permission = permission(&args)?;
+
+    let store = McpStore::default_paths();
+    let config = store.edit(|config| {
+        config.add(name, &server, enabled)?;
+        if permission.is_some() {
+            config.set_permission(&server, permission);
+        }
+        Ok(())
+    })?;
+    Ok(response(&store, &config))
+}
+
+/// Change fields of a server
+///
+/// Args: `name`, `patch`, an object of fields to set (`null` removes one),
+/// and optional `permission`: `allow`, `reject` or `null` to drop the rule
+/// for exactly this server.
+pub fn update(args: Value) -> Result<Value> {
+    let name = name(&args)?;
+    let patch = args.get("patch").cloned().unwrap_or_else(|| json!({}));
+    let permission = permission(&args)?;
+    let set_permission = args.get("permission").is_some();
+
+    let store = McpStore::default_paths();
+    let config = store.edit(|config| {
+        let server = config.update(name, &patch)?;
+        if set_permission {
+            config.set_permission(&server, permission);
+        }
+        Ok(())
+    })?;
+    Ok(response(&store, &config))
+}
+
+/// Remove a server and the rules written for exactly it
+pub fn remove(args: Value) -> Result<Value> {
+    let name = name(&args)?;
+
+    let store = McpStore::default_paths();
+    let config = store.edit(|config| config.remove(name).map(|_| ()))?;
+    Ok(response(&store, &config))
+}
+
+pub fn enable(args: Value) -> Result<Value> {
+    set_enabled(args, true)
+}
+
+/// Move a server out of `amp.mcpServers` so Amp no longer starts it
+pub fn disable(args: Value) -> Result<Value> {
+    set_enabled(args, false)
+}
+
+fn set_enabled(args: Value, enabled: bool) -> Result<Value> {
+    let name = name(&args)?;
+
+    let store = McpStore::default_paths();
+    let config = store.edit(|config| config.set_enabled(name, enabled))?;
+    Ok(response(&store, &config))
+}
+
+fn response(store: &McpStore, config: &McpConfig) -> Value {
+    let lookup = |name: &str| std::env::var(name).ok();
+    let (_, permission_errors) = config.permission_rules();
+    json!({
+        "servers": config.entries(&lookup),
+        "permissions": config.permissions,
+        "permission_errors": permission_errors,
+        "path": store.path,
+        "settings_path": store.settings_path,
+    })
+}
+
+fn name(args: &Value) -> Result<&str> {
+    Ok(args
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing name")?)
+}
+
+fn permission(args: &Value) -> Result<Option<McpAction>> {
+    match args.get("permission") {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => serde_json::from_value(v.clone()).map(Some).map_err(|_| {
+            AmpError::ValidationError("'permission' must be \"allow\" or \"reject\"".into())
+        }),
+    }
+}
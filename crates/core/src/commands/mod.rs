@@ -21,7 +21,14 @@ use serde_json::Value;
 
 use crate::errors::{AmpError, Result};
 
+mod collections;
+mod db;
+mod mcp;
+mod permissions;
 mod prompts;
+mod tags;
+mod threads;
+mod usage;
 
 // Removed command modules:
 // - account_update
@@ -50,12 +57,112 @@ static REGISTRY: Lazy<HashMap<&'static str, CommandHandler>> = Lazy::new(|| {
     // Test command
     map.insert("ping", ping as CommandHandler);
 
+    // Database
+    map.insert("db.status", db::status as CommandHandler);
+
     // DashX Prompts
     map.insert("prompts.list", prompts::list as CommandHandler);
+    map.insert("prompts.search", prompts::search as CommandHandler);
     map.insert("prompts.create", prompts::create as CommandHandler);
     map.insert("prompts.update", prompts::update as CommandHandler);
     map.insert("prompts.delete", prompts::delete as CommandHandler);
+    map.insert("prompts.trash", prompts::trash as CommandHandler);
+    map.insert(
+        "prompts.restore_deleted",
+        prompts::restore_deleted as CommandHandler,
+    );
+    map.insert("prompts.purge", prompts::purge as CommandHandler);
     map.insert("prompts.use", prompts::use_prompt as CommandHandler);
+    map.insert("prompts.usage", prompts::usage as CommandHandler);
+    map.insert("prompts.render", prompts::render as CommandHandler);
+    map.insert("prompts.variables", prompts::variables as CommandHandler);
+    map.insert("prompts.history", prompts::history as CommandHandler);
+    map.insert("prompts.diff", prompts::diff as CommandHandler);
+    map.insert("prompts.restore", prompts::restore as CommandHandler);
+    map.insert("prompts.export", prompts::export as CommandHandler);
+    map.insert("prompts.import", prompts::import as CommandHandler);
+
+    // Prompt tags
+    map.insert("tags.list", tags::list as CommandHandler);
+    map.insert("tags.rename", tags::rename as CommandHandler);
+    map.insert("tags.merge", tags::merge as CommandHandler);
+    map.insert("tags.delete", tags::delete as CommandHandler);
+
+    // Prompt collections
+    map.insert("collections.list", collections::list as CommandHandler);
+    map.insert("collections.create", collections::create as CommandHandler);
+    map.insert("collections.rename", collections::rename as CommandHandler);
+    map.insert(
+        "collections.move",
+        collections::move_collection as CommandHandler,
+    );
+    map.insert("collections.delete", collections::delete as CommandHandler);
+    map.insert(
+        "collections.reorder",
+        collections::reorder as CommandHandler,
+    );
+    map.insert(
+        "collections.move_prompt",
+        collections::move_prompt as CommandHandler,
+    );
+    map.insert(
+        "collections.reorder_prompts",
+        collections::reorder_prompts as CommandHandler,
+    );
+
+    // Threads
+    map.insert("threads.list", threads::list as CommandHandler);
+    map.insert("threads.search", threads::search as CommandHandler);
+    map.insert("threads.for_file", threads::for_file as CommandHandler);
+    map.insert("threads.reindex", threads::reindex as CommandHandler);
+    map.insert("threads.render", threads::render as CommandHandler);
+    map.insert("threads.export", threads::export as CommandHandler);
+    map.insert("threads.compare", threads::compare as CommandHandler);
+    map.insert(
+        "threads.continue",
+        threads::continue_thread as CommandHandler,
+    );
+    map.insert("threads.fork", threads::fork as CommandHandler);
+    map.insert("threads.processes", threads::processes as CommandHandler);
+    map.insert("threads.kill", threads::kill as CommandHandler);
+    map.insert("threads.events", threads::events as CommandHandler);
+    map.insert("threads.archive", threads::archive as CommandHandler);
+    map.insert("threads.unarchive", threads::unarchive as CommandHandler);
+    map.insert("threads.delete", threads::delete as CommandHandler);
+    map.insert("threads.restore", threads::restore as CommandHandler);
+    map.insert("threads.purge", threads::purge as CommandHandler);
+    map.insert("threads.trash", threads::trash as CommandHandler);
+
+    // Usage
+    map.insert("usage.summary", usage::summary as CommandHandler);
+
+    // Permissions
+    map.insert("permissions.list", permissions::list as CommandHandler);
+    map.insert("permissions.add", permissions::add as CommandHandler);
+    map.insert("permissions.update", permissions::update as CommandHandler);
+    map.insert("permissions.remove", permissions::remove as CommandHandler);
+    map.insert(
+        "permissions.reorder",
+        permissions::reorder as CommandHandler,
+    );
+    map.insert("permissions.toggle", permissions::toggle as CommandHandler);
+    map.insert(
+        "permissions.evaluate",
+        permissions::evaluate as CommandHandler,
+    );
+    map.insert("permissions.lint", permissions::lint as CommandHandler);
+    map.insert(
+        "permissions.suggest",
+        permissions::suggest as CommandHandler,
+    );
+
+    // MCP servers
+    map.insert("mcp.list", mcp::list as CommandHandler);
+    map.insert("mcp.add", mcp::add as CommandHandler);
+    map.insert("mcp.update", mcp::update as CommandHandler);
+    map.insert("mcp.remove", mcp::remove as CommandHandler);
+    map.insert("mcp.enable", mcp::enable as CommandHandler);
+    map.insert("mcp.disable", mcp::disable as CommandHandler);
 
     map
 });
@@ -261,4 +368,102 @@ mod tests {
         assert_eq!(result["field2"], json!(42));
         assert_eq!(result["field3"], json!(true));
     }
+
+    // ========================================
+    // permissions.evaluate tests
+    // ========================================
+
+    #[test]
+    fn test_dispatch_permissions_evaluate_with_rules() {
+        let args = json!({
+            "tool": "Bash",
+            "args": {"cmd": "git push origin"},
+            "context": "subagent",
+            "rules": [
+                {"tool": "Bash", "action": "allow", "context": "thread"},
+                {"tool": "Bash", "matches": {"cmd": "*git push*"}, "action": "ask"}
+            ]
+        });
+        let result = dispatch("permissions.evaluate", args).unwrap();
+
+        assert_eq!(result["index"], json!(1));
+        assert_eq!(result["action"], json!("ask"));
+        assert_eq!(result["context"], json!("subagent"));
+        assert_eq!(result["trace"][0]["applies"], json!(false));
+
+        // Without args the tool input is empty
+        let args = json!({"tool": "Read", "rules": [{"tool": "Bash", "action": "allow"}]});
+        let result = dispatch("permissions.evaluate", args).unwrap();
+        assert_eq!(result["action"], json!(null));
+    }
+
+    #[test]
+    fn test_dispatch_permissions_evaluate_rejects_bad_args() {
+        let rules = json!([{"tool": "Bash", "action": "allow"}]);
+
+        let args = json!({"tool": "Bash", "context": "everywhere", "rules": rules});
+        assert!(matches!(
+            dispatch("permissions.evaluate", args),
+            Err(AmpError::ValidationError(_))
+        ));
+
+        let args = json!({"rules": rules});
+        assert!(dispatch("permissions.evaluate", args).is_err());
+
+        let args = json!({"tool": "Bash", "rules": [{"tool": "Bash", "action": "delegate"}]});
+        assert!(matches!(
+            dispatch("permissions.evaluate", args),
+            Err(AmpError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_permissions_lint_with_rules() {
+        let args = json!({
+            "tools": ["Bash"],
+            "rules": [
+                {"tool": "Bash", "action": "ask"},
+                {"tool": "Bash", "matches": {"cmd": "/(/"}, "action": "allow"},
+                {"tool": "Grep", "action": "allow"}
+            ]
+        });
+        let result = dispatch("permissions.lint", args).unwrap();
+
+        assert_eq!(result["tools_checked"], json!(true));
+        let issues: Vec<(u64, &str)> = result["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| (i["index"].as_u64().unwrap(), i["kind"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![(1, "shadowed"), (1, "invalid_regex"), (2, "unknown_tool")]
+        );
+        // Draft rules have no position in a file
+        assert_eq!(result["issues"][0].get("line"), None);
+    }
+
+    // ========================================
+    // mcp tests
+    // ========================================
+
+    #[test]
+    fn test_dispatch_mcp_add_rejects_bad_args() {
+        // Rejected before the settings file is read
+        let args = json!({"name": "x", "server": {"command": "npx", "url": "https://a"}});
+        assert!(matches!(
+            dispatch("mcp.add", args),
+            Err(AmpError::ValidationError(_))
+        ));
+
+        let args = json!({"server": {"command": "npx"}});
+        assert!(dispatch("mcp.add", args).is_err());
+
+        let args = json!({"name": "x", "server": {"command": "npx"}, "permission": "ask"});
+        assert!(matches!(
+            dispatch("mcp.add", args),
+            Err(AmpError::ValidationError(_))
+        ));
+    }
 }
@@ -0,0 +1,229 @@
+use crate::{
+    errors::{AmpError, Result},
+    permissions::{
+        evaluate,
+        lint::{self, LintEnv},
+        model::{PermissionRule, RuleContext},
+        store::{self, PermissionStore},
+        suggest::{self, SuggestOptions},
+    },
+    threads::{
+        self,
+        manage::{Area, ThreadAreas},
+    },
+};
+use serde_json::{json, Value};
+
+/// All rules, enabled or not, in evaluation order
+pub fn list(_args: Value) -> Result<Value> {
+    let store = PermissionStore::default_paths();
+    let rules = store.load()?;
+    Ok(response(&store, &rules))
+}
+
+/// Add a rule
+///
+/// Args: `rule` (a rule object) and optional 0-based `index` (appended
+/// otherwise).
+pub fn add(args: Value) -> Result<Value> {
+    let rule = args.get("rule").cloned().ok_or("Missing rule")?;
+    let rule = PermissionRule::from_value(rule)?;
+    let index = optional_index(&args, "index")?;
+
+    let store = PermissionStore::default_paths();
+    let rules = store.edit(|rules| store::add(rules, rule, index))?;
+    Ok(response(&store, &rules))
+}
+
+/// Change fields of a rule
+///
+/// Args: `index` and `patch`, an object of fields to set (`null` removes
+/// one).
+pub fn update(args: Value) -> Result<Value> {
+    let index = index(&args)?;
+    let patch = args.get("patch").ok_or("Missing patch")?;
+
+    let store = PermissionStore::default_paths();
+    let rules = store.edit(|rules| store::update(rules, index, patch))?;
+    Ok(response(&store, &rules))
+}
+
+pub fn remove(args: Value) -> Result<Value> {
+    let index = index(&args)?;
+
+    let store = PermissionStore::default_paths();
+    let rules = store.edit(|rules| store::remove(rules, index).map(|_| ()))?;
+    Ok(response(&store, &rules))
+}
+
+/// Reorder rules
+///
+/// Args: `order`, the current indices in their new order.
+pub fn reorder(args: Value) -> Result<Value> {
+    let order = args
+        .get("order")
+        .and_then(|v| v.as_array())
+        .ok_or("Missing order")?
+        .iter()
+        .map(|v| {
+            v.as_u64()
+                .map(|i| i as usize)
+                .ok_or_else(|| AmpError::ValidationError("Order must list rule indices".into()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let store = PermissionStore::default_paths();
+    let rules = store.edit(|rules| store::reorder(rules, &order))?;
+    Ok(response(&store, &rules))
+}
+
+/// Enable or disable a rule
+///
+/// Args: `index` and optional `enabled` (flipped if omitted).
+pub fn toggle(args: Value) -> Result<Value> {
+    let index = index(&args)?;
+    let enabled = args.get("enabled").and_then(|v| v.as_bool());
+
+    let store = PermissionStore::default_paths();
+    let rules = store.edit(|rules| store::toggle(rules, index, enabled).map(|_| ()))?;
+    Ok(response(&store, &rules))
+}
+
+/// What Amp would do with a tool call
+///
+/// Args: `tool`, `args` (the tool input object), `context` (`thread` or
+/// `subagent`, default `thread`) and optional `rules` to try instead of the
+/// configured ones.
+pub fn evaluate(args: Value) -> Result<Value> {
+    let tool = args
+        .get("tool")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing tool")?;
+    let input = args.get("args").cloned().unwrap_or_else(|| json!({}));
+    let context = match args.get("context") {
+        None | Some(Value::Null) => RuleContext::Thread,
+        Some(v) => serde_json::from_value(v.clone()).map_err(|_| {
+            AmpError::ValidationError("'context' must be 'thread' or 'subagent'".into())
+        })?,
+    };
+
+    let rules = match draft_rules(&args)? {
+        Some(rules) => rules,
+        None => PermissionStore::default_paths().load()?,
+    };
+
+    let evaluation = evaluate::evaluate(&rules, tool, &input, context);
+    Ok(json!(evaluation))
+}
+
+/// Problems in the rules, with their position in the files when known
+///
+/// Args: optional `rules` to check instead of the configured ones (issues
+/// then have no position), `tools` (known tool names) to skip running
+/// `amp tools list`, and `check_tools: false` to skip the unknown tool
+/// check.
+pub fn lint(args: Value) -> Result<Value> {
+    let mut env = LintEnv::from_system_path();
+    let check_tools = args
+        .get("check_tools")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    if let Some(tools) = args.get("tools").and_then(|v| v.as_array()) {
+        env.tools = Some(
+            tools
+                .iter()
+                .filter_map(|t| t.as_str().map(String::from))
+                .collect(),
+        );
+    } else if check_tools {
+        env.load_tools();
+    }
+
+    let store = PermissionStore::default_paths();
+    let (rules, locations) = match draft_rules(&args)? {
+        Some(rules) => (rules, Vec::new()),
+        None => {
+            let rules = store.load()?;
+            let locations = store.locate(&rules);
+            (rules, locations)
+        },
+    };
+
+    let mut issues = lint::lint(&rules, &env);
+    for issue in &mut issues {
+        issue.location = locations.get(issue.index).cloned().flatten();
+    }
+
+    Ok(json!({
+        "issues": issues,
+        "tools_checked": env.tools.is_some(),
+        "tools_error": env.tools_error,
+        "path": store.path,
+        "settings_path": store.settings_path,
+    }))
+}
+
+/// Rules proposed from the tool calls in thread history
+///
+/// Args: `min_count` (calls a rule must cover, default 3), `limit` (default
+/// 20) and `include_archived` to also read archived threads. Calls the
+/// configured rules already decide are left out.
+pub fn suggest(args: Value) -> Result<Value> {
+    let defaults = SuggestOptions::default();
+    let count = |key: &str, default: usize| {
+        args.get(key)
+            .and_then(|v| v.as_u64())
+            .map_or(default, |n| n as usize)
+    };
+    let options = SuggestOptions {
+        min_count: count("min_count", defaults.min_count).max(1),
+        limit: count("limit", defaults.limit),
+    };
+    let include_archived = args
+        .get("include_archived")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let areas = ThreadAreas::new(&threads::threads_dir());
+    let mut dirs = vec![areas.dir(Area::Threads)];
+    if include_archived {
+        dirs.push(areas.dir(Area::Archive));
+    }
+
+    let existing = PermissionStore::default_paths().load()?;
+    let report = suggest::suggest(&dirs, &existing, options)?;
+    Ok(json!(report))
+}
+
+/// Rules passed in `rules`, if any
+fn draft_rules(args: &Value) -> Result<Option<Vec<PermissionRule>>> {
+    let Some(items) = args.get("rules").and_then(|v| v.as_array()) else {
+        return Ok(None);
+    };
+    items
+        .iter()
+        .map(|v| PermissionRule::from_value(v.clone()))
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+fn response(store: &PermissionStore, rules: &[PermissionRule]) -> Value {
+    json!({
+        "rules": rules,
+        "path": store.path,
+        "settings_path": store.settings_path,
+    })
+}
+
+fn index(args: &Value) -> Result<usize> {
+    optional_index(args, "index")?.ok_or_else(|| "Missing index".into())
+}
+
+fn optional_index(args: &Value, key: &str) -> Result<Option<usize>> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v.as_u64().map(|i| Some(i as usize)).ok_or_else(|| {
+            AmpError::ValidationError(format!("'{}' must be a rule index (0-based)", key))
+        }),
+    }
+}
@@ -1,9 +1,66 @@
-use crate::{db::prompts, errors::Result, runtime};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    db::{prompts, revisions},
+    errors::{AmpError, Result},
+    library::{
+        project::{self, PromptEdit},
+        transfer::{self, ConflictStrategy, Format},
+    },
+    runtime,
+    templates::{self, EditorContext, VariableDecl},
+};
 use serde_json::{json, Value};
 
-pub fn list(_args: Value) -> Result<Value> {
-    let prompts = runtime::block_on(async { prompts::list_prompts().await })?;
-    Ok(json!({ "prompts": prompts }))
+/// List global prompts merged with the project prompts of `cwd`
+///
+/// Args: `cwd` (defaults to the editor's working directory), the tag
+/// filters `tag`, `any_tags` and `all_tags`, `collection_id` to list one
+/// collection or `unfiled: true` for prompts in none, and `sort` (updated,
+/// frecency, most_used, recent, alphabetical or manual, the order within a
+/// collection; default updated).
+pub fn list(args: Value) -> Result<Value> {
+    let query = prompt_query(&args)?;
+    let cwd = cwd(&args)?;
+    let settings = project::settings();
+
+    let (prompts, library) =
+        runtime::block_on(async { project::merged(&query, &cwd, &settings).await })?;
+    Ok(json!({
+        "prompts": prompts,
+        "project_dir": library.dir,
+        "project_errors": library.errors,
+    }))
+}
+
+pub fn search(args: Value) -> Result<Value> {
+    let query = args
+        .get("query")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing query")?;
+
+    let options = search_options(&args);
+    let hits = runtime::block_on(async { prompts::search_prompts(query, options).await })?;
+    Ok(json!({ "results": hits }))
+}
+
+/// Read the full-text search options shared by prompt and thread searches
+pub(super) fn search_options(args: &Value) -> prompts::SearchOptions {
+    let mut options = prompts::SearchOptions::default();
+    if let Some(limit) = args.get("limit").and_then(|v| v.as_i64()) {
+        options.limit = limit;
+    }
+    if let Some(start) = args.get("highlight_start").and_then(|v| v.as_str()) {
+        options.highlight_start = start.to_string();
+    }
+    if let Some(end) = args.get("highlight_end").and_then(|v| v.as_str()) {
+        options.highlight_end = end.to_string();
+    }
+    if let Some(raw) = args.get("raw").and_then(|v| v.as_bool()) {
+        options.raw = raw;
+    }
+    options
 }
 
 pub fn create(args: Value) -> Result<Value> {
@@ -25,8 +82,36 @@ pub fn create(args: Value) -> Result<Value> {
             .collect()
     });
 
+    let variables = variable_decls(&args)?;
+
+    if args.get("scope").and_then(|v| v.as_str()) == Some("project") {
+        let edit = PromptEdit {
+            title: title.to_string(),
+            description,
+            content: content.to_string(),
+            tags,
+            variables,
+        };
+        let prompt = project::create(&cwd(&args)?, &project::settings(), edit)?;
+        return Ok(json!(prompt));
+    }
+
+    let collection_id = args
+        .get("collection_id")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+
     let prompt = runtime::block_on(async {
-        prompts::create_prompt(title.to_string(), description, content.to_string(), tags).await
+        prompts::insert_prompt(prompts::NewPrompt {
+            title: title.to_string(),
+            description,
+            content: content.to_string(),
+            tags,
+            variables,
+            collection_id,
+            ..Default::default()
+        })
+        .await
     })?;
 
     Ok(json!(prompt))
@@ -55,6 +140,21 @@ pub fn update(args: Value) -> Result<Value> {
             .collect()
     });
 
+    let variables = variable_decls(&args)?;
+
+    let settings = project::settings();
+    if let Some(prompt) = project::find(&cwd(&args)?, &settings, id)? {
+        let edit = PromptEdit {
+            title: title.to_string(),
+            description,
+            content: content.to_string(),
+            tags,
+            variables,
+        };
+        project::update(&settings, &prompt, edit)?;
+        return Ok(json!({ "success": true }));
+    }
+
     runtime::block_on(async {
         prompts::update_prompt(
             id.to_string(),
@@ -62,6 +162,7 @@ pub fn update(args: Value) -> Result<Value> {
             description,
             content.to_string(),
             tags,
+            variables,
         )
         .await
     })?;
@@ -75,11 +176,48 @@ pub fn delete(args: Value) -> Result<Value> {
         .and_then(|v| v.as_str())
         .ok_or("Missing id")?;
 
+    let settings = project::settings();
+    if let Some(prompt) = project::find(&cwd(&args)?, &settings, id)? {
+        project::delete(&settings, &prompt)?;
+        return Ok(json!({ "success": true }));
+    }
+
     runtime::block_on(async { prompts::delete_prompt(id.to_string()).await })?;
 
+    Ok(json!({ "success": true, "trashed": true }))
+}
+
+/// List prompts in the trash, most recently deleted first
+pub fn trash(_args: Value) -> Result<Value> {
+    let prompts = runtime::block_on(async { prompts::list_deleted().await })?;
+    Ok(json!({ "prompts": prompts }))
+}
+
+pub fn restore_deleted(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+
+    runtime::block_on(async { prompts::restore_deleted(id).await })?;
+
     Ok(json!({ "success": true }))
 }
 
+/// Permanently delete trashed prompts
+///
+/// Args: optional `older_than_days`, to keep recently trashed prompts.
+pub fn purge(args: Value) -> Result<Value> {
+    let older_than = args
+        .get("older_than_days")
+        .and_then(|v| v.as_f64())
+        .map(|days| (days * 24.0 * 60.0 * 60.0) as i64);
+
+    let purged = runtime::block_on(async { prompts::purge_deleted(older_than).await })?;
+
+    Ok(json!({ "success": true, "purged": purged }))
+}
+
 pub fn use_prompt(args: Value) -> Result<Value> {
     let id = args
         .get("id")
@@ -96,3 +234,242 @@ pub fn use_prompt(args: Value) -> Result<Value> {
 
     Ok(json!({ "success": true, "background": true }))
 }
+
+/// Per-day usage counts
+///
+/// Args: optional `id` to restrict to one prompt and `days` (default 30).
+pub fn usage(args: Value) -> Result<Value> {
+    let id = args.get("id").and_then(|v| v.as_str());
+    let days = args.get("days").and_then(|v| v.as_i64()).unwrap_or(30);
+
+    let usage = runtime::block_on(async { prompts::usage_by_day(id, days).await })?;
+
+    Ok(json!({ "usage": usage }))
+}
+
+/// List the revisions of a prompt, newest first
+pub fn history(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+
+    let revisions = runtime::block_on(async { revisions::list_revisions(id).await })?;
+
+    Ok(json!({ "revisions": revisions }))
+}
+
+/// Line diff between two revisions of a prompt
+///
+/// Args: `id`, optional `from` and `to` revision numbers (default: the latest
+/// revision against the one before it).
+pub fn diff(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let from = args.get("from").and_then(|v| v.as_i64());
+    let to = args.get("to").and_then(|v| v.as_i64());
+
+    let diff = runtime::block_on(async { revisions::diff_revisions(id, from, to).await })?;
+
+    Ok(json!(diff))
+}
+
+/// Restore a prompt to an earlier revision (recorded as a new revision)
+pub fn restore(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let revision = args
+        .get("revision")
+        .and_then(|v| v.as_i64())
+        .ok_or("Missing revision")?;
+
+    let prompt = runtime::block_on(async { revisions::restore_revision(id, revision).await })?;
+
+    Ok(json!(prompt))
+}
+
+/// Export prompts as JSON, CSV or Markdown
+///
+/// Args: `format` (defaults to a guess from `path`, else json), `path` (a
+/// directory for Markdown) and the tag filters of `prompts.list`. Without a
+/// `path` the export is returned as `content`.
+pub fn export(args: Value) -> Result<Value> {
+    let path = args.get("path").and_then(|v| v.as_str()).map(Path::new);
+    let format = match args.get("format").and_then(|v| v.as_str()) {
+        Some(name) => Format::parse(name)?,
+        None => path.map(Format::detect).unwrap_or(Format::Json),
+    };
+    let query = prompt_query(&args)?;
+
+    let records = runtime::block_on(async { transfer::export_records(&query).await })?;
+
+    match path {
+        Some(path) => {
+            transfer::write(&records, path, format)?;
+            Ok(json!({ "count": records.len(), "path": path }))
+        },
+        None => Ok(json!({
+            "count": records.len(),
+            "content": transfer::encode(&records, format)?,
+        })),
+    }
+}
+
+/// Import prompts from a file, a Markdown directory or inline `content`
+///
+/// Args: `path` or `content`, `format` (guessed from `path` if omitted) and
+/// `strategy` for prompts whose id already exists (skip, overwrite or
+/// duplicate; default skip).
+pub fn import(args: Value) -> Result<Value> {
+    let format = args
+        .get("format")
+        .and_then(|v| v.as_str())
+        .map(Format::parse)
+        .transpose()?;
+    let strategy = match args.get("strategy").and_then(|v| v.as_str()) {
+        Some(name) => ConflictStrategy::parse(name)?,
+        None => ConflictStrategy::default(),
+    };
+
+    let records = if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
+        let path = Path::new(path);
+        transfer::read(path, format.unwrap_or_else(|| Format::detect(path)))?
+    } else {
+        let content = args
+            .get("content")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing path or content")?;
+        transfer::decode(content, format.unwrap_or(Format::Json))?
+    };
+
+    let report = runtime::block_on(async { transfer::import_records(records, strategy).await })?;
+
+    Ok(json!(report))
+}
+
+/// Render a prompt template (by `id`, or ad-hoc `content` + `declarations`)
+///
+/// Args: `variables` (map of name -> value) and `context` (`filename`,
+/// `filetype`, `selection`, `diagnostics`).
+pub fn render(args: Value) -> Result<Value> {
+    let (content, declarations) = template_source(&args)?;
+
+    let variables: HashMap<String, Value> = match args.get("variables") {
+        Some(Value::Object(map)) => map.clone().into_iter().collect(),
+        _ => HashMap::new(),
+    };
+    let context: EditorContext = match args.get("context") {
+        Some(value) if !value.is_null() => serde_json::from_value(value.clone())?,
+        _ => EditorContext::default(),
+    };
+
+    let text = templates::render(&content, &declarations, &variables, &context)?;
+    Ok(json!({ "text": text }))
+}
+
+/// Describe the variables of a prompt template, so the UI can ask for values
+///
+/// Returns the declarations plus `undeclared` placeholders and which
+/// placeholders are filled from the editor context.
+pub fn variables(args: Value) -> Result<Value> {
+    let (content, declarations) = template_source(&args)?;
+    let placeholders = templates::placeholders(&content);
+
+    let undeclared: Vec<&String> = placeholders
+        .iter()
+        .filter(|name| !declarations.iter().any(|d| &d.name == *name))
+        .filter(|name| !templates::CONTEXT_VARIABLES.contains(&name.as_str()))
+        .collect();
+    let context: Vec<&String> = placeholders
+        .iter()
+        .filter(|name| templates::CONTEXT_VARIABLES.contains(&name.as_str()))
+        .collect();
+
+    Ok(json!({
+        "declared": declarations,
+        "undeclared": undeclared,
+        "context": context,
+    }))
+}
+
+/// Load template content and declarations from `id`, or from inline
+/// `content` and `declarations`
+fn template_source(args: &Value) -> Result<(String, Vec<VariableDecl>)> {
+    if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
+        // Project prompts shadow global ones with the same id, as in `list`
+        let prompt = match project::find(&cwd(args)?, &project::settings(), id)? {
+            Some(prompt) => Some(prompt),
+            None => runtime::block_on(async { prompts::get_prompt(id).await })?,
+        }
+        .ok_or_else(|| AmpError::ValidationError(format!("Prompt '{}' not found", id)))?;
+        let declarations = prompt.variable_decls()?;
+        return Ok((prompt.content, declarations));
+    }
+
+    let content = args
+        .get("content")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id or content")?;
+    let declarations = match args.get("declarations") {
+        Some(value @ Value::Array(_)) => serde_json::from_value(value.clone())?,
+        _ => vec![],
+    };
+    Ok((content.to_string(), declarations))
+}
+
+/// Read the optional `variables` declarations argument of create/update
+fn variable_decls(args: &Value) -> Result<Option<Vec<VariableDecl>>> {
+    match args.get("variables") {
+        Some(value @ Value::Array(_)) => Ok(Some(serde_json::from_value(value.clone())?)),
+        _ => Ok(None),
+    }
+}
+
+/// Workspace directory for project prompts (`cwd` argument, else the process
+/// working directory, which follows `:cd` in Neovim)
+fn cwd(args: &Value) -> Result<PathBuf> {
+    match args.get("cwd").and_then(|v| v.as_str()) {
+        Some(cwd) => Ok(PathBuf::from(cwd)),
+        None => Ok(std::env::current_dir()?),
+    }
+}
+
+/// Read the filters and `sort` shared by `prompts.list` and `prompts.export`
+fn prompt_query(args: &Value) -> Result<prompts::PromptQuery> {
+    let sort = match args.get("sort").and_then(|v| v.as_str()) {
+        Some(name) => prompts::PromptSort::parse(name)?,
+        None => prompts::PromptSort::default(),
+    };
+
+    let collection = match args.get("collection_id").and_then(|v| v.as_str()) {
+        Some(id) => Some(prompts::CollectionFilter::In(id.to_string())),
+        None if args.get("unfiled").and_then(|v| v.as_bool()) == Some(true) => {
+            Some(prompts::CollectionFilter::Unfiled)
+        },
+        None => None,
+    };
+
+    Ok(prompts::PromptQuery {
+        tag: args.get("tag").and_then(|v| v.as_str()).map(String::from),
+        any_tags: string_list(args, "any_tags"),
+        all_tags: string_list(args, "all_tags"),
+        collection,
+        sort,
+    })
+}
+
+/// Read an optional array of strings argument (missing -> empty)
+fn string_list(args: &Value, key: &str) -> Vec<String> {
+    args.get(key)
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
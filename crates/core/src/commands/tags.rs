@@ -0,0 +1,53 @@
+use crate::{db::tags, errors::Result, runtime};
+use serde_json::{json, Value};
+
+pub fn list(args: Value) -> Result<Value> {
+    let include_unused = args
+        .get("include_unused")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let tags = runtime::block_on(async { tags::list_tags(include_unused).await })?;
+    Ok(json!({ "tags": tags }))
+}
+
+pub fn rename(args: Value) -> Result<Value> {
+    let from = args
+        .get("from")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing from")?;
+    let to = args
+        .get("to")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing to")?;
+
+    let affected = runtime::block_on(async { tags::rename_tag(from, to).await })?;
+    Ok(json!({ "success": true, "affected": affected }))
+}
+
+pub fn merge(args: Value) -> Result<Value> {
+    let sources: Vec<String> = args
+        .get("sources")
+        .and_then(|v| v.as_array())
+        .ok_or("Missing sources")?
+        .iter()
+        .filter_map(|v| v.as_str().map(String::from))
+        .collect();
+    let target = args
+        .get("target")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing target")?;
+
+    let affected = runtime::block_on(async { tags::merge_tags(&sources, target).await })?;
+    Ok(json!({ "success": true, "affected": affected }))
+}
+
+pub fn delete(args: Value) -> Result<Value> {
+    let name = args
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing name")?;
+
+    let affected = runtime::block_on(async { tags::delete_tag(name).await })?;
+    Ok(json!({ "success": true, "affected": affected }))
+}
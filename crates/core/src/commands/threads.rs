@@ -0,0 +1,351 @@
+use super::prompts::search_options;
+use crate::{
+    errors::{AmpError, Result},
+    runtime,
+    threads::{
+        self,
+        compare::{self, MessageRange},
+        export, index,
+        launch::{self, LaunchAction},
+        manage::{self, Area, Selection, ThreadAreas, ThreadFilter},
+        model::Thread,
+        render, store, watcher,
+    },
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Default page size of `threads.list`
+const DEFAULT_LIMIT: usize = 50;
+
+pub fn list(args: Value) -> Result<Value> {
+    let sort = match args.get("sort").and_then(|v| v.as_str()) {
+        Some(name) => store::ThreadSort::parse(name)?,
+        None => store::ThreadSort::default(),
+    };
+    let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let limit = args
+        .get("limit")
+        .and_then(|v| v.as_u64())
+        .map_or(DEFAULT_LIMIT, |l| l as usize);
+
+    let dir = area_dir(&args)?;
+    let query = store::ThreadQuery {
+        sort,
+        offset,
+        limit: Some(limit),
+    };
+    let page = store::list_threads(&dir, &query)?;
+
+    Ok(json!({
+        "threads": page.threads,
+        "total": page.total,
+        "offset": offset,
+        "limit": limit,
+        "dir": dir,
+        "errors": page.errors,
+    }))
+}
+
+/// Search thread history, first bringing the index up to date
+///
+/// Pass `refresh = false` to search the index as it is (the background
+/// indexer keeps it close to current).
+pub fn search(args: Value) -> Result<Value> {
+    let query = args
+        .get("query")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing query")?;
+    let refresh = args
+        .get("refresh")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let options = search_options(&args);
+
+    let index = index::global()?;
+    let errors = if refresh {
+        runtime::block_on(async { index.refresh(&threads::threads_dir(), false).await })?.errors
+    } else {
+        Vec::new()
+    };
+
+    let hits = runtime::block_on(async { index.search(query, &options).await })?;
+
+    Ok(json!({ "results": hits, "errors": errors }))
+}
+
+/// Threads that read, edited or attached a file, most recent first
+///
+/// Args: `path` (relative paths are taken from the current directory),
+/// `limit` (default 50) and `refresh` (default true) as for `search`.
+pub fn for_file(args: Value) -> Result<Value> {
+    let path = args
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing path")?;
+    let limit = args
+        .get("limit")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(DEFAULT_LIMIT as i64);
+    let refresh = args
+        .get("refresh")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let path = std::path::absolute(path)?;
+
+    let index = index::global()?;
+    let errors = if refresh {
+        runtime::block_on(async { index.refresh(&threads::threads_dir(), false).await })?.errors
+    } else {
+        Vec::new()
+    };
+
+    let threads =
+        runtime::block_on(async { index.threads_for_file(&path.to_string_lossy(), limit).await })?;
+
+    Ok(json!({ "path": path, "threads": threads, "errors": errors }))
+}
+
+pub fn reindex(args: Value) -> Result<Value> {
+    let full = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let index = index::global()?;
+    let stats = runtime::block_on(async { index.refresh(&threads::threads_dir(), full).await })?;
+    Ok(json!(stats))
+}
+
+/// Take the change events queued by the thread watcher (for polling instead
+/// of the `User AmpThreadsChanged` autocommand)
+pub fn events(_args: Value) -> Result<Value> {
+    Ok(json!({
+        "watching": watcher::watched_dir(),
+        "events": watcher::take_events(),
+    }))
+}
+
+/// Render a thread as Markdown lines for a read-only buffer
+///
+/// Args: `id`, optional `area`, `thinking`, `tool_results` and
+/// `max_tool_lines`. Alongside the lines come the line span of each message,
+/// the `<details>` sections as folds and the files the thread references.
+pub fn render(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+
+    let defaults = render::RenderOptions::default();
+    let options = render::RenderOptions {
+        thinking: args
+            .get("thinking")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(defaults.thinking),
+        tool_results: args
+            .get("tool_results")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(defaults.tool_results),
+        max_tool_lines: match args.get("max_tool_lines") {
+            Some(Value::Null) => None,
+            Some(v) => Some(v.as_u64().ok_or("Invalid max_tool_lines")? as usize),
+            None => defaults.max_tool_lines,
+        },
+    };
+
+    let thread = store::get_thread(&area_dir(&args)?, id)?;
+    let rendered = render::render(&thread, &options);
+
+    Ok(json!({
+        "id": thread.id,
+        "title": thread.display_title(),
+        "lines": rendered.lines,
+        "messages": rendered.messages,
+        "folds": rendered.folds,
+        "files": rendered.files,
+    }))
+}
+
+/// Export a thread as Markdown, HTML or JSONL (stream JSON messages)
+///
+/// Args: `id`, optional `area`, `format` (defaults to a guess from `path`,
+/// else markdown), `path`, `redact_thinking` and `redact_tool_output`.
+/// Without a `path` the export is returned as `content`.
+pub fn export(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let path = args.get("path").and_then(|v| v.as_str()).map(Path::new);
+    let format = match args.get("format").and_then(|v| v.as_str()) {
+        Some(name) => export::Format::parse(name)?,
+        None => path
+            .map(export::Format::detect)
+            .unwrap_or(export::Format::Markdown),
+    };
+    let options = export::ExportOptions::deserialize(&args)?;
+
+    let thread = store::get_thread(&area_dir(&args)?, id)?;
+
+    match path {
+        Some(path) => {
+            export::write(&thread, path, format, &options)?;
+            Ok(json!({ "format": format, "path": path }))
+        },
+        None => Ok(json!({
+            "format": format,
+            "content": export::encode(&thread, format, &options)?,
+        })),
+    }
+}
+
+/// Compare two threads, or two message ranges of one thread
+///
+/// Args: `left` and `right`, each a thread id or `{ id, from?, to?, area? }`
+/// where `from` and `to` are message ids (inclusive).
+pub fn compare(args: Value) -> Result<Value> {
+    let (left, left_range) = compare_side(&args, "left")?;
+    let (right, right_range) = compare_side(&args, "right")?;
+
+    let comparison = compare::compare(&left, left_range, &right, right_range);
+    Ok(json!(comparison))
+}
+
+fn compare_side(args: &Value, key: &str) -> Result<(Thread, MessageRange)> {
+    let side = args.get(key).ok_or_else(|| format!("Missing {}", key))?;
+    if let Some(id) = side.as_str() {
+        let thread = store::get_thread(&area_dir(&Value::Null)?, id)?;
+        return Ok((thread, MessageRange::default()));
+    }
+
+    let id = side
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("Missing {}.id", key))?;
+    let thread = store::get_thread(&area_dir(side)?, id)?;
+    Ok((thread, MessageRange::deserialize(side)?))
+}
+
+/// Add a message to a thread with `amp threads continue` (execute mode)
+///
+/// Args: `id` (a thread id or URL), `message` and optional `cwd` (defaults
+/// to the thread's workspace). Returns the tracked process; see `processes`.
+pub fn continue_thread(args: Value) -> Result<Value> {
+    launch_thread(&args, LaunchAction::Continue)
+}
+
+/// Fork a thread and add a message to the fork with `amp threads fork`
+///
+/// Same args as `continue`.
+pub fn fork(args: Value) -> Result<Value> {
+    launch_thread(&args, LaunchAction::Fork)
+}
+
+fn launch_thread(args: &Value, action: LaunchAction) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing id")?;
+    let message = args
+        .get("message")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing message")?;
+    let cwd = args.get("cwd").and_then(|v| v.as_str()).map(Path::new);
+
+    let process = launch::launch(action, id, message, cwd)?;
+    Ok(json!(process))
+}
+
+/// Amp CLI processes started by `continue` and `fork` (or one, with `id`)
+pub fn processes(args: Value) -> Result<Value> {
+    if let Some(id) = args.get("id").and_then(|v| v.as_u64()) {
+        let process = launch::process(id)
+            .ok_or_else(|| AmpError::ValidationError(format!("Process {} not found", id)))?;
+        return Ok(json!(process));
+    }
+    Ok(json!({ "processes": launch::processes() }))
+}
+
+pub fn kill(args: Value) -> Result<Value> {
+    let id = args
+        .get("id")
+        .and_then(|v| v.as_u64())
+        .ok_or("Missing id")?;
+    Ok(json!({ "killed": launch::kill(id) }))
+}
+
+/// Move threads to the archive
+///
+/// Args: `ids` (or `id`), or a `filter` with `older_than_days` and/or
+/// `workspace`; optional `dry_run` to only report the matching threads. The
+/// same selection arguments apply to `unarchive`, `delete`, `restore` and
+/// `purge`.
+pub fn archive(args: Value) -> Result<Value> {
+    bulk(&args, manage::archive)
+}
+
+pub fn unarchive(args: Value) -> Result<Value> {
+    bulk(&args, manage::unarchive)
+}
+
+/// Move threads (active or archived) to the trash
+pub fn delete(args: Value) -> Result<Value> {
+    bulk(&args, manage::delete)
+}
+
+/// Move trashed threads back to where they were deleted from
+pub fn restore(args: Value) -> Result<Value> {
+    bulk(&args, manage::restore)
+}
+
+/// Permanently delete trashed threads
+pub fn purge(args: Value) -> Result<Value> {
+    bulk(&args, manage::purge)
+}
+
+pub fn trash(_args: Value) -> Result<Value> {
+    let areas = ThreadAreas::new(&threads::threads_dir());
+    let (threads, errors) = manage::list_trash(&areas)?;
+    Ok(json!({ "threads": threads, "errors": errors }))
+}
+
+/// Directory of the `area` argument (the active threads by default)
+fn area_dir(args: &Value) -> Result<PathBuf> {
+    let area = match args.get("area").and_then(|v| v.as_str()) {
+        Some(name) => Area::parse(name)?,
+        None => Area::Threads,
+    };
+    Ok(ThreadAreas::new(&threads::threads_dir())
+        .dir(area)
+        .to_path_buf())
+}
+
+type BulkOp = fn(&ThreadAreas, &Selection, bool) -> Result<manage::BulkResult>;
+
+fn bulk(args: &Value, op: BulkOp) -> Result<Value> {
+    let selection = selection(args)?;
+    let dry_run = args
+        .get("dry_run")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let areas = ThreadAreas::new(&threads::threads_dir());
+    let result = op(&areas, &selection, dry_run)?;
+    Ok(json!(result))
+}
+
+fn selection(args: &Value) -> Result<Selection> {
+    if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
+        return Ok(Selection::Ids(vec![id.to_string()]));
+    }
+    if let Some(ids) = args.get("ids").and_then(|v| v.as_array()) {
+        return Ok(Selection::Ids(
+            ids.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect(),
+        ));
+    }
+    match args.get("filter") {
+        Some(filter) => Ok(Selection::Filter(ThreadFilter::deserialize(filter)?)),
+        None => Err("Missing ids or filter".into()),
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    errors::{AmpError, Result},
+    threads::{
+        self,
+        manage::{Area, ThreadAreas},
+        usage::{self, UsageRange},
+    },
+};
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+
+/// Token and credit usage by day, workspace, agent mode and thread
+///
+/// Args: `from` and `to` (`YYYY-MM-DD`, inclusive, UTC) and
+/// `include_archived` to also count archived threads.
+pub fn summary(args: Value) -> Result<Value> {
+    let range = UsageRange::days(date(&args, "from")?, date(&args, "to")?);
+    let include_archived = args
+        .get("include_archived")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let areas = ThreadAreas::new(&threads::threads_dir());
+    let mut dirs = vec![areas.dir(Area::Threads)];
+    if include_archived {
+        dirs.push(areas.dir(Area::Archive));
+    }
+
+    let summary = usage::summarize(&dirs, range)?;
+    Ok(json!(summary))
+}
+
+fn date(args: &Value, key: &str) -> Result<Option<NaiveDate>> {
+    let Some(text) = args.get(key).and_then(|v| v.as_str()) else {
+        return Ok(None);
+    };
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| {
+            AmpError::ValidationError(format!(
+                "Invalid {} date '{}' (expected YYYY-MM-DD)",
+                key, text
+            ))
+        })
+}
@@ -0,0 +1,509 @@
+use super::Db;
+use crate::errors::{AmpError, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+use uuid::Uuid;
+
+/// A named folder of prompts; collections nest through `parent_id`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    /// `None` for top-level collections
+    pub parent_id: Option<String>,
+    /// Order among siblings
+    pub position: i64,
+    /// Prompts directly in this collection (trashed prompts excluded)
+    pub prompt_count: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A collection with its sub-collections, for rendering a tree
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionNode {
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub children: Vec<CollectionNode>,
+}
+
+/// List all collections, parents before children and siblings in order
+pub async fn list_collections() -> Result<Vec<Collection>> {
+    let pool = Db::pool()?;
+    let collections = sqlx::query_as::<_, Collection>(
+        "WITH RECURSIVE tree (id, depth, path) AS (
+             SELECT id, 0, printf('%08d', position) FROM collections WHERE parent_id IS NULL
+             UNION ALL
+             SELECT c.id, tree.depth + 1, tree.path || '/' || printf('%08d', c.position)
+             FROM collections c JOIN tree ON c.parent_id = tree.id
+         )
+         SELECT c.id, c.name, c.parent_id, c.position, c.created_at, c.updated_at,
+                (SELECT COUNT(*) FROM prompts p
+                 WHERE p.collection_id = c.id AND p.deleted_at IS NULL) AS prompt_count
+         FROM tree JOIN collections c ON c.id = tree.id
+         ORDER BY tree.path, c.name",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(collections)
+}
+
+/// All collections as a tree of top-level nodes
+pub async fn collection_tree() -> Result<Vec<CollectionNode>> {
+    Ok(build_tree(list_collections().await?, None))
+}
+
+fn build_tree(collections: Vec<Collection>, parent_id: Option<&str>) -> Vec<CollectionNode> {
+    let (children, rest): (Vec<_>, Vec<_>) = collections
+        .into_iter()
+        .partition(|c| c.parent_id.as_deref() == parent_id);
+
+    children
+        .into_iter()
+        .map(|collection| {
+            let descendants = rest
+                .iter()
+                .filter(|c| is_descendant(&rest, c, &collection.id))
+                .cloned()
+                .collect();
+            let children = build_tree(descendants, Some(&collection.id));
+            CollectionNode {
+                collection,
+                children,
+            }
+        })
+        .collect()
+}
+
+fn is_descendant(all: &[Collection], collection: &Collection, ancestor_id: &str) -> bool {
+    let mut parent = collection.parent_id.as_deref();
+    while let Some(id) = parent {
+        if id == ancestor_id {
+            return true;
+        }
+        parent = all
+            .iter()
+            .find(|c| c.id == id)
+            .and_then(|c| c.parent_id.as_deref());
+    }
+    false
+}
+
+/// Create a collection at the end of its siblings
+pub async fn create_collection(name: &str, parent_id: Option<&str>) -> Result<Collection> {
+    let name = validate_name(name)?;
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    if let Some(parent_id) = parent_id {
+        require_collection(&mut tx, parent_id).await?;
+    }
+    require_unique_name(&mut tx, name, parent_id, None).await?;
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().timestamp();
+    let position = next_position(&mut tx, parent_id).await?;
+
+    sqlx::query(
+        "INSERT INTO collections (id, name, parent_id, position, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(name)
+    .bind(parent_id)
+    .bind(position)
+    .bind(now)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Collection {
+        id,
+        name: name.to_string(),
+        parent_id: parent_id.map(String::from),
+        position,
+        prompt_count: 0,
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+pub async fn rename_collection(id: &str, name: &str) -> Result<()> {
+    let name = validate_name(name)?;
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let parent_id = require_collection(&mut tx, id).await?;
+    require_unique_name(&mut tx, name, parent_id.as_deref(), Some(id)).await?;
+
+    sqlx::query("UPDATE collections SET name = ?, updated_at = ? WHERE id = ?")
+        .bind(name)
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Move a collection under `parent_id` (top level if `None`)
+///
+/// It is inserted at `position` among its new siblings, or at the end.
+pub async fn move_collection(
+    id: &str,
+    parent_id: Option<&str>,
+    position: Option<usize>,
+) -> Result<()> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    require_collection(&mut tx, id).await?;
+    if let Some(parent_id) = parent_id {
+        require_collection(&mut tx, parent_id).await?;
+        if ancestors(&mut tx, parent_id).await?.iter().any(|a| a == id) {
+            return Err(AmpError::ValidationError(
+                "A collection cannot be moved into itself or its descendants".to_string(),
+            ));
+        }
+    }
+
+    let name: String = sqlx::query_scalar("SELECT name FROM collections WHERE id = ?")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    require_unique_name(&mut tx, &name, parent_id, Some(id)).await?;
+
+    sqlx::query("UPDATE collections SET parent_id = ?, updated_at = ? WHERE id = ?")
+        .bind(parent_id)
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut siblings = child_ids(&mut tx, parent_id).await?;
+    siblings.retain(|s| s != id);
+    let index = position.unwrap_or(siblings.len()).min(siblings.len());
+    siblings.insert(index, id.to_string());
+    write_collection_positions(&mut tx, &siblings).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Set the order of the sub-collections of `parent_id`
+///
+/// `ids` must list every child exactly once.
+pub async fn reorder_collections(parent_id: Option<&str>, ids: &[String]) -> Result<()> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let current = child_ids(&mut tx, parent_id).await?;
+    require_permutation(&current, ids, "collections")?;
+    write_collection_positions(&mut tx, ids).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Delete a collection
+///
+/// Its sub-collections and prompts move up to its parent, after the
+/// parent's existing entries. Returns the number of prompts moved.
+pub async fn delete_collection(id: &str) -> Result<u64> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let parent_id = require_collection(&mut tx, id).await?;
+
+    let mut siblings = child_ids(&mut tx, parent_id.as_deref()).await?;
+    siblings.retain(|s| s != id);
+    let children = child_ids(&mut tx, Some(id)).await?;
+    for child in &children {
+        sqlx::query("UPDATE collections SET parent_id = ? WHERE id = ?")
+            .bind(&parent_id)
+            .bind(child)
+            .execute(&mut *tx)
+            .await?;
+    }
+    siblings.extend(children);
+
+    let mut prompts = prompt_ids(&mut tx, parent_id.as_deref()).await?;
+    let moved = prompt_ids(&mut tx, Some(id)).await?;
+    for prompt_id in &moved {
+        sqlx::query("UPDATE prompts SET collection_id = ? WHERE id = ?")
+            .bind(&parent_id)
+            .bind(prompt_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    prompts.extend(moved.iter().cloned());
+
+    sqlx::query("DELETE FROM collections WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    write_collection_positions(&mut tx, &siblings).await?;
+    write_prompt_positions(&mut tx, &prompts).await?;
+
+    tx.commit().await?;
+    Ok(moved.len() as u64)
+}
+
+/// Put a prompt in a collection (unfiled if `None`)
+///
+/// It is inserted at `position` among the collection's prompts outside the
+/// trash, or at the end.
+pub async fn move_prompt(
+    prompt_id: &str,
+    collection_id: Option<&str>,
+    position: Option<usize>,
+) -> Result<()> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let exists: Option<String> = sqlx::query_scalar("SELECT id FROM prompts WHERE id = ?")
+        .bind(prompt_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if exists.is_none() {
+        return Err(AmpError::ValidationError(format!(
+            "Prompt '{}' not found",
+            prompt_id
+        )));
+    }
+    if let Some(collection_id) = collection_id {
+        require_collection(&mut tx, collection_id).await?;
+    }
+
+    sqlx::query("UPDATE prompts SET collection_id = ? WHERE id = ?")
+        .bind(collection_id)
+        .bind(prompt_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut slots = prompt_slots(&mut tx, collection_id).await?;
+    slots.retain(|(id, _)| id != prompt_id);
+    // Before the live prompt now at `position`
+    let index = position
+        .and_then(|position| {
+            slots
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, trashed))| !trashed)
+                .nth(position)
+        })
+        .map_or(slots.len(), |(index, _)| index);
+    let mut prompts: Vec<String> = slots.into_iter().map(|(id, _)| id).collect();
+    prompts.insert(index, prompt_id.to_string());
+    write_prompt_positions(&mut tx, &prompts).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Set the order of the prompts in a collection (unfiled prompts if `None`)
+///
+/// `ids` must list every prompt of the collection outside the trash exactly
+/// once. Trashed prompts keep their slots, so they come back in place when
+/// restored.
+pub async fn reorder_prompts(collection_id: Option<&str>, ids: &[String]) -> Result<()> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let slots = prompt_slots(&mut tx, collection_id).await?;
+    let live: Vec<String> = slots
+        .iter()
+        .filter(|(_, trashed)| !trashed)
+        .map(|(id, _)| id.clone())
+        .collect();
+    require_permutation(&live, ids, "prompts")?;
+
+    let mut ids = ids.iter();
+    let prompts: Vec<String> = slots
+        .into_iter()
+        .filter_map(|(id, trashed)| {
+            if trashed {
+                Some(id)
+            } else {
+                ids.next().cloned()
+            }
+        })
+        .collect();
+    write_prompt_positions(&mut tx, &prompts).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Position after the last prompt of a collection, for newly filed prompts
+pub(crate) async fn next_prompt_position(
+    conn: &mut SqliteConnection,
+    collection_id: Option<&str>,
+) -> Result<i64> {
+    let max: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(collection_position) FROM prompts WHERE collection_id IS ?")
+            .bind(collection_id)
+            .fetch_one(&mut *conn)
+            .await?;
+    Ok(max.map_or(0, |m| m + 1))
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AmpError::ValidationError(
+            "Collection name cannot be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+/// Check a collection exists and return its parent id
+pub(crate) async fn require_collection(
+    conn: &mut SqliteConnection,
+    id: &str,
+) -> Result<Option<String>> {
+    let row: Option<(Option<String>,)> =
+        sqlx::query_as("SELECT parent_id FROM collections WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    row.map(|(parent_id,)| parent_id)
+        .ok_or_else(|| AmpError::ValidationError(format!("Collection '{}' not found", id)))
+}
+
+/// Sibling collections must have distinct names (case-insensitive)
+async fn require_unique_name(
+    conn: &mut SqliteConnection,
+    name: &str,
+    parent_id: Option<&str>,
+    except_id: Option<&str>,
+) -> Result<()> {
+    let clash: Option<String> = sqlx::query_scalar(
+        "SELECT id FROM collections
+         WHERE parent_id IS ? AND name = ? COLLATE NOCASE AND id IS NOT ?",
+    )
+    .bind(parent_id)
+    .bind(name)
+    .bind(except_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match clash {
+        Some(_) => Err(AmpError::ValidationError(format!(
+            "A collection named '{}' already exists here",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ids of `id` and all its ancestors
+async fn ancestors(conn: &mut SqliteConnection, id: &str) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar(
+        "WITH RECURSIVE up (id, parent_id) AS (
+             SELECT id, parent_id FROM collections WHERE id = ?
+             UNION
+             SELECT c.id, c.parent_id FROM collections c JOIN up ON c.id = up.parent_id
+         )
+         SELECT id FROM up",
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(ids)
+}
+
+async fn next_position(conn: &mut SqliteConnection, parent_id: Option<&str>) -> Result<i64> {
+    let max: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(position) FROM collections WHERE parent_id IS ?")
+            .bind(parent_id)
+            .fetch_one(&mut *conn)
+            .await?;
+    Ok(max.map_or(0, |m| m + 1))
+}
+
+async fn child_ids(conn: &mut SqliteConnection, parent_id: Option<&str>) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar(
+        "SELECT id FROM collections WHERE parent_id IS ? ORDER BY position, name",
+    )
+    .bind(parent_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(ids)
+}
+
+/// Prompts of a collection in order (trashed prompts included, so they keep
+/// their place when restored)
+async fn prompt_ids(
+    conn: &mut SqliteConnection,
+    collection_id: Option<&str>,
+) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar(
+        "SELECT id FROM prompts WHERE collection_id IS ?
+         ORDER BY collection_position, title COLLATE NOCASE",
+    )
+    .bind(collection_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(ids)
+}
+
+/// Prompts of a collection in order, with whether each is in the trash
+async fn prompt_slots(
+    conn: &mut SqliteConnection,
+    collection_id: Option<&str>,
+) -> Result<Vec<(String, bool)>> {
+    let slots = sqlx::query_as(
+        "SELECT id, deleted_at IS NOT NULL FROM prompts WHERE collection_id IS ?
+         ORDER BY collection_position, title COLLATE NOCASE",
+    )
+    .bind(collection_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(slots)
+}
+
+async fn write_collection_positions(conn: &mut SqliteConnection, ids: &[String]) -> Result<()> {
+    for (position, id) in ids.iter().enumerate() {
+        sqlx::query("UPDATE collections SET position = ? WHERE id = ?")
+            .bind(position as i64)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn write_prompt_positions(conn: &mut SqliteConnection, ids: &[String]) -> Result<()> {
+    for (position, id) in ids.iter().enumerate() {
+        sqlx::query("UPDATE prompts SET collection_position = ? WHERE id = ?")
+            .bind(position as i64)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+fn require_permutation(current: &[String], ids: &[String], what: &str) -> Result<()> {
+    let mut expected = current.to_vec();
+    let mut given = ids.to_vec();
+    expected.sort();
+    given.sort();
+
+    if expected != given {
+        return Err(AmpError::ValidationError(format!(
+            "The new order must list each of the {} {} exactly once",
+            current.len(),
+            what
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,230 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::collections::{
+        collection_tree, create_collection, delete_collection, list_collections, move_collection,
+        move_prompt, rename_collection, reorder_collections, reorder_prompts,
+    };
+    use crate::db::prompts::{
+        create_prompt, delete_prompt, insert_prompt, query_prompts, restore_deleted,
+        CollectionFilter, NewPrompt, PromptQuery, PromptSort,
+    };
+    use crate::db::test_support::setup;
+    use crate::errors::Result;
+    use crate::runtime;
+
+    async fn filed_titles(collection: CollectionFilter) -> Result<Vec<String>> {
+        let query = PromptQuery {
+            collection: Some(collection),
+            sort: PromptSort::Manual,
+            ..Default::default()
+        };
+        Ok(query_prompts(&query)
+            .await?
+            .into_iter()
+            .map(|p| p.title)
+            .collect())
+    }
+
+    #[test]
+    fn test_nested_tree_and_names() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let work = create_collection("Work", None).await?;
+            let personal = create_collection("Personal", None).await?;
+            let review = create_collection("Review", Some(&work.id)).await?;
+
+            let err = create_collection("work", None).await.unwrap_err();
+            assert_eq!(err.category(), "validation");
+            assert!(create_collection("  ", None).await.is_err());
+            assert!(create_collection("x", Some("missing")).await.is_err());
+            // The same name is fine under another parent
+            create_collection("Review", Some(&personal.id)).await?;
+
+            let names: Vec<_> = list_collections()
+                .await?
+                .into_iter()
+                .map(|c| c.name)
+                .collect();
+            assert_eq!(names, vec!["Work", "Review", "Personal", "Review"]);
+
+            let tree = collection_tree().await?;
+            assert_eq!(tree.len(), 2);
+            assert_eq!(tree[0].collection.id, work.id);
+            assert_eq!(tree[0].children[0].collection.id, review.id);
+
+            rename_collection(&review.id, "Code review").await?;
+            assert!(rename_collection(&personal.id, "WORK").await.is_err());
+            // Renaming to its own name in another case is allowed
+            rename_collection(&work.id, "WORK").await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_move_and_reorder_collections() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let a = create_collection("A", None).await?;
+            let b = create_collection("B", None).await?;
+            let c = create_collection("C", Some(&a.id)).await?;
+
+            // No cycles
+            assert!(move_collection(&a.id, Some(&c.id), None).await.is_err());
+            assert!(move_collection(&a.id, Some(&a.id), None).await.is_err());
+
+            move_collection(&c.id, None, Some(0)).await?;
+            let top: Vec<_> = collection_tree()
+                .await?
+                .into_iter()
+                .map(|n| n.collection.name)
+                .collect();
+            assert_eq!(top, vec!["C", "A", "B"]);
+
+            reorder_collections(None, &[b.id.clone(), a.id.clone(), c.id.clone()]).await?;
+            let top: Vec<_> = collection_tree()
+                .await?
+                .into_iter()
+                .map(|n| n.collection.name)
+                .collect();
+            assert_eq!(top, vec!["B", "A", "C"]);
+
+            // Every sibling must be listed
+            let err = reorder_collections(None, &[b.id.clone(), a.id.clone()])
+                .await
+                .unwrap_err();
+            assert_eq!(err.category(), "validation");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_prompt_filing_and_order() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let work = create_collection("Work", None).await?;
+            let filed = |title: &str| NewPrompt {
+                title: title.into(),
+                content: "x".into(),
+                collection_id: Some(work.id.clone()),
+                ..Default::default()
+            };
+            let one = insert_prompt(filed("One")).await?;
+            let two = insert_prompt(filed("Two")).await?;
+            let loose = create_prompt("Loose".into(), None, "x".into(), None, None).await?;
+            assert_eq!(two.collection_position, 1);
+
+            let in_work = CollectionFilter::In(work.id.clone());
+            assert_eq!(filed_titles(in_work.clone()).await?, vec!["One", "Two"]);
+            assert_eq!(
+                filed_titles(CollectionFilter::Unfiled).await?,
+                vec!["Loose"]
+            );
+
+            move_prompt(&loose.id, Some(&work.id), Some(0)).await?;
+            assert_eq!(
+                filed_titles(in_work.clone()).await?,
+                vec!["Loose", "One", "Two"]
+            );
+
+            reorder_prompts(
+                Some(&work.id),
+                &[two.id.clone(), loose.id.clone(), one.id.clone()],
+            )
+            .await?;
+            assert_eq!(
+                filed_titles(in_work.clone()).await?,
+                vec!["Two", "Loose", "One"]
+            );
+            assert!(
+                reorder_prompts(Some(&work.id), std::slice::from_ref(&two.id))
+                    .await
+                    .is_err()
+            );
+
+            // Trashed prompts are not counted
+            delete_prompt(one.id.clone()).await?;
+            assert_eq!(list_collections().await?[0].prompt_count, 2);
+
+            // Reordering and moving only deal with the prompts listed
+            let three = insert_prompt(filed("Three")).await?;
+            delete_prompt(two.id.clone()).await?;
+            assert_eq!(filed_titles(in_work.clone()).await?, vec!["Loose", "Three"]);
+            reorder_prompts(Some(&work.id), &[three.id.clone(), loose.id.clone()]).await?;
+            assert_eq!(filed_titles(in_work.clone()).await?, vec!["Three", "Loose"]);
+            move_prompt(&loose.id, Some(&work.id), Some(0)).await?;
+            assert_eq!(filed_titles(in_work.clone()).await?, vec!["Loose", "Three"]);
+            move_prompt(&three.id, Some(&work.id), Some(1)).await?;
+            assert_eq!(filed_titles(in_work.clone()).await?, vec!["Loose", "Three"]);
+
+            // Restored prompts come back in their old slots
+            restore_deleted(&two.id).await?;
+            restore_deleted(&one.id).await?;
+            assert_eq!(
+                filed_titles(in_work.clone()).await?,
+                vec!["Two", "Loose", "One", "Three"]
+            );
+
+            assert!(move_prompt("missing", None, None).await.is_err());
+            assert!(insert_prompt(NewPrompt {
+                title: "Bad".into(),
+                collection_id: Some("missing".into()),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_delete_moves_contents_to_parent() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let parent = create_collection("Parent", None).await?;
+            let child = create_collection("Child", Some(&parent.id)).await?;
+            let grandchild = create_collection("Grandchild", Some(&child.id)).await?;
+
+            let kept = insert_prompt(NewPrompt {
+                title: "Kept".into(),
+                collection_id: Some(parent.id.clone()),
+                ..Default::default()
+            })
+            .await?;
+            let moved = insert_prompt(NewPrompt {
+                title: "Moved".into(),
+                collection_id: Some(child.id.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+            assert_eq!(delete_collection(&child.id).await?, 1);
+
+            let tree = collection_tree().await?;
+            assert_eq!(tree.len(), 1);
+            assert_eq!(tree[0].children.len(), 1);
+            assert_eq!(tree[0].children[0].collection.id, grandchild.id);
+
+            assert_eq!(
+                filed_titles(CollectionFilter::In(parent.id.clone())).await?,
+                vec![kept.title, moved.title]
+            );
+
+            // Top-level deletes leave prompts unfiled
+            delete_collection(&parent.id).await?;
+            assert_eq!(
+                filed_titles(CollectionFilter::Unfiled).await?,
+                vec!["Kept", "Moved"]
+            );
+            assert!(delete_collection(&parent.id).await.is_err());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,267 @@
+//! Versioned schema migrations
+//!
+//! Each [`Migration`] is applied at most once, inside its own transaction, and
+//! recorded in the `_migrations` table. A failing migration rolls back
+//! completely and is reported as `AmpError::DatabaseError`, leaving earlier
+//! migrations in place so the next start can retry from the same version.
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{migrate::MigrateError, FromRow, SqlitePool};
+
+use crate::errors::{AmpError, Result};
+
+/// A single numbered schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Strictly increasing version number, starting at 1
+    pub version: i64,
+    /// Short identifier shown in `db.status`
+    pub name: &'static str,
+    /// SQL script (may contain multiple statements)
+    pub sql: &'static str,
+}
+
+/// A migration recorded as applied
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    /// Unix timestamp (seconds)
+    pub applied_at: i64,
+}
+
+/// Migration state of a database
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    /// Highest applied version (0 for an empty database)
+    pub current_version: i64,
+    /// Highest version known to this build
+    pub latest_version: i64,
+    pub applied: Vec<AppliedMigration>,
+    /// Known migrations not yet applied, as `(version, name)`
+    pub pending: Vec<(i64, &'static str)>,
+}
+
+const CREATE_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS _migrations (
+    version INTEGER PRIMARY KEY,  -- Migration version number
+    name TEXT NOT NULL,           -- Migration name
+    applied_at INTEGER NOT NULL   -- Unix timestamp (seconds)
+)";
+
+/// Apply all pending migrations in version order
+///
+/// Returns the versions that were applied by this call.
+pub async fn run(pool: &SqlitePool, migrations: &[Migration]) -> Result<Vec<i64>> {
+    validate(migrations)?;
+    sqlx::query(CREATE_TABLE).execute(pool).await?;
+
+    let current = current_version(pool).await?;
+    let mut applied = Vec::new();
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        apply(pool, migration)
+            .await
+            .map_err(|e| migration_error(e, migration.version))?;
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+/// Report applied and pending migrations
+pub async fn status(pool: &SqlitePool, migrations: &[Migration]) -> Result<MigrationStatus> {
+    sqlx::query(CREATE_TABLE).execute(pool).await?;
+
+    let applied = sqlx::query_as::<_, AppliedMigration>(
+        "SELECT version, name, applied_at FROM _migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let current_version = applied.last().map(|m| m.version).unwrap_or(0);
+    let pending = migrations
+        .iter()
+        .filter(|m| m.version > current_version)
+        .map(|m| (m.version, m.name))
+        .collect();
+
+    Ok(MigrationStatus {
+        current_version,
+        latest_version: migrations.last().map(|m| m.version).unwrap_or(0),
+        applied,
+        pending,
+    })
+}
+
+async fn current_version(pool: &SqlitePool) -> Result<i64> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _migrations")
+        .fetch_one(pool)
+        .await?;
+    Ok(version.unwrap_or(0))
+}
+
+async fn apply(pool: &SqlitePool, migration: &Migration) -> std::result::Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO _migrations (version, name, applied_at) VALUES (?, ?, ?)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+/// Wrap a failure so the message names the migration version
+fn migration_error(err: sqlx::Error, version: i64) -> AmpError {
+    AmpError::DatabaseError(sqlx::Error::Migrate(Box::new(
+        MigrateError::ExecuteMigration(err, version),
+    )))
+}
+
+/// Guard against mistakes in the migration list itself
+fn validate(migrations: &[Migration]) -> Result<()> {
+    let mut previous = 0;
+    for migration in migrations {
+        if migration.version <= previous {
+            return Err(AmpError::ConfigError(format!(
+                "Migration versions must be strictly increasing (found {} after {})",
+                migration.version, previous
+            )));
+        }
+        previous = migration.version;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::runtime;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "create_items",
+            sql: "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        },
+        Migration {
+            version: 2,
+            name: "add_items_note",
+            sql: "ALTER TABLE items ADD COLUMN note TEXT;",
+        },
+    ];
+
+    fn memory_pool() -> SqlitePool {
+        runtime::block_on(async {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn test_run_applies_pending_once() {
+        let pool = memory_pool();
+
+        runtime::block_on(async {
+            assert_eq!(run(&pool, &MIGRATIONS[..1]).await.unwrap(), vec![1]);
+            assert_eq!(run(&pool, MIGRATIONS).await.unwrap(), vec![2]);
+            assert!(run(&pool, MIGRATIONS).await.unwrap().is_empty());
+
+            let status = status(&pool, MIGRATIONS).await.unwrap();
+            assert_eq!(status.current_version, 2);
+            assert_eq!(status.latest_version, 2);
+            assert_eq!(status.applied.len(), 2);
+            assert_eq!(status.applied[1].name, "add_items_note");
+            assert!(status.pending.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let pool = memory_pool();
+        let broken = [
+            MIGRATIONS[0],
+            Migration {
+                version: 2,
+                name: "broken",
+                sql: "CREATE TABLE other (id INTEGER); INSERT INTO missing VALUES (1);",
+            },
+        ];
+
+        runtime::block_on(async {
+            let err = run(&pool, &broken).await.unwrap_err();
+            assert_eq!(err.category(), "database");
+            assert!(err.to_string().contains("migration 2"));
+
+            let status = status(&pool, &broken).await.unwrap();
+            assert_eq!(status.current_version, 1);
+            assert_eq!(status.pending, vec![(2, "broken")]);
+
+            // The partial CREATE TABLE from the failed script was rolled back
+            let exists: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'other'",
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            assert_eq!(exists, 0);
+        });
+    }
+
+    #[test]
+    fn test_legacy_database_is_upgraded() {
+        let pool = memory_pool();
+
+        runtime::block_on(async {
+            // Shape of prompts.db before the description column and versioning
+            sqlx::query(
+                "CREATE TABLE prompts (
+                    id TEXT PRIMARY KEY, title TEXT NOT NULL, content TEXT NOT NULL,
+                    tags TEXT, usage_count INTEGER DEFAULT 0, last_used_at INTEGER,
+                    created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL
+                )",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("INSERT INTO prompts VALUES ('p1', 'Old', 'Body', NULL, 0, NULL, 1, 1)")
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            crate::db::upgrade_legacy_schema(&pool).await.unwrap();
+            run(&pool, crate::db::schema::MIGRATIONS).await.unwrap();
+
+            let status = status(&pool, crate::db::schema::MIGRATIONS).await.unwrap();
+            assert_eq!(status.current_version, status.latest_version);
+
+            // Existing rows are searchable after the FTS migration backfill
+            let hits: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM prompts_fts WHERE prompts_fts MATCH 'old'",
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            assert_eq!(hits, 1);
+        });
+    }
+
+    #[test]
+    fn test_rejects_unordered_migrations() {
+        let pool = memory_pool();
+        let unordered = [MIGRATIONS[1], MIGRATIONS[0]];
+
+        let err = runtime::block_on(run(&pool, &unordered)).unwrap_err();
+        assert_eq!(err.category(), "config");
+    }
+}
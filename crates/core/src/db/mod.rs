@@ -1,13 +1,27 @@
 use crate::errors::Result;
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
+pub mod collections;
+#[cfg(test)]
+mod collections_test;
+pub mod migrations;
 pub mod prompts;
 #[cfg(test)]
 mod prompts_test;
+pub mod revisions;
+#[cfg(test)]
+mod revisions_test;
 pub mod schema;
+pub mod tags;
+#[cfg(test)]
+mod tags_test;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 static DB_POOL: OnceLock<SqlitePool> = OnceLock::new();
+static DB_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 pub struct Db;
 
@@ -19,37 +33,39 @@ impl Db {
         }
 
         // Create directory if it doesn't exist
-        if let Some(parent) = std::path::Path::new(path).parent() {
+        if let Some(parent) = Path::new(path).parent() {
             tokio::fs::create_dir_all(parent)
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to create database directory: {}", e))?;
         }
 
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect_with(
-                sqlx::sqlite::SqliteConnectOptions::new()
-                    .filename(path)
-                    .create_if_missing(true)
-                    .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal),
-            )
-            .await?;
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+
+        // Migrate on a dedicated connection so pooled connections never hold a
+        // schema cached from before the migrations ran
+        {
+            let setup = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options.clone())
+                .await?;
 
-        // Run schema migration
-        // Split by semicolon to run multiple statements
-        for statement in schema::SCHEMA.split(';') {
-            if statement.trim().is_empty() {
-                continue;
-            }
-            sqlx::query(statement).execute(&pool).await?;
+            // Databases created before versioning may lack the description
+            // column, which the CREATE TABLE IF NOT EXISTS in migration 1
+            // cannot add
+            upgrade_legacy_schema(&setup).await?;
+            migrations::run(&setup, schema::MIGRATIONS).await?;
+            setup.close().await;
         }
 
-        // Manual migrations
-        // Attempt to add description column if it doesn't exist
-        let _ = sqlx::query("ALTER TABLE prompts ADD COLUMN description TEXT")
-            .execute(&pool)
-            .await;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?;
 
+        let _ = DB_PATH.set(PathBuf::from(path));
         DB_POOL
             .set(pool)
             .map_err(|_| anyhow::anyhow!("Failed to set global DB pool"))?;
@@ -63,4 +79,29 @@ impl Db {
             .get()
             .ok_or_else(|| anyhow::anyhow!("Database not initialized").into())
     }
+
+    /// Path of the database file, once initialized
+    pub fn path() -> Option<&'static Path> {
+        DB_PATH.get().map(PathBuf::as_path)
+    }
+
+    /// Report the schema version and pending migrations
+    pub async fn status() -> Result<migrations::MigrationStatus> {
+        migrations::status(Self::pool()?, schema::MIGRATIONS).await
+    }
+}
+
+/// Add columns that pre-versioning databases may be missing
+async fn upgrade_legacy_schema(pool: &SqlitePool) -> Result<()> {
+    let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('prompts')")
+        .fetch_all(pool)
+        .await?;
+
+    if !columns.is_empty() && !columns.iter().any(|c| c == "description") {
+        sqlx::query("ALTER TABLE prompts ADD COLUMN description TEXT")
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
 }
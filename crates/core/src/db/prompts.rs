@@ -1,10 +1,26 @@
-use super::Db;
-use crate::errors::Result;
+use super::{collections, revisions, tags, Db};
+use crate::errors::{AmpError, Result};
+use crate::templates::{self, VariableDecl};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, QueryBuilder, Sqlite};
+use std::cmp::{Ordering, Reverse};
 use uuid::Uuid;
 
+/// Age (in seconds) at which a use counts half towards frecency
+pub const FRECENCY_HALF_LIFE: i64 = 7 * 24 * 60 * 60;
+
+/// Where a prompt lives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// The personal library in `prompts.db`
+    #[default]
+    Global,
+    /// A Markdown file in the workspace (see [`crate::library::project`])
+    Project,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Prompt {
     pub id: String,
@@ -12,104 +28,596 @@ pub struct Prompt {
     pub description: Option<String>,
     pub content: String,
     pub tags: Option<String>,
+    /// JSON array of template variable declarations
+    pub variables: Option<String>,
     pub usage_count: i32,
     pub last_used_at: Option<i64>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// When the prompt was moved to the trash
+    pub deleted_at: Option<i64>,
+    /// Collection the prompt is filed in (`None` when unfiled)
+    pub collection_id: Option<String>,
+    /// Order within the collection
+    pub collection_position: i64,
+    /// Decayed usage score, only computed when sorting by frecency
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frecency: Option<f64>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub scope: Scope,
+    /// File backing a project prompt
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// A prompt matched by full-text search
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PromptSearchHit {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub prompt: Prompt,
+    /// BM25 score (lower is a better match)
+    pub rank: f64,
+    /// Title with matched terms wrapped in highlight markers
+    pub title_highlight: String,
+    /// Best matching fragment with matched terms wrapped in highlight markers
+    pub snippet: String,
+}
+
+/// Options for full-text prompt search
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Maximum number of hits to return
+    pub limit: i64,
+    /// Marker inserted before each matched term
+    pub highlight_start: String,
+    /// Marker inserted after each matched term
+    pub highlight_end: String,
+    /// Pass the query to FTS5 verbatim instead of escaping it
+    pub raw: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            limit: 50,
+            highlight_start: "**".to_string(),
+            highlight_end: "**".to_string(),
+            raw: false,
+        }
+    }
+}
+
+/// Order of [`query_prompts`] results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptSort {
+    /// Most recently edited first
+    #[default]
+    Updated,
+    /// Highest decayed usage score first: each use counts
+    /// `1 / (1 + age / FRECENCY_HALF_LIFE)`
+    Frecency,
+    /// Highest usage count first
+    MostUsed,
+    /// Most recently used first (never used prompts last)
+    Recent,
+    /// By title, case-insensitively
+    Alphabetical,
+    /// Manual order within a collection, then by title
+    Manual,
+}
+
+impl PromptSort {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "updated" => Ok(PromptSort::Updated),
+            "frecency" => Ok(PromptSort::Frecency),
+            "most_used" => Ok(PromptSort::MostUsed),
+            "recent" => Ok(PromptSort::Recent),
+            "alphabetical" => Ok(PromptSort::Alphabetical),
+            "manual" => Ok(PromptSort::Manual),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown sort '{}' (expected updated, frecency, most_used, recent, alphabetical or manual)",
+                other
+            ))),
+        }
+    }
+
+    fn order_by(self) -> &'static str {
+        match self {
+            PromptSort::Updated => " ORDER BY p.updated_at DESC",
+            PromptSort::Frecency => {
+                " ORDER BY frecency DESC, p.last_used_at DESC, p.updated_at DESC"
+            },
+            PromptSort::MostUsed => {
+                " ORDER BY p.usage_count DESC, p.last_used_at DESC, p.updated_at DESC"
+            },
+            PromptSort::Recent => " ORDER BY p.last_used_at DESC, p.updated_at DESC",
+            PromptSort::Alphabetical => " ORDER BY p.title COLLATE NOCASE, p.title",
+            PromptSort::Manual => {
+                " ORDER BY p.collection_position, p.title COLLATE NOCASE, p.title"
+            },
+        }
+    }
+
+    /// The same order as the SQL, for merging prompts from other sources
+    pub fn compare(self, a: &Prompt, b: &Prompt) -> Ordering {
+        let updated = |p: &Prompt| Reverse(p.updated_at);
+        let used = |p: &Prompt| Reverse(p.last_used_at);
+        match self {
+            PromptSort::Updated => updated(a).cmp(&updated(b)),
+            PromptSort::Frecency => {
+                let score = |p: &Prompt| p.frecency.unwrap_or(0.0);
+                score(b)
+                    .total_cmp(&score(a))
+                    .then_with(|| used(a).cmp(&used(b)))
+                    .then_with(|| updated(a).cmp(&updated(b)))
+            },
+            PromptSort::MostUsed => (Reverse(a.usage_count), used(a), updated(a)).cmp(&(
+                Reverse(b.usage_count),
+                used(b),
+                updated(b),
+            )),
+            PromptSort::Recent => (used(a), updated(a)).cmp(&(used(b), updated(b))),
+            PromptSort::Alphabetical => by_title(a, b),
+            PromptSort::Manual => a
+                .collection_position
+                .cmp(&b.collection_position)
+                .then_with(|| by_title(a, b)),
+        }
+    }
+}
+
+fn by_title(a: &Prompt, b: &Prompt) -> Ordering {
+    a.title
+        .to_lowercase()
+        .cmp(&b.title.to_lowercase())
+        .then_with(|| a.title.cmp(&b.title))
+}
+
+/// Restriction of [`query_prompts`] to part of the collection tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionFilter {
+    /// Prompts filed directly in this collection
+    In(String),
+    /// Prompts not filed in any collection
+    Unfiled,
+}
+
+/// Filters for [`query_prompts`]; the default matches every prompt
+#[derive(Debug, Clone, Default)]
+pub struct PromptQuery {
+    /// Only prompts carrying this tag
+    pub tag: Option<String>,
+    /// Only prompts carrying at least one of these tags
+    pub any_tags: Vec<String>,
+    /// Only prompts carrying every one of these tags
+    pub all_tags: Vec<String>,
+    pub collection: Option<CollectionFilter>,
+    pub sort: PromptSort,
 }
 
 pub async fn list_prompts() -> Result<Vec<Prompt>> {
+    query_prompts(&PromptQuery::default()).await
+}
+
+/// List prompts matching `query`, in `query.sort` order
+///
+/// Trashed prompts are excluded. Tag names are matched case-insensitively.
+pub async fn query_prompts(query: &PromptQuery) -> Result<Vec<Prompt>> {
     let pool = Db::pool()?;
-    let prompts = sqlx::query_as::<_, Prompt>("SELECT * FROM prompts ORDER BY updated_at DESC")
-        .fetch_all(pool)
-        .await?;
+    let mut builder = QueryBuilder::<Sqlite>::new("SELECT p.*");
+
+    if query.sort == PromptSort::Frecency {
+        builder
+            .push(", COALESCE((SELECT SUM(1.0 / (1.0 + MAX(")
+            .push_bind(Utc::now().timestamp())
+            .push(" - e.used_at, 0) / ")
+            .push_bind(FRECENCY_HALF_LIFE as f64)
+            .push(")) FROM prompt_usage_events e WHERE e.prompt_id = p.id), 0.0) AS frecency");
+    }
+    builder.push(" FROM prompts p WHERE p.deleted_at IS NULL");
+
+    if let Some(tag) = &query.tag {
+        push_tag_filter(&mut builder, std::slice::from_ref(tag), false);
+    }
+    push_tag_filter(&mut builder, &query.any_tags, false);
+    push_tag_filter(&mut builder, &query.all_tags, true);
+
+    match &query.collection {
+        Some(CollectionFilter::In(id)) => {
+            builder
+                .push(" AND p.collection_id = ")
+                .push_bind(id.clone());
+        },
+        Some(CollectionFilter::Unfiled) => {
+            builder.push(" AND p.collection_id IS NULL");
+        },
+        None => {},
+    }
 
+    builder.push(query.sort.order_by());
+
+    let prompts = builder.build_query_as::<Prompt>().fetch_all(pool).await?;
     Ok(prompts)
 }
 
+/// Restrict to prompts tagged with any (or, if `require_all`, every) of `names`
+fn push_tag_filter(builder: &mut QueryBuilder<'_, Sqlite>, names: &[String], require_all: bool) {
+    let names = tags::normalize_tags(names);
+    if names.is_empty() {
+        return;
+    }
+
+    builder.push(
+        " AND p.id IN (SELECT pt.prompt_id FROM prompt_tags pt \
+         JOIN tags t ON t.id = pt.tag_id WHERE t.name IN (",
+    );
+    let mut separated = builder.separated(", ");
+    for name in &names {
+        separated.push_bind(name.clone());
+    }
+    builder.push(")");
+
+    if require_all {
+        builder
+            .push(" GROUP BY pt.prompt_id HAVING COUNT(DISTINCT pt.tag_id) = ")
+            .push_bind(names.len() as i64);
+    }
+    builder.push(")");
+}
+
+/// Fields of a prompt to insert
+///
+/// `id` and the timestamps are generated when absent; importers set them to
+/// keep a prompt's identity across machines.
+#[derive(Debug, Clone, Default)]
+pub struct NewPrompt {
+    pub id: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub variables: Option<Vec<VariableDecl>>,
+    /// Collection to file the prompt in, at the end
+    pub collection_id: Option<String>,
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+}
+
 pub async fn create_prompt(
     title: String,
     description: Option<String>,
     content: String,
     tags: Option<Vec<String>>,
+    variables: Option<Vec<VariableDecl>>,
 ) -> Result<Prompt> {
+    insert_prompt(NewPrompt {
+        title,
+        description,
+        content,
+        tags,
+        variables,
+        ..Default::default()
+    })
+    .await
+}
+
+/// Insert a prompt, keeping the given id and timestamps if set
+pub async fn insert_prompt(new: NewPrompt) -> Result<Prompt> {
     let pool = Db::pool()?;
-    let id = Uuid::new_v4().to_string();
+    let id = new.id.unwrap_or_else(|| Uuid::new_v4().to_string());
     let now = Utc::now().timestamp();
+    let created_at = new.created_at.unwrap_or(now);
+    let updated_at = new.updated_at.unwrap_or(created_at);
+    let variables_json = variables_json(new.variables.as_deref())?;
 
-    let tags_json = tags.map(|t| serde_json::to_string(&t).unwrap_or_default());
+    let mut tx = pool.begin().await?;
+
+    if let Some(collection_id) = &new.collection_id {
+        collections::require_collection(&mut tx, collection_id).await?;
+    }
+    let collection_position =
+        collections::next_prompt_position(&mut tx, new.collection_id.as_deref()).await?;
 
     sqlx::query(
-        "INSERT INTO prompts (id, title, description, content, tags, usage_count, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?, 0, ?, ?)"
+        "INSERT INTO prompts (id, title, description, content, variables, usage_count, created_at, updated_at, collection_id, collection_position)
+         VALUES (?, ?, ?, ?, ?, 0, ?, ?, ?, ?)",
     )
     .bind(&id)
-    .bind(&title)
-    .bind(&description)
-    .bind(&content)
-    .bind(&tags_json)
-    .bind(now)
-    .bind(now)
-    .execute(pool)
+    .bind(&new.title)
+    .bind(&new.description)
+    .bind(&new.content)
+    .bind(&variables_json)
+    .bind(created_at)
+    .bind(updated_at)
+    .bind(&new.collection_id)
+    .bind(collection_position)
+    .execute(&mut *tx)
     .await?;
 
+    let tags_json = tags::set_prompt_tags(&mut tx, &id, new.tags.as_deref()).await?;
+    revisions::record_revision(&mut tx, &id).await?;
+
+    tx.commit().await?;
+
     Ok(Prompt {
         id,
-        title,
-        description,
-        content,
+        title: new.title,
+        description: new.description,
+        content: new.content,
         tags: tags_json,
+        variables: variables_json,
         usage_count: 0,
         last_used_at: None,
-        created_at: now,
-        updated_at: now,
+        created_at,
+        updated_at,
+        deleted_at: None,
+        collection_id: new.collection_id,
+        collection_position,
+        frecency: None,
+        scope: Scope::Global,
+        path: None,
     })
 }
 
+/// Update a prompt, recording the result as a new revision
+///
+/// `tags: None` clears the tags, while `variables: None` keeps the existing
+/// declarations (the edit form does not know about them).
 pub async fn update_prompt(
     id: String,
     title: String,
     description: Option<String>,
     content: String,
     tags: Option<Vec<String>>,
+    variables: Option<Vec<VariableDecl>>,
 ) -> Result<()> {
     let pool = Db::pool()?;
     let now = Utc::now().timestamp();
-    let tags_json = tags.map(|t| serde_json::to_string(&t).unwrap_or_default());
 
-    sqlx::query(
-        "UPDATE prompts SET title = ?, description = ?, content = ?, tags = ?, updated_at = ? WHERE id = ?"
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        "UPDATE prompts SET title = ?, description = ?, content = ?, updated_at = ? WHERE id = ?",
     )
     .bind(title)
     .bind(description)
     .bind(content)
-    .bind(tags_json)
     .bind(now)
-    .bind(id)
-    .execute(pool)
+    .bind(&id)
+    .execute(&mut *tx)
     .await?;
+    if result.rows_affected() == 0 {
+        return Err(AmpError::ValidationError(format!(
+            "Prompt '{}' not found",
+            id
+        )));
+    }
 
+    if let Some(variables) = variables {
+        sqlx::query("UPDATE prompts SET variables = ? WHERE id = ?")
+            .bind(variables_json(Some(&variables))?)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tags::set_prompt_tags(&mut tx, &id, tags.as_deref()).await?;
+    revisions::record_revision(&mut tx, &id).await?;
+
+    tx.commit().await?;
     Ok(())
 }
 
+/// Fetch a single prompt by id
+pub async fn get_prompt(id: &str) -> Result<Option<Prompt>> {
+    let pool = Db::pool()?;
+    let prompt = sqlx::query_as::<_, Prompt>("SELECT * FROM prompts WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(prompt)
+}
+
+/// Move a prompt to the trash
+///
+/// Trashed prompts are hidden from listings and search until restored with
+/// [`restore_deleted`] or removed for good by [`purge_deleted`].
 pub async fn delete_prompt(id: String) -> Result<()> {
     let pool = Db::pool()?;
-    sqlx::query("DELETE FROM prompts WHERE id = ?")
+    sqlx::query("UPDATE prompts SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+        .bind(Utc::now().timestamp())
         .bind(id)
         .execute(pool)
         .await?;
     Ok(())
 }
 
+/// List trashed prompts, most recently deleted first
+pub async fn list_deleted() -> Result<Vec<Prompt>> {
+    let pool = Db::pool()?;
+    let prompts = sqlx::query_as::<_, Prompt>(
+        "SELECT * FROM prompts WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(prompts)
+}
+
+/// Take a prompt out of the trash
+pub async fn restore_deleted(id: &str) -> Result<()> {
+    let pool = Db::pool()?;
+    let result =
+        sqlx::query("UPDATE prompts SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AmpError::ValidationError(format!(
+            "Prompt '{}' is not in the trash",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// Permanently delete trashed prompts
+///
+/// With `older_than` (seconds), only prompts trashed at least that long ago
+/// are removed. Returns the number of prompts purged.
+pub async fn purge_deleted(older_than: Option<i64>) -> Result<u64> {
+    let pool = Db::pool()?;
+    let cutoff = Utc::now().timestamp() - older_than.unwrap_or(0);
+
+    let result =
+        sqlx::query("DELETE FROM prompts WHERE deleted_at IS NOT NULL AND deleted_at <= ?")
+            .bind(cutoff)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Uses of prompts on one day
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DailyUsage {
+    /// Local date, `YYYY-MM-DD`
+    pub day: String,
+    pub prompt_id: String,
+    pub count: i64,
+}
+
+/// Record a use of a prompt (counter, last use and usage event)
 pub async fn record_usage(id: String) -> Result<()> {
     let pool = Db::pool()?;
     let now = Utc::now().timestamp();
 
+    let mut tx = pool.begin().await?;
+
     sqlx::query("UPDATE prompts SET usage_count = usage_count + 1, last_used_at = ? WHERE id = ?")
         .bind(now)
-        .bind(id)
-        .execute(pool)
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO prompt_usage_events (prompt_id, used_at) SELECT id, ? FROM prompts WHERE id = ?")
+        .bind(now)
+        .bind(&id)
+        .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
     Ok(())
 }
+
+/// Per-day usage counts over the last `days` days, newest day first
+///
+/// Restricted to one prompt when `prompt_id` is set.
+pub async fn usage_by_day(prompt_id: Option<&str>, days: i64) -> Result<Vec<DailyUsage>> {
+    let pool = Db::pool()?;
+    let since = Utc::now().timestamp() - days * 24 * 60 * 60;
+
+    let usage = sqlx::query_as::<_, DailyUsage>(
+        "SELECT date(used_at, 'unixepoch', 'localtime') AS day, prompt_id, COUNT(*) AS count
+         FROM prompt_usage_events
+         WHERE used_at >= ? AND (? IS NULL OR prompt_id = ?)
+         GROUP BY day, prompt_id
+         ORDER BY day DESC, count DESC, prompt_id",
+    )
+    .bind(since)
+    .bind(prompt_id)
+    .bind(prompt_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(usage)
+}
+
+impl Prompt {
+    /// Tag names, in order
+    pub fn tag_names(&self) -> Result<Vec<String>> {
+        match &self.tags {
+            Some(json) => Ok(serde_json::from_str(json)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Parsed template variable declarations
+    pub fn variable_decls(&self) -> Result<Vec<VariableDecl>> {
+        match &self.variables {
+            Some(json) => Ok(serde_json::from_str(json)?),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Validate declarations and serialize them for the `variables` column
+fn variables_json(variables: Option<&[VariableDecl]>) -> Result<Option<String>> {
+    match variables {
+        Some(variables) if !variables.is_empty() => {
+            templates::validate_declarations(variables)?;
+            Ok(Some(serde_json::to_string(variables)?))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Search prompts using the FTS5 index, best matches first
+///
+/// Title matches weigh more than tag and description matches, which in turn
+/// weigh more than content matches.
+pub async fn search_prompts(query: &str, options: SearchOptions) -> Result<Vec<PromptSearchHit>> {
+    let match_expr = if options.raw {
+        query.trim().to_string()
+    } else {
+        fts_query(query)
+    };
+
+    if match_expr.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let pool = Db::pool()?;
+    let hits = sqlx::query_as::<_, PromptSearchHit>(
+        "SELECT p.*,
+                bm25(prompts_fts, 0.0, 10.0, 4.0, 1.0, 6.0) AS rank,
+                highlight(prompts_fts, 1, ?, ?) AS title_highlight,
+                snippet(prompts_fts, -1, ?, ?, '…', 16) AS snippet
+         FROM prompts_fts
+         JOIN prompts p ON p.id = prompts_fts.id
+         WHERE prompts_fts MATCH ? AND p.deleted_at IS NULL
+         ORDER BY rank
+         LIMIT ?",
+    )
+    .bind(&options.highlight_start)
+    .bind(&options.highlight_end)
+    .bind(&options.highlight_start)
+    .bind(&options.highlight_end)
+    .bind(&match_expr)
+    .bind(options.limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(hits)
+}
+
+/// Turn free-form user input into a safe FTS5 match expression
+///
+/// Each whitespace-separated word becomes a quoted prefix term, so input like
+/// `fix-bug "unit` can never produce an FTS5 syntax error. All terms must match.
+pub fn fts_query(input: &str) -> String {
+    input
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,64 +1,352 @@
 #[cfg(test)]
 mod tests {
     use crate::db::prompts::{
-        create_prompt, delete_prompt, list_prompts, record_usage, update_prompt,
+        create_prompt, delete_prompt, fts_query, get_prompt, list_deleted, list_prompts,
+        purge_deleted, query_prompts, record_usage, restore_deleted, search_prompts, update_prompt,
+        usage_by_day, PromptQuery, PromptSort, SearchOptions,
     };
+    use crate::db::tags::list_tags;
+    use crate::db::test_support::setup;
     use crate::db::Db;
     use crate::errors::Result;
-    use tempfile::tempdir;
-
-    #[tokio::test]
-    async fn test_crud_operations() -> Result<()> {
-        // Setup isolated DB
-        let dir = tempdir().unwrap();
-        let db_path = dir.path().join("test_prompts.db");
-
-        // Initialize DB
-        Db::init(db_path.to_str().unwrap()).await?;
-
-        // 1. Create
-        let prompt = create_prompt(
-            "Test Title".into(),
-            Some("Test Description".into()),
-            "Test Content".into(),
-            Some(vec!["tag1".into(), "tag2".into()]),
-        )
-        .await?;
-
-        assert_eq!(prompt.title, "Test Title");
-        assert_eq!(prompt.description, Some("Test Description".into()));
-        assert_eq!(prompt.usage_count, 0);
-
-        // 2. List
-        let prompts = list_prompts().await?;
-        assert!(!prompts.is_empty());
-        assert_eq!(prompts[0].id, prompt.id);
-
-        // 3. Update
-        update_prompt(
-            prompt.id.clone(),
-            "Updated Title".into(),
-            Some("Updated Description".into()),
-            "Updated Content".into(),
-            None,
-        )
-        .await?;
-
-        let prompts = list_prompts().await?;
-        assert_eq!(prompts[0].title, "Updated Title");
-        assert_eq!(prompts[0].description, Some("Updated Description".into()));
-        assert_eq!(prompts[0].content, "Updated Content");
-
-        // 4. Usage
-        record_usage(prompt.id.clone()).await?;
-        let prompts = list_prompts().await?;
-        assert_eq!(prompts[0].usage_count, 1);
-
-        // 5. Delete
-        delete_prompt(prompt.id.clone()).await?;
-        let prompts = list_prompts().await?;
-        assert!(prompts.iter().all(|p| p.id != prompt.id));
+    use crate::runtime;
+    use crate::templates::VariableDecl;
 
+    #[test]
+    fn test_crud_operations() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            // 1. Create
+            let prompt = create_prompt(
+                "Test Title".into(),
+                Some("Test Description".into()),
+                "Test Content".into(),
+                Some(vec!["tag1".into(), "tag2".into()]),
+                None,
+            )
+            .await?;
+
+            assert_eq!(prompt.title, "Test Title");
+            assert_eq!(prompt.description, Some("Test Description".into()));
+            assert_eq!(prompt.usage_count, 0);
+
+            // 2. List
+            let prompts = list_prompts().await?;
+            assert!(!prompts.is_empty());
+            assert_eq!(prompts[0].id, prompt.id);
+
+            // 3. Update
+            update_prompt(
+                prompt.id.clone(),
+                "Updated Title".into(),
+                Some("Updated Description".into()),
+                "Updated Content".into(),
+                None,
+                None,
+            )
+            .await?;
+
+            let prompts = list_prompts().await?;
+            assert_eq!(prompts[0].title, "Updated Title");
+            assert_eq!(prompts[0].description, Some("Updated Description".into()));
+            assert_eq!(prompts[0].content, "Updated Content");
+
+            // 4. Usage
+            record_usage(prompt.id.clone()).await?;
+            let prompts = list_prompts().await?;
+            assert_eq!(prompts[0].usage_count, 1);
+
+            // 5. Delete
+            delete_prompt(prompt.id.clone()).await?;
+            let prompts = list_prompts().await?;
+            assert!(prompts.iter().all(|p| p.id != prompt.id));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_update_unknown_prompt() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            for tags in [None, Some(vec!["tag1".to_string()])] {
+                let err = update_prompt(
+                    "missing".into(),
+                    "Title".into(),
+                    None,
+                    "Content".into(),
+                    tags,
+                    None,
+                )
+                .await
+                .unwrap_err();
+                assert_eq!(err.category(), "validation");
+                assert_eq!(
+                    err.to_string(),
+                    "Validation error: Prompt 'missing' not found"
+                );
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_search_ranks_and_highlights() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let in_title = create_prompt(
+                "Refactor Selection".into(),
+                None,
+                "Make it idiomatic".into(),
+                Some(vec!["coding".into()]),
+                None,
+            )
+            .await?;
+            let in_content = create_prompt(
+                "Cleanup".into(),
+                None,
+                "Please refactor this module".into(),
+                None,
+                None,
+            )
+            .await?;
+            create_prompt("Unrelated".into(), None, "Nothing here".into(), None, None).await?;
+
+            let hits = search_prompts("refact", SearchOptions::default()).await?;
+            assert_eq!(hits.len(), 2);
+            assert_eq!(hits[0].prompt.id, in_title.id);
+            assert_eq!(hits[1].prompt.id, in_content.id);
+            assert_eq!(hits[0].title_highlight, "**Refactor** Selection");
+            assert!(hits[1].snippet.contains("**refactor**"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_search_index_tracks_updates_and_deletes() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let prompt = create_prompt(
+                "Explain".into(),
+                None,
+                "Explain the code".into(),
+                None,
+                None,
+            )
+            .await?;
+
+            update_prompt(
+                prompt.id.clone(),
+                "Explain".into(),
+                Some("Walkthrough of edge cases".into()),
+                "Explain the code".into(),
+                Some(vec!["review".into()]),
+                None,
+            )
+            .await?;
+
+            let hits = search_prompts("walkthrough", SearchOptions::default()).await?;
+            assert_eq!(hits.len(), 1);
+            let hits = search_prompts("review", SearchOptions::default()).await?;
+            assert_eq!(hits.len(), 1);
+
+            delete_prompt(prompt.id).await?;
+            let hits = search_prompts("explain", SearchOptions::default()).await?;
+            assert!(hits.is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_variables_survive_updates() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let declarations: Vec<VariableDecl> =
+                serde_json::from_str(r#"[{"name": "lang", "default": "rust"}]"#)?;
+            let prompt = create_prompt(
+                "Review".into(),
+                None,
+                "Review {{file}} as {{lang}}".into(),
+                None,
+                Some(declarations.clone()),
+            )
+            .await?;
+
+            // Updates without declarations keep the stored ones
+            update_prompt(
+                prompt.id.clone(),
+                "Review".into(),
+                None,
+                "Review {{file}} as {{ lang }}".into(),
+                None,
+                None,
+            )
+            .await?;
+            let stored = get_prompt(&prompt.id).await?.unwrap();
+            assert_eq!(stored.variable_decls()?, declarations);
+
+            let invalid: Vec<VariableDecl> = serde_json::from_str(r#"[{"name": "bad name"}]"#)?;
+            let result = create_prompt("X".into(), None, "x".into(), None, Some(invalid)).await;
+            assert!(result.is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_soft_delete_trash_and_purge() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let kept = create_prompt(
+                "Kept".into(),
+                None,
+                "findme".into(),
+                Some(vec!["t".into()]),
+                None,
+            )
+            .await?;
+            let old = create_prompt("Old".into(), None, "findme".into(), None, None).await?;
+
+            delete_prompt(kept.id.clone()).await?;
+            delete_prompt(old.id.clone()).await?;
+
+            // Hidden everywhere but the trash
+            assert!(list_prompts().await?.is_empty());
+            let hits = search_prompts("findme", SearchOptions::default()).await?;
+            assert!(hits.is_empty());
+            assert!(list_tags(false).await?.is_empty());
+            assert_eq!(list_deleted().await?.len(), 2);
+
+            restore_deleted(&kept.id).await?;
+            let restored = list_prompts().await?;
+            assert_eq!(restored.len(), 1);
+            assert_eq!(restored[0].tags.as_deref(), Some(r#"["t"]"#));
+            assert!(restore_deleted(&kept.id).await.is_err());
+
+            // Backdate the remaining trashed prompt
+            sqlx::query("UPDATE prompts SET deleted_at = deleted_at - 90000 WHERE id = ?")
+                .bind(&old.id)
+                .execute(Db::pool()?)
+                .await?;
+            delete_prompt(kept.id.clone()).await?;
+
+            assert_eq!(purge_deleted(Some(24 * 60 * 60)).await?, 1);
+            assert!(get_prompt(&old.id).await?.is_none());
+            assert_eq!(purge_deleted(None).await?, 1);
+            assert!(list_deleted().await?.is_empty());
+
+            Ok(())
+        })
+    }
+
+    /// Insert usage events `ages` seconds ago and set the summary columns
+    async fn used(id: &str, ages: &[i64]) -> Result<()> {
+        let pool = Db::pool()?;
+        let now = chrono::Utc::now().timestamp();
+        for age in ages {
+            sqlx::query("INSERT INTO prompt_usage_events (prompt_id, used_at) VALUES (?, ?)")
+                .bind(id)
+                .bind(now - age)
+                .execute(pool)
+                .await?;
+        }
+        sqlx::query("UPDATE prompts SET usage_count = ?, last_used_at = ? WHERE id = ?")
+            .bind(ages.len() as i64)
+            .bind(ages.iter().min().map(|age| now - age))
+            .bind(id)
+            .execute(pool)
+            .await?;
         Ok(())
     }
+
+    async fn sorted(sort: PromptSort) -> Result<Vec<String>> {
+        let query = PromptQuery {
+            sort,
+            ..Default::default()
+        };
+        Ok(query_prompts(&query)
+            .await?
+            .into_iter()
+            .map(|p| p.title)
+            .collect())
+    }
+
+    #[test]
+    fn test_sort_orders() -> Result<()> {
+        let _guard = setup();
+        const DAY: i64 = 24 * 60 * 60;
+
+        runtime::block_on(async {
+            let old_favourite = create_prompt("b".into(), None, "x".into(), None, None).await?;
+            let recent = create_prompt("C".into(), None, "x".into(), None, None).await?;
+            create_prompt("a".into(), None, "x".into(), None, None).await?;
+
+            // Used a lot, but two months ago
+            used(&old_favourite.id, &[60 * DAY; 5]).await?;
+            // Used twice this week
+            used(&recent.id, &[DAY, 2 * DAY]).await?;
+
+            assert_eq!(sorted(PromptSort::Frecency).await?, vec!["C", "b", "a"]);
+            assert_eq!(sorted(PromptSort::MostUsed).await?, vec!["b", "C", "a"]);
+            assert_eq!(sorted(PromptSort::Recent).await?[..2], ["C", "b"]);
+            assert_eq!(sorted(PromptSort::Alphabetical).await?, vec!["a", "b", "C"]);
+
+            let scored = query_prompts(&PromptQuery {
+                sort: PromptSort::Frecency,
+                ..Default::default()
+            })
+            .await?;
+            assert!(scored[0].frecency.unwrap() > 1.0);
+            assert_eq!(scored[2].frecency, Some(0.0));
+
+            assert!(PromptSort::parse("popular").is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_usage_events_by_day() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let a = create_prompt("A".into(), None, "x".into(), None, None).await?;
+            let b = create_prompt("B".into(), None, "x".into(), None, None).await?;
+
+            record_usage(a.id.clone()).await?;
+            record_usage(a.id.clone()).await?;
+            record_usage(b.id.clone()).await?;
+            // Unknown ids are ignored rather than failing the foreign key
+            record_usage("project:review.md".into()).await?;
+
+            let usage = usage_by_day(None, 1).await?;
+            assert_eq!(usage.len(), 2);
+            assert_eq!(
+                (usage[0].prompt_id.as_str(), usage[0].count),
+                (a.id.as_str(), 2)
+            );
+
+            let only_b = usage_by_day(Some(&b.id), 1).await?;
+            assert_eq!(only_b.len(), 1);
+            assert_eq!(only_b[0].count, 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_fts_query_escapes_input() {
+        assert_eq!(fts_query("fix bug"), "\"fix\"* \"bug\"*");
+        assert_eq!(fts_query("say \"hi\""), "\"say\"* \"hi\"*");
+        assert_eq!(fts_query("  "), "");
+        assert_eq!(fts_query("a-b OR"), "\"a-b\"* \"OR\"*");
+    }
 }
@@ -0,0 +1,205 @@
+use super::{prompts, Db};
+use crate::errors::{AmpError, Result};
+use crate::templates::VariableDecl;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use sqlx::{FromRow, SqliteConnection};
+
+/// A snapshot of a prompt as it was written
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Revision {
+    pub id: i64,
+    pub prompt_id: String,
+    /// 1-based revision number, per prompt
+    pub revision: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub content: String,
+    pub tags: Option<String>,
+    pub variables: Option<String>,
+    pub created_at: i64,
+}
+
+/// One line of a diff between two revisions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    /// `"equal"`, `"insert"` or `"delete"`
+    pub kind: &'static str,
+    /// Line number in the old text (absent for insertions)
+    pub old_lnum: Option<usize>,
+    /// Line number in the new text (absent for deletions)
+    pub new_lnum: Option<usize>,
+    /// Line text, without the trailing newline
+    pub text: String,
+}
+
+/// Line diff of the content of two revisions
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub title_changed: bool,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub lines: Vec<DiffLine>,
+    /// The same diff in unified format
+    pub unified: String,
+}
+
+/// Snapshot the current state of a prompt as its next revision
+///
+/// Nothing is written when the prompt is identical to its latest revision.
+/// Returns whether a revision was recorded.
+pub(crate) async fn record_revision(conn: &mut SqliteConnection, prompt_id: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO prompt_revisions
+            (prompt_id, revision, title, description, content, tags, variables, created_at)
+         SELECT p.id, COALESCE(latest.revision, 0) + 1, p.title, p.description, p.content,
+                p.tags, p.variables, p.updated_at
+         FROM prompts p
+         LEFT JOIN prompt_revisions latest ON latest.prompt_id = p.id
+              AND latest.revision = (SELECT MAX(revision) FROM prompt_revisions
+                                     WHERE prompt_id = p.id)
+         WHERE p.id = ?
+           AND NOT (latest.id IS NOT NULL
+                    AND latest.title IS p.title
+                    AND latest.description IS p.description
+                    AND latest.content IS p.content
+                    AND latest.tags IS p.tags
+                    AND latest.variables IS p.variables)",
+    )
+    .bind(prompt_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// List the revisions of a prompt, newest first
+pub async fn list_revisions(prompt_id: &str) -> Result<Vec<Revision>> {
+    let pool = Db::pool()?;
+    let revisions = sqlx::query_as::<_, Revision>(
+        "SELECT * FROM prompt_revisions WHERE prompt_id = ? ORDER BY revision DESC",
+    )
+    .bind(prompt_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(revisions)
+}
+
+/// Fetch a single revision of a prompt
+pub async fn get_revision(prompt_id: &str, revision: i64) -> Result<Option<Revision>> {
+    let pool = Db::pool()?;
+    let revision = sqlx::query_as::<_, Revision>(
+        "SELECT * FROM prompt_revisions WHERE prompt_id = ? AND revision = ?",
+    )
+    .bind(prompt_id)
+    .bind(revision)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(revision)
+}
+
+/// Diff the content of two revisions of a prompt
+///
+/// `to` defaults to the latest revision and `from` to the one before `to`.
+pub async fn diff_revisions(
+    prompt_id: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<RevisionDiff> {
+    let to = match to {
+        Some(to) => to,
+        None => {
+            let pool = Db::pool()?;
+            let latest: Option<i64> = sqlx::query_scalar(
+                "SELECT MAX(revision) FROM prompt_revisions WHERE prompt_id = ?",
+            )
+            .bind(prompt_id)
+            .fetch_one(pool)
+            .await?;
+            latest.ok_or_else(|| {
+                AmpError::ValidationError(format!("Prompt '{}' has no revisions", prompt_id))
+            })?
+        },
+    };
+    let from = from.unwrap_or(to - 1);
+
+    let old = require_revision(prompt_id, from).await?;
+    let new = require_revision(prompt_id, to).await?;
+
+    let lines = line_diff(&old.content, &new.content);
+    let unified = TextDiff::from_lines(&old.content, &new.content)
+        .unified_diff()
+        .header(&format!("revision {}", from), &format!("revision {}", to))
+        .to_string();
+
+    Ok(RevisionDiff {
+        from,
+        to,
+        title_changed: old.title != new.title,
+        insertions: lines.iter().filter(|l| l.kind == "insert").count(),
+        deletions: lines.iter().filter(|l| l.kind == "delete").count(),
+        lines,
+        unified,
+    })
+}
+
+/// Restore a prompt to an earlier revision
+///
+/// The restore is itself an update, so it is recorded as a new revision and
+/// can be undone the same way.
+pub async fn restore_revision(prompt_id: &str, revision: i64) -> Result<prompts::Prompt> {
+    let snapshot = require_revision(prompt_id, revision).await?;
+
+    let tags: Option<Vec<String>> = match &snapshot.tags {
+        Some(json) => Some(serde_json::from_str(json)?),
+        None => None,
+    };
+    let variables: Vec<VariableDecl> = match &snapshot.variables {
+        Some(json) => serde_json::from_str(json)?,
+        None => vec![],
+    };
+
+    prompts::update_prompt(
+        prompt_id.to_string(),
+        snapshot.title,
+        snapshot.description,
+        snapshot.content,
+        tags,
+        Some(variables),
+    )
+    .await?;
+
+    prompts::get_prompt(prompt_id)
+        .await?
+        .ok_or_else(|| AmpError::ValidationError(format!("Prompt '{}' not found", prompt_id)))
+}
+
+async fn require_revision(prompt_id: &str, revision: i64) -> Result<Revision> {
+    get_revision(prompt_id, revision).await?.ok_or_else(|| {
+        AmpError::ValidationError(format!(
+            "Revision {} of prompt '{}' not found",
+            revision, prompt_id
+        ))
+    })
+}
+
+/// Line-by-line diff of two texts (line numbers are 1-based)
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Insert => "insert",
+                ChangeTag::Delete => "delete",
+            },
+            old_lnum: change.old_index().map(|i| i + 1),
+            new_lnum: change.new_index().map(|i| i + 1),
+            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect()
+}
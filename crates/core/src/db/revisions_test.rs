@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::prompts::{create_prompt, get_prompt, update_prompt};
+    use crate::db::revisions::{
+        diff_revisions, get_revision, line_diff, list_revisions, restore_revision,
+    };
+    use crate::db::test_support::setup;
+    use crate::errors::Result;
+    use crate::runtime;
+
+    #[test]
+    fn test_updates_record_revisions() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let prompt = create_prompt("Review".into(), None, "v1".into(), None, None).await?;
+            let id = prompt.id.clone();
+
+            update_prompt(id.clone(), "Review".into(), None, "v2".into(), None, None).await?;
+            // Saving the form without changes does not add a revision
+            update_prompt(id.clone(), "Review".into(), None, "v2".into(), None, None).await?;
+            update_prompt(
+                id.clone(),
+                "Review code".into(),
+                None,
+                "v3".into(),
+                Some(vec!["rust".into()]),
+                None,
+            )
+            .await?;
+
+            let revisions = list_revisions(&id).await?;
+            let numbers: Vec<_> = revisions.iter().map(|r| r.revision).collect();
+            assert_eq!(numbers, vec![3, 2, 1]);
+            assert_eq!(revisions[0].title, "Review code");
+            assert_eq!(revisions[0].tags.as_deref(), Some(r#"["rust"]"#));
+            assert_eq!(revisions[2].content, "v1");
+
+            assert!(get_revision(&id, 4).await?.is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_restore_is_a_new_revision() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let prompt = create_prompt(
+                "Explain".into(),
+                Some("Tuned".into()),
+                "Carefully tuned".into(),
+                Some(vec!["docs".into()]),
+                None,
+            )
+            .await?;
+            let id = prompt.id.clone();
+
+            update_prompt(id.clone(), "Oops".into(), None, "".into(), None, None).await?;
+
+            let restored = restore_revision(&id, 1).await?;
+            assert_eq!(restored.title, "Explain");
+            assert_eq!(restored.description.as_deref(), Some("Tuned"));
+            assert_eq!(restored.content, "Carefully tuned");
+            assert_eq!(restored.tags.as_deref(), Some(r#"["docs"]"#));
+
+            let revisions = list_revisions(&id).await?;
+            assert_eq!(revisions.len(), 3);
+            assert_eq!(revisions[0].content, "Carefully tuned");
+
+            assert!(restore_revision(&id, 9).await.is_err());
+            assert_eq!(get_prompt(&id).await?.unwrap().content, "Carefully tuned");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_diff_revisions() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let prompt = create_prompt("Fix".into(), None, "a\nb\nc\n".into(), None, None).await?;
+            let id = prompt.id.clone();
+            update_prompt(
+                id.clone(),
+                "Fix".into(),
+                None,
+                "a\nB\nc\nd\n".into(),
+                None,
+                None,
+            )
+            .await?;
+
+            // Defaults to the latest revision against the one before it
+            let diff = diff_revisions(&id, None, None).await?;
+            assert_eq!((diff.from, diff.to), (1, 2));
+            assert!(!diff.title_changed);
+            assert_eq!(diff.insertions, 2);
+            assert_eq!(diff.deletions, 1);
+            assert!(diff.unified.contains("--- revision 1"));
+            assert!(diff.unified.contains("+B"));
+
+            assert!(diff_revisions(&id, Some(0), Some(2)).await.is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_line_diff_numbers_lines() {
+        let lines = line_diff("one\ntwo\n", "one\n2\n");
+        let summary: Vec<_> = lines
+            .iter()
+            .map(|l| (l.kind, l.old_lnum, l.new_lnum, l.text.as_str()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("equal", Some(1), Some(1), "one"),
+                ("delete", Some(2), None, "two"),
+                ("insert", None, Some(2), "2"),
+            ]
+        );
+    }
+}
@@ -1,4 +1,57 @@
-pub const SCHEMA: &str = "
+//! Database schema, expressed as ordered migrations
+//!
+//! Migrations are applied in version order by [`super::migrations::run`] and
+//! recorded in the `_migrations` table. Never edit a released migration;
+//! append a new one with the next version number instead.
+
+use super::migrations::Migration;
+
+/// All prompt database migrations, in ascending version order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_prompts",
+        sql: PROMPTS,
+    },
+    Migration {
+        version: 2,
+        name: "prompts_fts",
+        sql: PROMPTS_FTS,
+    },
+    Migration {
+        version: 3,
+        name: "normalized_tags",
+        sql: TAGS,
+    },
+    Migration {
+        version: 4,
+        name: "prompt_variables",
+        sql: VARIABLES,
+    },
+    Migration {
+        version: 5,
+        name: "prompt_revisions",
+        sql: REVISIONS,
+    },
+    Migration {
+        version: 6,
+        name: "prompt_usage_events",
+        sql: USAGE_EVENTS,
+    },
+    Migration {
+        version: 7,
+        name: "prompt_soft_delete",
+        sql: SOFT_DELETE,
+    },
+    Migration {
+        version: 8,
+        name: "prompt_collections",
+        sql: COLLECTIONS,
+    },
+];
+
+/// v1: core prompts table
+const PROMPTS: &str = "
 -- Core prompts table
 CREATE TABLE IF NOT EXISTS prompts (
     id TEXT PRIMARY KEY,          -- UUID v4 string
@@ -16,3 +69,143 @@ CREATE TABLE IF NOT EXISTS prompts (
 CREATE INDEX IF NOT EXISTS idx_prompts_usage ON prompts(usage_count DESC);
 CREATE INDEX IF NOT EXISTS idx_prompts_updated ON prompts(updated_at DESC);
 ";
+
+/// v2: full-text search index over prompts
+const PROMPTS_FTS: &str = "
+-- FTS5 index (id is stored but not tokenized, so rows can be joined back)
+CREATE VIRTUAL TABLE IF NOT EXISTS prompts_fts USING fts5(
+    id UNINDEXED,
+    title,
+    description,
+    content,
+    tags,
+    tokenize = 'porter unicode61'
+);
+
+-- Keep the index in sync with the prompts table
+CREATE TRIGGER IF NOT EXISTS prompts_fts_insert AFTER INSERT ON prompts BEGIN
+    INSERT INTO prompts_fts (id, title, description, content, tags)
+    VALUES (new.id, new.title, new.description, new.content, new.tags);
+END;
+
+CREATE TRIGGER IF NOT EXISTS prompts_fts_delete AFTER DELETE ON prompts BEGIN
+    DELETE FROM prompts_fts WHERE id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS prompts_fts_update
+AFTER UPDATE OF title, description, content, tags ON prompts BEGIN
+    DELETE FROM prompts_fts WHERE id = old.id;
+    INSERT INTO prompts_fts (id, title, description, content, tags)
+    VALUES (new.id, new.title, new.description, new.content, new.tags);
+END;
+
+-- Backfill rows that predate the index
+INSERT INTO prompts_fts (id, title, description, content, tags)
+SELECT id, title, description, content, tags FROM prompts
+WHERE id NOT IN (SELECT id FROM prompts_fts);
+";
+
+/// v3: normalized tags, migrated from the JSON `prompts.tags` column
+///
+/// `prompts.tags` is kept as a denormalized JSON copy (read by the Lua UI and
+/// the FTS index); `prompt_tags` is the source of truth.
+const TAGS: &str = "
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE, -- Case-insensitive tag name
+    created_at INTEGER NOT NULL               -- Unix timestamp (seconds)
+);
+
+CREATE TABLE IF NOT EXISTS prompt_tags (
+    prompt_id TEXT NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,      -- Order of the tag on the prompt
+    PRIMARY KEY (prompt_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompt_tags_tag ON prompt_tags(tag_id);
+
+-- Migrate existing JSON tags
+INSERT OR IGNORE INTO tags (name, created_at)
+SELECT trim(j.value), CAST(strftime('%s', 'now') AS INTEGER)
+FROM prompts p, json_each(CASE WHEN json_valid(p.tags) THEN p.tags END) j
+WHERE j.type = 'text' AND trim(j.value) <> ''
+ORDER BY p.created_at, j.key;
+
+INSERT OR IGNORE INTO prompt_tags (prompt_id, tag_id, position)
+SELECT p.id, t.id, j.key
+FROM prompts p, json_each(CASE WHEN json_valid(p.tags) THEN p.tags END) j
+JOIN tags t ON t.name = trim(j.value)
+WHERE j.type = 'text';
+";
+
+/// v4: template variable declarations
+const VARIABLES: &str = "
+ALTER TABLE prompts ADD COLUMN variables TEXT; -- JSON array of variable declarations
+";
+
+/// v5: prompt revision history
+///
+/// Each row is a snapshot of a prompt as written by a create, update or
+/// restore. Existing prompts start their history at their current state.
+const REVISIONS: &str = "
+CREATE TABLE IF NOT EXISTS prompt_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    prompt_id TEXT NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,    -- 1-based, per prompt
+    title TEXT NOT NULL,
+    description TEXT,
+    content TEXT NOT NULL,
+    tags TEXT,                    -- JSON copy of the tags at the time
+    variables TEXT,               -- JSON variable declarations at the time
+    created_at INTEGER NOT NULL,  -- Unix timestamp (seconds)
+    UNIQUE (prompt_id, revision)
+);
+
+INSERT INTO prompt_revisions (prompt_id, revision, title, description, content, tags, variables, created_at)
+SELECT id, 1, title, description, content, tags, variables, updated_at FROM prompts;
+";
+
+/// v6: usage event log, for frecency ranking and per-day usage
+///
+/// `prompts.usage_count` and `last_used_at` stay as summary columns. Prompts
+/// used before the log existed get one event at their last use.
+const USAGE_EVENTS: &str = "
+CREATE TABLE IF NOT EXISTS prompt_usage_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    prompt_id TEXT NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    used_at INTEGER NOT NULL      -- Unix timestamp (seconds)
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_events_prompt ON prompt_usage_events(prompt_id, used_at);
+CREATE INDEX IF NOT EXISTS idx_usage_events_used_at ON prompt_usage_events(used_at);
+
+INSERT INTO prompt_usage_events (prompt_id, used_at)
+SELECT id, last_used_at FROM prompts WHERE last_used_at IS NOT NULL;
+";
+
+/// v7: soft delete; trashed prompts keep their tags, revisions and usage
+const SOFT_DELETE: &str = "
+ALTER TABLE prompts ADD COLUMN deleted_at INTEGER; -- Unix timestamp (seconds) when trashed
+
+CREATE INDEX IF NOT EXISTS idx_prompts_deleted ON prompts(deleted_at);
+";
+
+/// v8: nested collections, each prompt in at most one, manually ordered
+const COLLECTIONS: &str = "
+CREATE TABLE IF NOT EXISTS collections (
+    id TEXT PRIMARY KEY,          -- UUID v4 string
+    name TEXT NOT NULL,
+    parent_id TEXT REFERENCES collections(id) ON DELETE CASCADE, -- NULL at the top level
+    position INTEGER NOT NULL DEFAULT 0, -- Order among siblings
+    created_at INTEGER NOT NULL,  -- Unix timestamp (seconds)
+    updated_at INTEGER NOT NULL   -- Unix timestamp (seconds)
+);
+
+CREATE INDEX IF NOT EXISTS idx_collections_parent ON collections(parent_id, position);
+
+ALTER TABLE prompts ADD COLUMN collection_id TEXT REFERENCES collections(id) ON DELETE SET NULL;
+ALTER TABLE prompts ADD COLUMN collection_position INTEGER NOT NULL DEFAULT 0; -- Order within the collection
+
+CREATE INDEX IF NOT EXISTS idx_prompts_collection ON prompts(collection_id, collection_position);
+";
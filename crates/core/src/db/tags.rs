@@ -0,0 +1,286 @@
+use super::Db;
+use crate::errors::{AmpError, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub prompt_count: i64,
+}
+
+/// Trim, drop empty and de-duplicate (case-insensitively) tag names,
+/// keeping the first spelling of each
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut result = Vec::new();
+
+    for tag in tags {
+        let name = tag.trim();
+        let key = name.to_lowercase();
+        if name.is_empty() || seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        result.push(name.to_string());
+    }
+
+    result
+}
+
+/// List tags with the number of prompts carrying each
+///
+/// Trashed prompts are not counted. Tags no longer attached to any prompt are
+/// omitted unless `include_unused`.
+pub async fn list_tags(include_unused: bool) -> Result<Vec<Tag>> {
+    let pool = Db::pool()?;
+    let having = if include_unused {
+        ""
+    } else {
+        "HAVING COUNT(p.id) > 0"
+    };
+
+    let tags = sqlx::query_as::<_, Tag>(&format!(
+        "SELECT t.id, t.name, COUNT(p.id) AS prompt_count
+         FROM tags t
+         LEFT JOIN prompt_tags pt ON pt.tag_id = t.id
+         LEFT JOIN prompts p ON p.id = pt.prompt_id AND p.deleted_at IS NULL
+         GROUP BY t.id
+         {}
+         ORDER BY t.name",
+        having
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags)
+}
+
+/// Rename a tag across the whole library
+///
+/// Renaming onto another existing tag is refused; use [`merge_tags`] for that.
+/// Returns the number of prompts affected.
+pub async fn rename_tag(from: &str, to: &str) -> Result<u64> {
+    let to = to.trim();
+    if to.is_empty() {
+        return Err(AmpError::ValidationError(
+            "Tag name cannot be empty".to_string(),
+        ));
+    }
+
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let id = require_tag_id(&mut tx, from).await?;
+    if let Some(existing) = find_tag_id(&mut tx, to).await? {
+        if existing != id {
+            return Err(AmpError::ValidationError(format!(
+                "Tag '{}' already exists; merge the tags instead",
+                to
+            )));
+        }
+    }
+
+    sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+        .bind(to)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let affected = sync_tags_json_for_tags(&mut tx, &[id]).await?;
+
+    tx.commit().await?;
+    Ok(affected)
+}
+
+/// Merge `sources` into `target`, creating `target` if needed
+///
+/// Every prompt tagged with a source tag ends up tagged with `target`, and the
+/// source tags are removed. Returns the number of prompts affected.
+pub async fn merge_tags(sources: &[String], target: &str) -> Result<u64> {
+    let target = target.trim();
+    if target.is_empty() {
+        return Err(AmpError::ValidationError(
+            "Tag name cannot be empty".to_string(),
+        ));
+    }
+
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let target_id = ensure_tag(&mut tx, target).await?;
+    let mut source_ids = Vec::new();
+    for source in sources {
+        let id = require_tag_id(&mut tx, source).await?;
+        if id != target_id && !source_ids.contains(&id) {
+            source_ids.push(id);
+        }
+    }
+
+    if source_ids.is_empty() {
+        tx.commit().await?;
+        return Ok(0);
+    }
+
+    let prompt_ids = prompts_with_tags(&mut tx, &source_ids).await?;
+    let placeholders = placeholders(source_ids.len());
+
+    let insert_sql = format!(
+        "INSERT OR IGNORE INTO prompt_tags (prompt_id, tag_id, position)
+         SELECT prompt_id, ?, MIN(position) FROM prompt_tags
+         WHERE tag_id IN ({})
+         GROUP BY prompt_id",
+        placeholders
+    );
+    let mut insert = sqlx::query(&insert_sql).bind(target_id);
+    for id in &source_ids {
+        insert = insert.bind(id);
+    }
+    insert.execute(&mut *tx).await?;
+
+    let delete_sql = format!("DELETE FROM tags WHERE id IN ({})", placeholders);
+    let mut delete = sqlx::query(&delete_sql);
+    for id in &source_ids {
+        delete = delete.bind(id);
+    }
+    delete.execute(&mut *tx).await?;
+
+    sync_tags_json(&mut tx, &prompt_ids).await?;
+
+    tx.commit().await?;
+    Ok(prompt_ids.len() as u64)
+}
+
+/// Remove a tag from every prompt and delete it
+///
+/// Returns the number of prompts affected.
+pub async fn delete_tag(name: &str) -> Result<u64> {
+    let pool = Db::pool()?;
+    let mut tx = pool.begin().await?;
+
+    let id = require_tag_id(&mut tx, name).await?;
+    let prompt_ids = prompts_with_tags(&mut tx, &[id]).await?;
+
+    sqlx::query("DELETE FROM tags WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sync_tags_json(&mut tx, &prompt_ids).await?;
+
+    tx.commit().await?;
+    Ok(prompt_ids.len() as u64)
+}
+
+/// Replace the tags of a prompt (both the join table and the JSON copy)
+///
+/// Returns the JSON copy written to `prompts.tags`.
+pub(crate) async fn set_prompt_tags(
+    conn: &mut SqliteConnection,
+    prompt_id: &str,
+    tags: Option<&[String]>,
+) -> Result<Option<String>> {
+    sqlx::query("DELETE FROM prompt_tags WHERE prompt_id = ?")
+        .bind(prompt_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let Some(tags) = tags else {
+        sqlx::query("UPDATE prompts SET tags = NULL WHERE id = ?")
+            .bind(prompt_id)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(None);
+    };
+
+    for (position, name) in normalize_tags(tags).iter().enumerate() {
+        let tag_id = ensure_tag(conn, name).await?;
+        sqlx::query("INSERT INTO prompt_tags (prompt_id, tag_id, position) VALUES (?, ?, ?)")
+            .bind(prompt_id)
+            .bind(tag_id)
+            .bind(position as i64)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sync_tags_json(conn, &[prompt_id.to_string()]).await?;
+
+    let json = sqlx::query_scalar("SELECT tags FROM prompts WHERE id = ?")
+        .bind(prompt_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(json)
+}
+
+/// Look up a tag id by name (case-insensitive)
+async fn find_tag_id(conn: &mut SqliteConnection, name: &str) -> Result<Option<i64>> {
+    let id = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(name.trim())
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(id)
+}
+
+async fn require_tag_id(conn: &mut SqliteConnection, name: &str) -> Result<i64> {
+    find_tag_id(conn, name)
+        .await?
+        .ok_or_else(|| AmpError::ValidationError(format!("Tag '{}' not found", name.trim())))
+}
+
+/// Get the id of a tag, creating it if it does not exist
+async fn ensure_tag(conn: &mut SqliteConnection, name: &str) -> Result<i64> {
+    if let Some(id) = find_tag_id(conn, name).await? {
+        return Ok(id);
+    }
+
+    let result = sqlx::query("INSERT INTO tags (name, created_at) VALUES (?, ?)")
+        .bind(name.trim())
+        .bind(Utc::now().timestamp())
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.last_insert_rowid())
+}
+
+async fn prompts_with_tags(conn: &mut SqliteConnection, tag_ids: &[i64]) -> Result<Vec<String>> {
+    let sql = format!(
+        "SELECT DISTINCT prompt_id FROM prompt_tags WHERE tag_id IN ({})",
+        placeholders(tag_ids.len())
+    );
+    let mut query = sqlx::query_scalar::<_, String>(&sql);
+    for id in tag_ids {
+        query = query.bind(id);
+    }
+    Ok(query.fetch_all(&mut *conn).await?)
+}
+
+async fn sync_tags_json_for_tags(conn: &mut SqliteConnection, tag_ids: &[i64]) -> Result<u64> {
+    let prompt_ids = prompts_with_tags(conn, tag_ids).await?;
+    sync_tags_json(conn, &prompt_ids).await?;
+    Ok(prompt_ids.len() as u64)
+}
+
+/// Rewrite the JSON `prompts.tags` copy from `prompt_tags`
+async fn sync_tags_json(conn: &mut SqliteConnection, prompt_ids: &[String]) -> Result<()> {
+    for id in prompt_ids {
+        sqlx::query(
+            "UPDATE prompts SET tags = (
+                SELECT json_group_array(name) FROM (
+                    SELECT t.name FROM prompt_tags pt
+                    JOIN tags t ON t.id = pt.tag_id
+                    WHERE pt.prompt_id = prompts.id
+                    ORDER BY pt.position, t.name
+                )
+            )
+            WHERE id = ?",
+        )
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// `?, ?, ?` for an `IN (...)` clause
+pub(crate) fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
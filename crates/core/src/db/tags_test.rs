@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::prompts::{create_prompt, list_prompts, query_prompts, PromptQuery};
+    use crate::db::tags::{delete_tag, list_tags, merge_tags, normalize_tags, rename_tag};
+    use crate::db::test_support::setup;
+    use crate::errors::Result;
+    use crate::runtime;
+
+    async fn tagged(title: &str, tags: &[&str]) -> Result<String> {
+        let tags = tags.iter().map(|t| t.to_string()).collect();
+        let prompt = create_prompt(title.into(), None, "content".into(), Some(tags), None).await?;
+        Ok(prompt.id)
+    }
+
+    fn titles(prompts: Vec<crate::db::prompts::Prompt>) -> Vec<String> {
+        let mut titles: Vec<String> = prompts.into_iter().map(|p| p.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = vec![" review ".into(), "Review".into(), "".into(), "docs".into()];
+        assert_eq!(normalize_tags(&tags), vec!["review", "docs"]);
+    }
+
+    #[test]
+    fn test_list_counts_and_json_copy() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            tagged("A", &["review", "rust"]).await?;
+            tagged("B", &["Review"]).await?;
+
+            let tags = list_tags(false).await?;
+            assert_eq!(tags.len(), 2);
+            assert_eq!(tags[0].name, "review");
+            assert_eq!(tags[0].prompt_count, 2);
+            assert_eq!(tags[1].name, "rust");
+            assert_eq!(tags[1].prompt_count, 1);
+
+            let prompts = list_prompts().await?;
+            let a = prompts.iter().find(|p| p.title == "A").unwrap();
+            assert_eq!(a.tags.as_deref(), Some(r#"["review","rust"]"#));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_tag_filters() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            tagged("A", &["review", "rust"]).await?;
+            tagged("B", &["review"]).await?;
+            tagged("C", &["docs"]).await?;
+
+            let by_tag = PromptQuery {
+                tag: Some("REVIEW".into()),
+                ..Default::default()
+            };
+            assert_eq!(titles(query_prompts(&by_tag).await?), vec!["A", "B"]);
+
+            let any = PromptQuery {
+                any_tags: vec!["rust".into(), "docs".into()],
+                ..Default::default()
+            };
+            assert_eq!(titles(query_prompts(&any).await?), vec!["A", "C"]);
+
+            let all = PromptQuery {
+                all_tags: vec!["review".into(), "rust".into()],
+                ..Default::default()
+            };
+            assert_eq!(titles(query_prompts(&all).await?), vec!["A"]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_rename_merge_delete() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            tagged("A", &["bug", "rust"]).await?;
+            tagged("B", &["bugs"]).await?;
+            tagged("C", &["defect", "bug"]).await?;
+
+            // Renaming onto an existing tag must go through merge
+            assert!(rename_tag("bugs", "bug").await.is_err());
+
+            assert_eq!(rename_tag("rust", "Rust").await?, 1);
+            assert_eq!(
+                merge_tags(&["bugs".into(), "defect".into()], "bug").await?,
+                2
+            );
+
+            let tags = list_tags(false).await?;
+            let names: Vec<_> = tags
+                .iter()
+                .map(|t| (t.name.as_str(), t.prompt_count))
+                .collect();
+            assert_eq!(names, vec![("bug", 3), ("Rust", 1)]);
+
+            let prompts = list_prompts().await?;
+            let c = prompts.iter().find(|p| p.title == "C").unwrap();
+            assert_eq!(c.tags.as_deref(), Some(r#"["bug"]"#));
+
+            assert_eq!(delete_tag("bug").await?, 3);
+            let prompts = list_prompts().await?;
+            let a = prompts.iter().find(|p| p.title == "A").unwrap();
+            assert_eq!(a.tags.as_deref(), Some(r#"["Rust"]"#));
+            assert!(delete_tag("bug").await.is_err());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,42 @@
+//! Shared setup for database tests
+//!
+//! The connection pool is a process-wide global, so all database tests share
+//! one database file. [`setup`] initializes it once on the global runtime,
+//! serializes tests with a lock and clears every table so each test starts
+//! from an empty library.
+
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use super::Db;
+use crate::runtime;
+
+static LOCK: Mutex<()> = Mutex::new(());
+static DB_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Tables wiped before each test (children before parents)
+const TABLES: &[&str] = &["prompts", "tags", "collections"];
+
+/// Initialize the shared test database and take the test lock
+///
+/// Hold the returned guard for the duration of the test.
+pub fn setup() -> MutexGuard<'static, ()> {
+    let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let dir = DB_DIR.get_or_init(|| tempfile::tempdir().unwrap().keep());
+    let db_path = dir.join("test_prompts.db");
+
+    runtime::block_on(async {
+        Db::init(db_path.to_str().unwrap()).await.unwrap();
+
+        let pool = Db::pool().unwrap();
+        for table in TABLES {
+            sqlx::query(&format!("DELETE FROM {}", table))
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    });
+
+    guard
+}
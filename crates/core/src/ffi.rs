@@ -7,24 +7,38 @@
 
 use std::sync::OnceLock;
 
-use nvim_oxi::{serde::Deserializer, Dictionary, Object};
-use serde::Deserialize;
+use nvim_oxi::{
+    api::{self, opts::ExecAutocmdsOpts},
+    libuv::AsyncHandle,
+    serde::{Deserializer, Serializer},
+    Array, Dictionary, Object,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{
     commands,
     db::Db,
     errors::{AmpError, Result},
+    library::project::{self, ProjectSettings},
     runtime,
+    threads::{self, index, watcher, ThreadSettings},
 };
 
 /// Plugin configuration
 #[derive(Debug, Clone, Default, Deserialize)]
 struct Config {
-    // Add configuration fields here if needed in the future
-    // Previously had auto_start for server
+    /// Checked-in prompt libraries merged with the global database
+    #[serde(default)]
+    project_prompts: ProjectSettings,
+    /// Where the Amp CLI keeps its threads
+    #[serde(default)]
+    threads: ThreadSettings,
 }
 
+/// `User` autocommand pattern fired when thread files change
+const THREADS_CHANGED_EVENT: &str = "AmpThreadsChanged";
+
 /// Global config storage
 static CONFIG: OnceLock<Config> = OnceLock::new();
 
@@ -48,8 +62,6 @@ pub fn call(command: String, args: Object) -> nvim_oxi::Result<Object> {
     match dispatch_command(&command, args_value) {
         Ok(result) => {
             // Convert serde_json::Value back to nvim-oxi Object
-            use nvim_oxi::serde::Serializer;
-            use serde::Serialize;
             result
                 .serialize(Serializer::new())
                 .map_err(nvim_oxi::Error::Serialize)
@@ -88,9 +100,10 @@ pub fn autocomplete(kind: String, prefix: String) -> nvim_oxi::Result<Vec<String
 ///
 /// Returns:
 /// ```lua
-/// { success = true }
+/// { success = true, warnings = { "Thread index unavailable: ..." } }
 /// ```
-/// Or on error:
+/// where `warnings` lists optional features (thread index, watcher) that
+/// failed to start. Or on error:
 /// ```lua
 /// {
 ///   error = true,
@@ -102,6 +115,8 @@ pub fn setup(config_obj: Object) -> nvim_oxi::Result<Object> {
     let config: Config = Config::deserialize(Deserializer::new(config_obj)).unwrap_or_default();
 
     // Store config (first call wins)
+    project::configure(config.project_prompts.clone());
+    threads::configure(config.threads.clone());
     let _ = CONFIG.set(config);
 
     // Initialize Database
@@ -121,7 +136,29 @@ pub fn setup(config_obj: Object) -> nvim_oxi::Result<Object> {
         return Ok(create_error_object(&e));
     }
 
-    let result = Dictionary::from_iter([("success", Object::from(true))]);
+    // The thread search index lives next to prompts.db and is brought up to
+    // date in the background. Without it only thread search and live
+    // updates are lost, so its errors are reported as warnings.
+    let mut warnings: Vec<String> = Vec::new();
+    let index_path = config_dir.join("amp-extras/threads.db");
+    match runtime::block_on(index::init(&index_path)) {
+        Ok(()) => {
+            runtime::spawn(refresh_thread_index());
+        },
+        Err(e) => warnings.push(format!("Thread index unavailable: {}", e.user_message())),
+    }
+
+    // A missing or unwatchable thread directory only disables live updates
+    if threads::settings().watch {
+        if let Err(e) = start_thread_watcher() {
+            warnings.push(format!("Thread watcher not started: {}", e.user_message()));
+        }
+    }
+
+    let result = Dictionary::from_iter([
+        ("success", Object::from(true)),
+        ("warnings", Object::from(Array::from_iter(warnings))),
+    ]);
     Ok(Object::from(result))
 }
 
@@ -141,6 +178,44 @@ fn autocomplete_impl(_kind: &str, _prefix: &str) -> Result<Vec<String>> {
     Ok(vec![])
 }
 
+/// Bring the thread search index up to date, ignoring errors (searches
+/// refresh it again)
+async fn refresh_thread_index() {
+    if let Ok(index) = index::global() {
+        let _ = index.refresh(&threads::threads_dir(), false).await;
+    }
+}
+
+/// Watch the thread directory
+///
+/// After each batch of changes, the watcher wakes the main thread, which
+/// fires `User AmpThreadsChanged` with the events as `data.events`, and the
+/// search index is refreshed in the background.
+fn start_thread_watcher() -> Result<bool> {
+    let handle = AsyncHandle::new(|| -> nvim_oxi::Result<()> {
+        let events = watcher::take_events();
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let data = serde_json::json!({ "events": events })
+            .serialize(Serializer::new())
+            .map_err(nvim_oxi::Error::Serialize)?;
+        let opts = ExecAutocmdsOpts::builder()
+            .patterns(THREADS_CHANGED_EVENT)
+            .data(data)
+            .build();
+        api::exec_autocmds(["User"], &opts)?;
+        Ok(())
+    })
+    .map_err(|e| anyhow::anyhow!("Failed to create thread watcher handle: {}", e))?;
+
+    watcher::watch(&threads::threads_dir(), move || {
+        let _ = handle.send();
+        runtime::spawn(refresh_thread_index());
+    })
+}
+
 /// Create a structured error object for Lua
 ///
 /// Returns a Dictionary with fields:
@@ -22,7 +22,13 @@ pub mod commands;
 pub mod db;
 pub mod errors;
 pub mod ffi;
+pub mod library;
+pub mod mcp;
+pub mod permissions;
 pub mod runtime;
+pub mod settings;
+pub mod templates;
+pub mod threads;
 
 use nvim_oxi::{Dictionary, Function, Object};
 
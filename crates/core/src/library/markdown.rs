@@ -0,0 +1,189 @@
+//! Markdown prompt files with YAML frontmatter
+//!
+//! ```markdown
+//! ---
+//! id: 7b0c3c8e-...
+//! title: Explain selection
+//! tags: [docs]
+//! ---
+//!
+//! Explain {{ selection }}
+//! ```
+//!
+//! The frontmatter holds every [`PromptRecord`] field except `content`, which
+//! is the body. Frontmatter is optional; without a `title` the file name is
+//! used instead.
+
+use serde::{Deserialize, Serialize};
+
+use super::PromptRecord;
+use crate::errors::{AmpError, Result};
+use crate::templates::VariableDecl;
+
+/// [`PromptRecord`] without the content
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Frontmatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    variables: Vec<VariableDecl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<i64>,
+}
+
+/// Parse a Markdown prompt file
+///
+/// `fallback_title` (usually the file stem) is used when the frontmatter has
+/// no title. Leading blank lines and trailing whitespace of the body are not
+/// part of the content.
+pub fn parse(text: &str, fallback_title: &str) -> Result<PromptRecord> {
+    let (frontmatter, body) = split(text);
+
+    let meta: Frontmatter = match frontmatter {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml_ng::from_str(yaml)
+            .map_err(|e| AmpError::ValidationError(format!("Invalid frontmatter: {}", e)))?,
+        _ => Frontmatter::default(),
+    };
+
+    let title = meta
+        .title
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| fallback_title.to_string());
+
+    Ok(PromptRecord {
+        id: meta.id,
+        title,
+        description: meta.description,
+        content: body.trim_start_matches(['\r', '\n']).trim_end().to_string(),
+        tags: meta.tags,
+        variables: meta.variables,
+        created_at: meta.created_at,
+        updated_at: meta.updated_at,
+    })
+}
+
+/// Render a record as a Markdown file
+pub fn render(record: &PromptRecord) -> Result<String> {
+    let meta = Frontmatter {
+        id: record.id.clone(),
+        title: Some(record.title.clone()),
+        description: record.description.clone(),
+        tags: record.tags.clone(),
+        variables: record.variables.clone(),
+        created_at: record.created_at,
+        updated_at: record.updated_at,
+    };
+    let yaml = serde_yaml_ng::to_string(&meta)
+        .map_err(|e| AmpError::Other(format!("Failed to write frontmatter: {}", e)))?;
+
+    Ok(format!(
+        "---\n{}---\n\n{}\n",
+        yaml,
+        record.content.trim_end()
+    ))
+}
+
+/// File name for a record: a slug of the title plus the start of the id, so
+/// prompts with the same title do not collide
+pub fn file_name(record: &PromptRecord) -> String {
+    let slug = slug(&record.title);
+    match &record.id {
+        Some(id) => format!("{}-{}.md", slug, id.chars().take(8).collect::<String>()),
+        None => format!("{}.md", slug),
+    }
+}
+
+/// Split `---` delimited frontmatter from the body
+fn split(text: &str) -> (Option<&str>, &str) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    // Unterminated frontmatter: treat the whole file as content
+    (None, text)
+}
+
+fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 48 {
+            break;
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "prompt".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let record = PromptRecord {
+            id: Some("7b0c3c8e-0000-4000-8000-000000000000".into()),
+            title: "Explain: selection".into(),
+            description: Some("Multi\nline".into()),
+            content: "Explain {{ selection }}\n\n---\n\nThanks".into(),
+            tags: vec!["docs".into(), "a, b".into()],
+            variables: vec![],
+            created_at: Some(1),
+            updated_at: Some(2),
+        };
+
+        let text = render(&record).unwrap();
+        assert!(text.starts_with("---\nid: 7b0c3c8e"));
+        assert_eq!(parse(&text, "ignored").unwrap(), record);
+        assert_eq!(file_name(&record), "explain-selection-7b0c3c8e.md");
+    }
+
+    #[test]
+    fn test_frontmatter_is_optional() {
+        let record = parse("\nJust a body\n", "my-prompt").unwrap();
+        assert_eq!(record.title, "my-prompt");
+        assert_eq!(record.content, "Just a body");
+        assert!(record.id.is_none());
+
+        // An unterminated block is content, not frontmatter
+        let record = parse("---\ntitle: x\nbody", "f").unwrap();
+        assert_eq!(record.title, "f");
+        assert!(record.content.starts_with("---"));
+    }
+
+    #[test]
+    fn test_invalid_frontmatter() {
+        let err = parse("---\ntags: [unclosed\n---\nbody", "f").unwrap_err();
+        assert_eq!(err.category(), "validation");
+    }
+}
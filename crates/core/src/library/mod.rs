@@ -0,0 +1,76 @@
+//! Portable prompt library formats
+//!
+//! Outside of `prompts.db`, a prompt is a [`PromptRecord`]: the fields worth
+//! sharing between machines, without local usage statistics. Records are
+//! written as a JSON bundle, CSV, or Markdown files with YAML frontmatter
+//! (see [`markdown`] and [`transfer`]).
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::prompts::{NewPrompt, Prompt};
+use crate::errors::Result;
+use crate::templates::VariableDecl;
+
+pub mod markdown;
+pub mod project;
+pub mod transfer;
+
+/// A prompt in portable form
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptRecord {
+    /// UUID of the prompt; records without one are always imported as new
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<VariableDecl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+}
+
+impl PromptRecord {
+    pub fn from_prompt(prompt: &Prompt) -> Result<Self> {
+        Ok(Self {
+            id: Some(prompt.id.clone()),
+            title: prompt.title.clone(),
+            description: prompt.description.clone(),
+            content: prompt.content.clone(),
+            tags: prompt.tag_names()?,
+            variables: prompt.variable_decls()?,
+            created_at: Some(prompt.created_at),
+            updated_at: Some(prompt.updated_at),
+        })
+    }
+
+    /// Tags as expected by the database layer (`None` for no tags)
+    pub fn tags_option(&self) -> Option<Vec<String>> {
+        if self.tags.is_empty() {
+            None
+        } else {
+            Some(self.tags.clone())
+        }
+    }
+
+    pub fn into_new_prompt(self) -> NewPrompt {
+        let tags = self.tags_option();
+        NewPrompt {
+            id: self.id,
+            title: self.title,
+            description: self.description,
+            content: self.content,
+            tags,
+            variables: Some(self.variables),
+            collection_id: None,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
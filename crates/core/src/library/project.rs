@@ -0,0 +1,464 @@
+//! Project-scoped prompt libraries
+//!
+//! A workspace can check in team prompts as Markdown files (by default
+//! `.amp/prompts/*.md` at the workspace root, see [`markdown`] for the file
+//! format). They are merged with the global database by [`merged`] and carry
+//! `scope: "project"`.
+//!
+//! Project prompts are read-only unless [`ProjectMode::WriteThrough`] is
+//! configured, in which case edits rewrite (and deletes remove) the files.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{markdown, PromptRecord};
+use crate::db::prompts::{self, CollectionFilter, Prompt, PromptQuery, Scope};
+use crate::db::tags::normalize_tags;
+use crate::errors::{AmpError, Result};
+use crate::templates::{self, VariableDecl};
+
+/// Prefix of ids derived from the file name, for files without an `id`
+const PATH_ID_PREFIX: &str = "project:";
+
+/// How edits to project prompts are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectMode {
+    /// Project prompts cannot be changed from the editor
+    #[default]
+    ReadOnly,
+    /// Edits are written back to the Markdown files
+    WriteThrough,
+}
+
+/// `project_prompts` section of the plugin configuration
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    pub enabled: bool,
+    /// Prompt directory, relative to the workspace root
+    pub dir: PathBuf,
+    pub mode: ProjectMode,
+}
+
+impl Default for ProjectSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from(".amp/prompts"),
+            mode: ProjectMode::ReadOnly,
+        }
+    }
+}
+
+/// Prompts of one project directory
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProjectLibrary {
+    pub dir: Option<PathBuf>,
+    pub prompts: Vec<Prompt>,
+    /// Files that could not be parsed, as `path: reason`
+    pub errors: Vec<String>,
+}
+
+/// Edited fields of a prompt
+#[derive(Debug, Clone)]
+pub struct PromptEdit {
+    pub title: String,
+    pub description: Option<String>,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    /// `None` keeps the existing declarations
+    pub variables: Option<Vec<VariableDecl>>,
+}
+
+struct IndexedFile {
+    /// Modification time and size when parsed
+    stamp: (SystemTime, u64),
+    prompt: Prompt,
+}
+
+static SETTINGS: OnceLock<ProjectSettings> = OnceLock::new();
+
+/// Parsed files by path, reused while their modification time and size are
+/// unchanged
+static INDEX: Lazy<Mutex<HashMap<PathBuf, IndexedFile>>> = Lazy::new(Default::default);
+
+/// Set the project settings (first call wins, like the rest of the config)
+pub fn configure(settings: ProjectSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+pub fn settings() -> ProjectSettings {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+/// Find the prompt directory of the workspace containing `start`
+///
+/// Walks up from `start` to the nearest ancestor that has the configured
+/// prompt directory.
+pub fn find_dir(start: &Path, settings: &ProjectSettings) -> Option<PathBuf> {
+    if !settings.enabled {
+        return None;
+    }
+    start
+        .ancestors()
+        .map(|ancestor| ancestor.join(&settings.dir))
+        .find(|dir| dir.is_dir())
+}
+
+/// Load the project prompts visible from `cwd`
+pub fn load(cwd: &Path, settings: &ProjectSettings) -> Result<ProjectLibrary> {
+    match find_dir(cwd, settings) {
+        Some(dir) => load_dir(&dir),
+        None => Ok(ProjectLibrary::default()),
+    }
+}
+
+/// Load every `*.md` file of `dir`, reparsing only files that changed
+pub fn load_dir(dir: &Path) -> Result<ProjectLibrary> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("md"))
+        .collect();
+    paths.sort();
+
+    let mut index = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    index.retain(|path, _| !path.starts_with(dir) || paths.contains(path));
+
+    let mut library = ProjectLibrary {
+        dir: Some(dir.to_path_buf()),
+        ..Default::default()
+    };
+
+    for path in paths {
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata.modified()?;
+        let stamp = (modified, metadata.len());
+        if let Some(cached) = index.get(&path).filter(|c| c.stamp == stamp) {
+            library.prompts.push(cached.prompt.clone());
+            continue;
+        }
+
+        match parse_file(&path, modified) {
+            Ok(prompt) => {
+                library.prompts.push(prompt.clone());
+                index.insert(path, IndexedFile { stamp, prompt });
+            },
+            Err(e) => {
+                index.remove(&path);
+                library.errors.push(format!("{}: {}", path.display(), e));
+            },
+        }
+    }
+
+    Ok(library)
+}
+
+/// Global prompts matching `query`, merged with the project prompts visible
+/// from `cwd`, in `query.sort` order (project prompts have no usage)
+///
+/// A project prompt shadows a global prompt with the same id.
+pub async fn merged(
+    query: &PromptQuery,
+    cwd: &Path,
+    settings: &ProjectSettings,
+) -> Result<(Vec<Prompt>, ProjectLibrary)> {
+    let library = load(cwd, settings)?;
+    let mut prompts: Vec<Prompt> = prompts::query_prompts(query)
+        .await?
+        .into_iter()
+        .filter(|p| !library.prompts.iter().any(|pp| pp.id == p.id))
+        .collect();
+
+    prompts.extend(
+        library
+            .prompts
+            .iter()
+            .filter(|p| matches(p, query))
+            .cloned(),
+    );
+    prompts.sort_by(|a, b| query.sort.compare(a, b));
+
+    Ok((prompts, library))
+}
+
+/// Find a project prompt by id
+pub fn find(cwd: &Path, settings: &ProjectSettings, id: &str) -> Result<Option<Prompt>> {
+    let library = load(cwd, settings)?;
+    Ok(library.prompts.into_iter().find(|p| p.id == id))
+}
+
+/// Create a prompt file in the project directory (write-through only)
+pub fn create(cwd: &Path, settings: &ProjectSettings, edit: PromptEdit) -> Result<Prompt> {
+    require_write_through(settings, &edit.title)?;
+    let dir = find_dir(cwd, settings).ok_or_else(|| {
+        AmpError::ValidationError(format!(
+            "No {} directory found for this workspace",
+            settings.dir.display()
+        ))
+    })?;
+
+    let now = Utc::now().timestamp();
+    let record = PromptRecord {
+        id: Some(Uuid::new_v4().to_string()),
+        title: edit.title,
+        description: edit.description,
+        content: edit.content,
+        tags: normalize_tags(&edit.tags.unwrap_or_default()),
+        variables: edit.variables.unwrap_or_default(),
+        created_at: Some(now),
+        updated_at: Some(now),
+    };
+    templates::validate_declarations(&record.variables)?;
+
+    let path = dir.join(markdown::file_name(&record));
+    fs::write(&path, markdown::render(&record)?)?;
+    parse_file(&path, fs::metadata(&path)?.modified()?)
+}
+
+/// Rewrite the file of a project prompt (write-through only)
+pub fn update(settings: &ProjectSettings, prompt: &Prompt, edit: PromptEdit) -> Result<()> {
+    require_write_through(settings, &prompt.title)?;
+    let path = prompt_path(prompt)?;
+
+    let variables = match edit.variables {
+        Some(variables) => variables,
+        None => prompt.variable_decls()?,
+    };
+    templates::validate_declarations(&variables)?;
+
+    let record = PromptRecord {
+        id: file_id(&prompt.id),
+        title: edit.title,
+        description: edit.description,
+        content: edit.content,
+        tags: normalize_tags(&edit.tags.unwrap_or_default()),
+        variables,
+        created_at: Some(prompt.created_at),
+        updated_at: Some(Utc::now().timestamp()),
+    };
+
+    fs::write(path, markdown::render(&record)?)?;
+    Ok(())
+}
+
+/// Remove the file of a project prompt (write-through only)
+pub fn delete(settings: &ProjectSettings, prompt: &Prompt) -> Result<()> {
+    require_write_through(settings, &prompt.title)?;
+    fs::remove_file(prompt_path(prompt)?)?;
+    Ok(())
+}
+
+fn require_write_through(settings: &ProjectSettings, title: &str) -> Result<()> {
+    if settings.mode == ProjectMode::WriteThrough {
+        return Ok(());
+    }
+    Err(AmpError::ValidationError(format!(
+        "Project prompt '{}' is read-only",
+        title
+    )))
+}
+
+fn prompt_path(prompt: &Prompt) -> Result<&Path> {
+    prompt
+        .path
+        .as_deref()
+        .map(Path::new)
+        .ok_or_else(|| AmpError::Other(format!("Prompt '{}' has no file", prompt.id)))
+}
+
+/// The id to keep in the frontmatter (derived ids are not written back)
+fn file_id(id: &str) -> Option<String> {
+    if id.starts_with(PATH_ID_PREFIX) {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+fn parse_file(path: &Path, modified: SystemTime) -> Result<Prompt> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Untitled");
+    let record = markdown::parse(&fs::read_to_string(path)?, stem)?;
+
+    let mtime = chrono::DateTime::<Utc>::from(modified).timestamp();
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or(stem);
+
+    Ok(Prompt {
+        id: record
+            .id
+            .unwrap_or_else(|| format!("{}{}", PATH_ID_PREFIX, file_name)),
+        title: record.title,
+        description: record.description,
+        content: record.content,
+        tags: match record.tags.as_slice() {
+            [] => None,
+            tags => Some(serde_json::to_string(&normalize_tags(tags))?),
+        },
+        variables: match record.variables.as_slice() {
+            [] => None,
+            variables => Some(serde_json::to_string(variables)?),
+        },
+        usage_count: 0,
+        last_used_at: None,
+        created_at: record.created_at.unwrap_or(mtime),
+        updated_at: record.updated_at.unwrap_or(mtime),
+        deleted_at: None,
+        collection_id: None,
+        collection_position: 0,
+        frecency: None,
+        scope: Scope::Project,
+        path: Some(path.to_string_lossy().into_owned()),
+    })
+}
+
+/// Apply the tag filters of `query` to a project prompt
+fn matches(prompt: &Prompt, query: &PromptQuery) -> bool {
+    let tags: Vec<String> = prompt
+        .tag_names()
+        .unwrap_or_default()
+        .iter()
+        .map(|t| t.to_lowercase())
+        .collect();
+    let has = |name: &String| tags.contains(&name.trim().to_lowercase());
+
+    let any = normalize_tags(&query.any_tags);
+    let all = normalize_tags(&query.all_tags);
+
+    // Project prompts are never filed in a collection
+    !matches!(query.collection, Some(CollectionFilter::In(_)))
+        && query
+            .tag
+            .as_ref()
+            .is_none_or(|t| t.trim().is_empty() || has(t))
+        && (any.is_empty() || any.iter().any(has))
+        && all.iter().all(has)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::prompts::create_prompt;
+    use crate::db::test_support::setup;
+    use crate::runtime;
+
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join(".amp/prompts");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("review.md"),
+            "---\ntitle: Team review\ntags: [Review]\n---\nReview {{ selection }}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        (root, dir)
+    }
+
+    #[test]
+    fn test_discovers_from_subdirectory() {
+        let (root, dir) = workspace();
+        let nested = root.path().join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        let settings = ProjectSettings::default();
+
+        assert_eq!(find_dir(&nested, &settings), Some(dir));
+
+        let library = load(&nested, &settings).unwrap();
+        assert_eq!(library.prompts.len(), 1);
+        let prompt = &library.prompts[0];
+        assert_eq!(prompt.id, "project:review.md");
+        assert_eq!(prompt.scope, Scope::Project);
+        assert_eq!(prompt.tags.as_deref(), Some(r#"["Review"]"#));
+
+        let disabled = ProjectSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(load(&nested, &disabled).unwrap().prompts.is_empty());
+    }
+
+    #[test]
+    fn test_reports_broken_files() {
+        let (_root, dir) = workspace();
+        fs::write(dir.join("broken.md"), "---\ntags: [\n---\n").unwrap();
+
+        let library = load_dir(&dir).unwrap();
+        assert_eq!(library.prompts.len(), 1);
+        assert_eq!(library.errors.len(), 1);
+        assert!(library.errors[0].contains("broken.md"));
+    }
+
+    #[test]
+    fn test_merged_view_and_tag_filters() -> Result<()> {
+        let _guard = setup();
+        let (root, _dir) = workspace();
+        let settings = ProjectSettings::default();
+
+        runtime::block_on(async {
+            create_prompt("Personal".into(), None, "mine".into(), None, None).await?;
+
+            let (all, _) = merged(&PromptQuery::default(), root.path(), &settings).await?;
+            let scopes: Vec<_> = all.iter().map(|p| (p.title.as_str(), p.scope)).collect();
+            assert!(scopes.contains(&("Personal", Scope::Global)));
+            assert!(scopes.contains(&("Team review", Scope::Project)));
+
+            let review = PromptQuery {
+                tag: Some("review".into()),
+                ..Default::default()
+            };
+            let (filtered, _) = merged(&review, root.path(), &settings).await?;
+            assert_eq!(filtered.len(), 1);
+            assert_eq!(filtered[0].title, "Team review");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_read_only_and_write_through() {
+        let (root, _dir) = workspace();
+        let read_only = ProjectSettings::default();
+        let prompt = find(root.path(), &read_only, "project:review.md")
+            .unwrap()
+            .unwrap();
+        let edit = PromptEdit {
+            title: "Team review v2".into(),
+            description: None,
+            content: "Review harder".into(),
+            tags: None,
+            variables: None,
+        };
+
+        let err = update(&read_only, &prompt, edit.clone()).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+
+        let write_through = ProjectSettings {
+            mode: ProjectMode::WriteThrough,
+            ..read_only
+        };
+        update(&write_through, &prompt, edit.clone()).unwrap();
+        let updated = find(root.path(), &write_through, "project:review.md")
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.title, "Team review v2");
+        assert_eq!(updated.content, "Review harder");
+
+        let created = create(root.path(), &write_through, edit).unwrap();
+        assert!(Path::new(created.path.as_deref().unwrap()).exists());
+        assert_eq!(load(root.path(), &write_through).unwrap().prompts.len(), 2);
+
+        delete(&write_through, &created).unwrap();
+        assert_eq!(load(root.path(), &write_through).unwrap().prompts.len(), 1);
+    }
+}
@@ -0,0 +1,566 @@
+//! Export and import of the prompt library
+//!
+//! Prompts are matched on their UUID `id` when importing; what happens to a
+//! prompt that already exists is chosen with a [`ConflictStrategy`].
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{markdown, PromptRecord};
+use crate::db::prompts::{self, PromptQuery};
+use crate::errors::{AmpError, Result};
+use crate::templates;
+
+/// Version written to JSON bundles
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// On-disk format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// A single JSON bundle
+    Json,
+    /// A directory of Markdown files with YAML frontmatter (or one file)
+    Markdown,
+    /// A single CSV file
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "csv" => Ok(Format::Csv),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown format '{}' (expected json, markdown or csv)",
+                other
+            ))),
+        }
+    }
+
+    /// Guess the format from a path: directories and `.md` files are
+    /// Markdown, `.csv` files CSV, anything else JSON
+    pub fn detect(path: &Path) -> Self {
+        if path.is_dir() {
+            return Format::Markdown;
+        }
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("md") | Some("markdown") => Format::Markdown,
+            Some("csv") => Format::Csv,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// What to do when an imported prompt's id already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Keep the existing prompt
+    #[default]
+    Skip,
+    /// Replace the existing prompt (recorded as a new revision), taking it
+    /// out of the trash
+    Overwrite,
+    /// Import as a new prompt with a fresh id
+    Duplicate,
+}
+
+impl ConflictStrategy {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "duplicate" => Ok(ConflictStrategy::Duplicate),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown conflict strategy '{}' (expected skip, overwrite or duplicate)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Counts of what an import did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// New prompts (including ones keeping their imported id)
+    pub created: usize,
+    pub overwritten: usize,
+    /// Overwritten prompts taken out of the trash
+    pub restored: usize,
+    pub skipped: usize,
+    pub duplicated: usize,
+}
+
+/// JSON bundle layout
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    version: u32,
+    #[serde(default)]
+    exported_at: i64,
+    prompts: Vec<PromptRecord>,
+}
+
+/// CSV row layout; tags are comma separated and variables JSON encoded
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    id: Option<String>,
+    title: String,
+    description: Option<String>,
+    content: String,
+    tags: Option<String>,
+    variables: Option<String>,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+}
+
+/// Load the prompts matching `query` as portable records
+pub async fn export_records(query: &PromptQuery) -> Result<Vec<PromptRecord>> {
+    prompts::query_prompts(query)
+        .await?
+        .iter()
+        .map(PromptRecord::from_prompt)
+        .collect()
+}
+
+/// Serialize records to a string (JSON or CSV)
+pub fn encode(records: &[PromptRecord], format: Format) -> Result<String> {
+    match format {
+        Format::Json => {
+            let bundle = Bundle {
+                version: BUNDLE_VERSION,
+                exported_at: Utc::now().timestamp(),
+                prompts: records.to_vec(),
+            };
+            Ok(serde_json::to_string_pretty(&bundle)?)
+        },
+        Format::Csv => to_csv(records),
+        Format::Markdown => match records {
+            [record] => markdown::render(record),
+            _ => Err(AmpError::ValidationError(
+                "Markdown exports of several prompts need a directory path".to_string(),
+            )),
+        },
+    }
+}
+
+/// Parse records from a string (JSON or CSV, or one Markdown file)
+pub fn decode(text: &str, format: Format) -> Result<Vec<PromptRecord>> {
+    match format {
+        Format::Json => from_json(text),
+        Format::Csv => from_csv(text),
+        Format::Markdown => Ok(vec![markdown::parse(text, "Untitled")?]),
+    }
+}
+
+/// Write records to `path` (a directory for Markdown exports)
+pub fn write(records: &[PromptRecord], path: &Path, format: Format) -> Result<()> {
+    if format == Format::Markdown && path.extension().is_none() {
+        fs::create_dir_all(path)?;
+        for record in records {
+            fs::write(
+                path.join(markdown::file_name(record)),
+                markdown::render(record)?,
+            )?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, encode(records, format)?)?;
+    Ok(())
+}
+
+/// Read records from `path` (every `*.md` file of a directory for Markdown)
+pub fn read(path: &Path, format: Format) -> Result<Vec<PromptRecord>> {
+    if format == Format::Markdown && path.is_dir() {
+        return read_markdown_dir(path);
+    }
+
+    let text = fs::read_to_string(path)?;
+    if format == Format::Markdown {
+        return Ok(vec![parse_markdown_file(path, &text)?]);
+    }
+    decode(&text, format)
+}
+
+/// Import records, resolving id conflicts with `strategy`
+///
+/// Records keep their id when it is not in the library yet, so exporting and
+/// importing again on another machine does not create duplicates. Ids are
+/// matched in their canonical lowercase form.
+///
+/// Titles, ids and variable declarations of every record are validated
+/// before anything is written, so a batch with an invalid record imports
+/// nothing. Records are then written one at a time: a database error part
+/// way through keeps the records imported before it.
+pub async fn import_records(
+    mut records: Vec<PromptRecord>,
+    strategy: ConflictStrategy,
+) -> Result<ImportReport> {
+    let mut ids = HashSet::new();
+    for record in &mut records {
+        validate(record)?;
+        if let Some(id) = &record.id {
+            if !ids.insert(id.clone()) {
+                return Err(AmpError::ValidationError(format!(
+                    "Prompt id '{}' appears more than once in the import",
+                    id
+                )));
+            }
+        }
+    }
+
+    let mut report = ImportReport::default();
+    for mut record in records {
+        let existing = match &record.id {
+            Some(id) => prompts::get_prompt(id).await?,
+            None => None,
+        };
+
+        let Some(existing) = existing else {
+            prompts::insert_prompt(record.into_new_prompt()).await?;
+            report.created += 1;
+            continue;
+        };
+
+        match strategy {
+            ConflictStrategy::Skip => report.skipped += 1,
+            ConflictStrategy::Overwrite => {
+                let tags = record.tags_option();
+                prompts::update_prompt(
+                    existing.id.clone(),
+                    record.title,
+                    record.description,
+                    record.content,
+                    tags,
+                    Some(record.variables),
+                )
+                .await?;
+                if existing.deleted_at.is_some() {
+                    prompts::restore_deleted(&existing.id).await?;
+                    report.restored += 1;
+                }
+                report.overwritten += 1;
+            },
+            ConflictStrategy::Duplicate => {
+                record.id = None;
+                prompts::insert_prompt(record.into_new_prompt()).await?;
+                report.duplicated += 1;
+            },
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check a record and put its id in canonical form
+fn validate(record: &mut PromptRecord) -> Result<()> {
+    if record.title.trim().is_empty() {
+        return Err(AmpError::ValidationError(
+            "Imported prompt has an empty title".to_string(),
+        ));
+    }
+    if let Some(id) = &record.id {
+        let uuid = Uuid::parse_str(id).map_err(|_| {
+            AmpError::ValidationError(format!(
+                "Prompt '{}' has an invalid id '{}'",
+                record.title, id
+            ))
+        })?;
+        record.id = Some(uuid.to_string());
+    }
+    templates::validate_declarations(&record.variables)
+}
+
+/// Accept a bundle or a bare array of records
+fn from_json(text: &str) -> Result<Vec<PromptRecord>> {
+    let value: serde_json::Value = serde_json::from_str(text)?;
+    if value.is_array() {
+        return Ok(serde_json::from_value(value)?);
+    }
+
+    let bundle: Bundle = serde_json::from_value(value)?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(AmpError::ValidationError(format!(
+            "Bundle version {} is newer than supported ({})",
+            bundle.version, BUNDLE_VERSION
+        )));
+    }
+    Ok(bundle.prompts)
+}
+
+fn to_csv(records: &[PromptRecord]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in records {
+        let variables = if record.variables.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&record.variables)?)
+        };
+        writer
+            .serialize(CsvRow {
+                id: record.id.clone(),
+                title: record.title.clone(),
+                description: record.description.clone(),
+                content: record.content.clone(),
+                tags: Some(record.tags.join(", ")).filter(|t| !t.is_empty()),
+                variables,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+            })
+            .map_err(csv_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AmpError::Other(format!("Failed to write CSV: {}", e)))?;
+    String::from_utf8(bytes).map_err(|e| AmpError::Other(e.to_string()))
+}
+
+fn from_csv(text: &str) -> Result<Vec<PromptRecord>> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let mut records = Vec::new();
+
+    for row in reader.deserialize::<CsvRow>() {
+        let row = row.map_err(csv_error)?;
+        let variables = match row.variables.as_deref().map(str::trim) {
+            Some(json) if !json.is_empty() => serde_json::from_str(json)?,
+            _ => vec![],
+        };
+        records.push(PromptRecord {
+            id: row.id.filter(|id| !id.trim().is_empty()),
+            title: row.title,
+            description: row.description.filter(|d| !d.is_empty()),
+            content: row.content,
+            tags: row
+                .tags
+                .unwrap_or_default()
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            variables,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        });
+    }
+
+    Ok(records)
+}
+
+fn csv_error(err: csv::Error) -> AmpError {
+    AmpError::ValidationError(format!("Invalid CSV: {}", err))
+}
+
+fn read_markdown_dir(dir: &Path) -> Result<Vec<PromptRecord>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("md"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| parse_markdown_file(path, &fs::read_to_string(path)?))
+        .collect()
+}
+
+fn parse_markdown_file(path: &Path, text: &str) -> Result<PromptRecord> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Untitled");
+    markdown::parse(text, stem).map_err(|e| match e {
+        AmpError::ValidationError(msg) => {
+            AmpError::ValidationError(format!("{}: {}", path.display(), msg))
+        },
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::prompts::{create_prompt, delete_prompt, get_prompt, list_prompts};
+    use crate::db::test_support::setup;
+    use crate::runtime;
+    use crate::templates::{VariableDecl, VariableType};
+
+    fn record(id: Option<&str>, title: &str) -> PromptRecord {
+        PromptRecord {
+            id: id.map(String::from),
+            title: title.into(),
+            description: None,
+            content: format!("{} content", title),
+            tags: vec!["shared".into()],
+            variables: vec![],
+            created_at: Some(100),
+            updated_at: Some(200),
+        }
+    }
+
+    #[test]
+    fn test_json_and_csv_round_trip() {
+        let mut records = vec![
+            record(Some("7b0c3c8e-0000-4000-8000-000000000000"), "A"),
+            record(None, "B, \"quoted\""),
+        ];
+        records[1].content = "line 1\nline 2".into();
+        records[1].tags = vec!["x".into(), "y".into()];
+
+        for format in [Format::Json, Format::Csv] {
+            let text = encode(&records, format).unwrap();
+            assert_eq!(decode(&text, format).unwrap(), records, "{:?}", format);
+        }
+
+        // A bare array is accepted too
+        let array = serde_json::to_string(&records).unwrap();
+        assert_eq!(decode(&array, Format::Json).unwrap(), records);
+    }
+
+    #[test]
+    fn test_markdown_directory_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = vec![
+            record(Some("7b0c3c8e-0000-4000-8000-000000000000"), "A"),
+            record(Some("8c1d4d9f-0000-4000-8000-000000000000"), "A"),
+        ];
+
+        write(&records, dir.path(), Format::Markdown).unwrap();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert_eq!(Format::detect(dir.path()), Format::Markdown);
+        assert_eq!(read(dir.path(), Format::Markdown).unwrap(), records);
+    }
+
+    #[test]
+    fn test_import_conflict_strategies() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let existing = create_prompt("Local".into(), None, "mine".into(), None, None).await?;
+            let incoming = || {
+                vec![
+                    record(Some(&existing.id), "Remote"),
+                    record(Some("7b0c3c8e-0000-4000-8000-000000000000"), "New"),
+                ]
+            };
+
+            let report = import_records(incoming(), ConflictStrategy::Skip).await?;
+            assert_eq!((report.created, report.skipped), (1, 1));
+            assert_eq!(get_prompt(&existing.id).await?.unwrap().title, "Local");
+
+            // The new prompt kept its id and timestamps
+            let new = get_prompt("7b0c3c8e-0000-4000-8000-000000000000")
+                .await?
+                .unwrap();
+            assert_eq!((new.created_at, new.updated_at), (100, 200));
+            assert_eq!(new.tags.as_deref(), Some(r#"["shared"]"#));
+
+            let report = import_records(incoming(), ConflictStrategy::Overwrite).await?;
+            assert_eq!(report.overwritten, 2);
+            let updated = get_prompt(&existing.id).await?.unwrap();
+            assert_eq!(updated.title, "Remote");
+            assert_eq!(updated.content, "Remote content");
+
+            let report = import_records(incoming(), ConflictStrategy::Duplicate).await?;
+            assert_eq!(report.duplicated, 2);
+            assert_eq!(list_prompts().await?.len(), 4);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_records() {
+        let _guard = setup();
+
+        let bad_id = record(Some("not-a-uuid"), "A");
+        let err = runtime::block_on(import_records(vec![bad_id], ConflictStrategy::Skip));
+        assert_eq!(err.unwrap_err().category(), "validation");
+
+        // Nothing is imported when any record is invalid
+        let batch = vec![record(None, "ok"), record(None, " ")];
+        assert!(runtime::block_on(import_records(batch, ConflictStrategy::Skip)).is_err());
+        assert!(runtime::block_on(list_prompts()).unwrap().is_empty());
+
+        // Ids repeated within a batch are rejected up front too
+        let id = "7b0c3c8e-0000-4000-8000-000000000000";
+        let batch = vec![record(Some(id), "A"), record(Some(&id.to_uppercase()), "B")];
+        let err = runtime::block_on(import_records(batch, ConflictStrategy::Overwrite));
+        assert_eq!(err.unwrap_err().category(), "validation");
+        assert!(runtime::block_on(list_prompts()).unwrap().is_empty());
+
+        // So are bad variable declarations in later records
+        let mut bad_variable = record(None, "C");
+        bad_variable.variables = vec![VariableDecl {
+            name: "1st".into(),
+            kind: VariableType::String,
+            default: None,
+            required: true,
+            description: None,
+        }];
+        let batch = vec![record(None, "A"), record(None, "B"), bad_variable];
+        let err = runtime::block_on(import_records(batch, ConflictStrategy::Skip));
+        assert_eq!(err.unwrap_err().category(), "validation");
+        assert!(runtime::block_on(list_prompts()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_import_matches_ids_case_insensitively() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let existing = create_prompt("Local".into(), None, "mine".into(), None, None).await?;
+            let upper = existing.id.to_uppercase();
+
+            let report =
+                import_records(vec![record(Some(&upper), "Remote")], ConflictStrategy::Skip)
+                    .await?;
+            assert_eq!((report.created, report.skipped), (0, 1));
+
+            let id = "7B0C3C8E-0000-4000-8000-000000000000";
+            import_records(vec![record(Some(id), "New")], ConflictStrategy::Skip).await?;
+            assert!(get_prompt(&id.to_lowercase()).await?.is_some());
+            assert_eq!(list_prompts().await?.len(), 2);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_import_overwrite_restores_trashed_prompt() -> Result<()> {
+        let _guard = setup();
+
+        runtime::block_on(async {
+            let existing = create_prompt("Local".into(), None, "mine".into(), None, None).await?;
+            delete_prompt(existing.id.clone()).await?;
+
+            let incoming = || vec![record(Some(&existing.id), "Remote")];
+            let report = import_records(incoming(), ConflictStrategy::Skip).await?;
+            assert_eq!(report.skipped, 1);
+            assert!(get_prompt(&existing.id)
+                .await?
+                .unwrap()
+                .deleted_at
+                .is_some());
+
+            let report = import_records(incoming(), ConflictStrategy::Overwrite).await?;
+            assert_eq!((report.overwritten, report.restored), (1, 1));
+            let restored = get_prompt(&existing.id).await?.unwrap();
+            assert_eq!(restored.title, "Remote");
+            assert_eq!(restored.deleted_at, None);
+            assert_eq!(list_prompts().await?.len(), 1);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,8 @@
+//! MCP servers configured for Amp
+//!
+//! [`model`] validates server configurations and `amp.mcpPermissions` rules
+//! and previews `${VAR}` expansion. [`store`] edits them in the Amp
+//! settings file, keeping disabled servers aside until they are enabled.
+
+pub mod model;
+pub mod store;
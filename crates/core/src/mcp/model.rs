@@ -0,0 +1,428 @@
+//! MCP server configurations (`schemas/mcp-server.json`) and server
+//! permission rules (`schemas/mcp-permission.json`)
+//!
+//! String fields may reference environment variables as `${VAR}`, which
+//! Amp expands when it starts the server. [`McpServer::preview`] shows the
+//! result without revealing variables that look like secrets.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::errors::{AmpError, Result};
+use crate::permissions::evaluate::glob_matches;
+
+/// Shown instead of the value of secret-looking variables
+pub const MASK: &str = "********";
+
+/// Words marking a variable as secret in previews
+const SECRET_WORDS: &[&str] = &["TOKEN", "SECRET", "KEY", "PASSWORD", "AUTH", "CREDENTIAL"];
+
+/// A server started by Amp
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalServer {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+/// A server reached over HTTP
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteServer {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum McpServer {
+    Local(LocalServer),
+    Remote(RemoteServer),
+}
+
+/// An environment variable referenced by a server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Variable {
+    pub name: String,
+    pub set: bool,
+    /// Masked in the preview
+    pub secret: bool,
+}
+
+/// A server configuration with its variables expanded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Preview {
+    /// The configuration as Amp would use it; unset variables stay as
+    /// `${VAR}`
+    pub config: Value,
+    /// Sorted by name
+    pub variables: Vec<Variable>,
+}
+
+impl McpServer {
+    /// Parse and validate a server configuration
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let fields = value.as_object().ok_or_else(|| {
+            AmpError::ValidationError("MCP server config must be an object".into())
+        })?;
+        let (kind, allowed): (&str, &[&str]) =
+            match (fields.contains_key("command"), fields.contains_key("url")) {
+                (true, false) => ("local", &["command", "args", "env"]),
+                (false, true) => ("remote", &["url", "headers"]),
+                (true, true) => {
+                    return Err(AmpError::ValidationError(
+                        "An MCP server has either 'command' (local) or 'url' (remote), not both"
+                            .into(),
+                    ))
+                },
+                (false, false) => {
+                    return Err(AmpError::ValidationError(
+                        "An MCP server needs 'command' (local) or 'url' (remote)".into(),
+                    ))
+                },
+            };
+        if let Some(key) = fields.keys().find(|k| !allowed.contains(&k.as_str())) {
+            return Err(AmpError::ValidationError(format!(
+                "Unknown field '{}' for a {} MCP server",
+                key, kind
+            )));
+        }
+
+        let invalid = |e: serde_json::Error| {
+            AmpError::ValidationError(format!("Invalid {} MCP server: {}", kind, e))
+        };
+        let server = if kind == "local" {
+            McpServer::Local(serde_json::from_value(value.clone()).map_err(invalid)?)
+        } else {
+            McpServer::Remote(serde_json::from_value(value.clone()).map_err(invalid)?)
+        };
+
+        match &server {
+            McpServer::Local(local) if local.command.trim().is_empty() => Err(
+                AmpError::ValidationError("MCP server command cannot be empty".into()),
+            ),
+            McpServer::Remote(remote) if !looks_like_url(&remote.url) => Err(
+                AmpError::ValidationError(format!("MCP server url '{}' is not a URL", remote.url)),
+            ),
+            _ => Ok(server),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            McpServer::Local(_) => "local",
+            McpServer::Remote(_) => "remote",
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// The configuration with `${VAR}` replaced using `lookup`
+    pub fn preview(&self, lookup: &dyn Fn(&str) -> Option<String>) -> Preview {
+        let mut variables = Vec::new();
+        let config = expand_value(&self.to_value(), lookup, &mut variables);
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+        Preview { config, variables }
+    }
+}
+
+/// A scheme followed by `:`, or a URL built from a variable
+fn looks_like_url(url: &str) -> bool {
+    if url.starts_with("${") {
+        return true;
+    }
+    url.split_once(':').is_some_and(|(scheme, rest)| {
+        !rest.is_empty()
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    })
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_WORDS.iter().any(|w| name.contains(w))
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace `${VAR}` in `text`, recording each variable once
+pub fn expand(
+    text: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    variables: &mut Vec<Variable>,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(name) = after
+            .find('}')
+            .map(|end| &after[..end])
+            .filter(|n| is_variable_name(n))
+        else {
+            out.push_str("${");
+            rest = after;
+            continue;
+        };
+
+        let value = lookup(name);
+        let secret = is_secret(name);
+        if !variables.iter().any(|v| v.name == name) {
+            variables.push(Variable {
+                name: name.to_string(),
+                set: value.is_some(),
+                secret,
+            });
+        }
+        match value {
+            Some(_) if secret => out.push_str(MASK),
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + name.len() + 3]),
+        }
+        rest = &after[name.len() + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn expand_value(
+    value: &Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    variables: &mut Vec<Variable>,
+) -> Value {
+    match value {
+        Value::String(text) => Value::String(expand(text, lookup, variables)),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| expand_value(v, lookup, variables))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), expand_value(v, lookup, variables)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpAction {
+    Allow,
+    Reject,
+}
+
+/// A rule of `amp.mcpPermissions`: the first rule matching a server decides
+/// whether Amp may start it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpPermission {
+    /// `command` and `args` globs for local servers, `url` for remote ones
+    pub matches: Map<String, Value>,
+    pub action: McpAction,
+}
+
+impl McpPermission {
+    /// Parse and validate a rule
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let rule: Self = serde_json::from_value(value.clone())
+            .map_err(|e| AmpError::ValidationError(format!("Invalid MCP permission: {}", e)))?;
+
+        let keys: Vec<&str> = rule.matches.keys().map(String::as_str).collect();
+        let local = keys.iter().all(|k| ["command", "args"].contains(k));
+        let remote = keys == ["url"];
+        if !local && !remote {
+            return Err(AmpError::ValidationError(format!(
+                "MCP permission matches either 'command'/'args' or 'url', not {}",
+                keys.join(", ")
+            )));
+        }
+        for (key, pattern) in &rule.matches {
+            let valid = match pattern {
+                Value::String(_) => true,
+                Value::Array(items) => key == "args" && items.iter().all(Value::is_string),
+                _ => false,
+            };
+            if !valid {
+                return Err(AmpError::ValidationError(format!(
+                    "MCP permission '{}' must be a pattern{}",
+                    key,
+                    if key == "args" {
+                        " or a list of patterns"
+                    } else {
+                        ""
+                    }
+                )));
+            }
+        }
+        Ok(rule)
+    }
+
+    /// The rule matching exactly `server`
+    pub fn exact(server: &McpServer, action: McpAction) -> Self {
+        let mut matches = Map::new();
+        match server {
+            McpServer::Local(local) => {
+                matches.insert("command".into(), Value::from(local.command.clone()));
+                matches.insert("args".into(), Value::from(local.args.clone()));
+            },
+            McpServer::Remote(remote) => {
+                matches.insert("url".into(), Value::from(remote.url.clone()));
+            },
+        }
+        Self { matches, action }
+    }
+
+    /// Whether the rule applies to `server`
+    ///
+    /// `args` given as one pattern is matched against the arguments joined
+    /// by spaces; a list is matched argument by argument.
+    pub fn applies(&self, server: &McpServer) -> bool {
+        let text = |key: &str| self.matches.get(key).and_then(|v| v.as_str());
+        match server {
+            McpServer::Local(local) => {
+                if self.matches.contains_key("url") {
+                    return false;
+                }
+                let command = text("command").is_none_or(|p| glob_matches(p, &local.command));
+                let args = match self.matches.get("args") {
+                    None => true,
+                    Some(Value::String(p)) => glob_matches(p, &local.args.join(" ")),
+                    Some(Value::Array(patterns)) => {
+                        patterns.len() == local.args.len()
+                            && patterns
+                                .iter()
+                                .zip(&local.args)
+                                .all(|(p, arg)| p.as_str().is_some_and(|p| glob_matches(p, arg)))
+                    },
+                    Some(_) => false,
+                };
+                command && args
+            },
+            McpServer::Remote(remote) => {
+                let local_keys = ["command", "args"];
+                !local_keys.iter().any(|k| self.matches.contains_key(*k))
+                    && text("url").is_none_or(|p| glob_matches(p, &remote.url))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_server_validation() {
+        let local = McpServer::from_value(&json!({"command": "npx", "args": ["-y", "x"]})).unwrap();
+        assert_eq!(local.kind(), "local");
+        assert_eq!(
+            local.to_value(),
+            json!({"command": "npx", "args": ["-y", "x"]})
+        );
+        let remote = McpServer::from_value(&json!({"url": "${SRC}/mcp"})).unwrap();
+        assert_eq!(remote.kind(), "remote");
+
+        let invalid = [
+            json!("npx"),
+            json!({}),
+            json!({"command": "npx", "url": "https://x"}),
+            json!({"command": "npx", "headers": {}}),
+            json!({"command": " "}),
+            json!({"command": "npx", "env": {"A": 1}}),
+            json!({"url": "mcp.example.com"}),
+        ];
+        for value in invalid {
+            assert!(McpServer::from_value(&value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_preview() {
+        let server = McpServer::from_value(&json!({
+            "url": "${SRC_ENDPOINT}/.api/mcp/v1",
+            "headers": {"Authorization": "token ${SRC_ACCESS_TOKEN}", "X-Team": "${TEAM} ${}"}
+        }))
+        .unwrap();
+        let lookup = |name: &str| match name {
+            "SRC_ENDPOINT" => Some("https://sg.example.com".to_string()),
+            "SRC_ACCESS_TOKEN" => Some("sgp_123".to_string()),
+            _ => None,
+        };
+
+        let preview = server.preview(&lookup);
+        assert_eq!(
+            preview.config,
+            json!({
+                "url": "https://sg.example.com/.api/mcp/v1",
+                "headers": {"Authorization": "token ********", "X-Team": "${TEAM} ${}"}
+            })
+        );
+        let vars: Vec<_> = preview
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.set, v.secret))
+            .collect();
+        assert_eq!(
+            vars,
+            vec![
+                ("SRC_ACCESS_TOKEN", true, true),
+                ("SRC_ENDPOINT", true, false),
+                ("TEAM", false, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_permissions() {
+        let playwright = McpServer::from_value(&json!({
+            "command": "npx", "args": ["-y", "@playwright/mcp@latest", "--headless"]
+        }))
+        .unwrap();
+        let remote =
+            McpServer::from_value(&json!({"url": "https://evil.malicious.com/mcp"})).unwrap();
+
+        let rule = |v: Value| McpPermission::from_value(&v).unwrap();
+        assert!(rule(
+            json!({"matches": {"command": "npx", "args": "* @playwright/mcp@*"}, "action": "allow"})
+        )
+        .applies(&playwright));
+        assert!(
+            !rule(json!({"matches": {"command": "python"}, "action": "allow"}))
+                .applies(&playwright)
+        );
+        assert!(
+            rule(json!({"matches": {"url": "*.malicious.com*"}, "action": "reject"}))
+                .applies(&remote)
+        );
+        assert!(!rule(json!({"matches": {"url": "*"}, "action": "reject"})).applies(&playwright));
+
+        let exact = McpPermission::exact(&playwright, McpAction::Allow);
+        assert!(exact.applies(&playwright));
+        assert!(McpPermission::from_value(&serde_json::to_value(&exact).unwrap()).is_ok());
+
+        let invalid = [
+            json!({"matches": {"url": "x", "command": "y"}, "action": "allow"}),
+            json!({"matches": {"command": ["a"]}, "action": "allow"}),
+            json!({"matches": {"url": "x"}, "action": "ask"}),
+        ];
+        for value in invalid {
+            assert!(McpPermission::from_value(&value).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Reading and writing MCP server settings
+//!
+//! Enabled servers live in `amp.mcpServers` and their permission rules in
+//! `amp.mcpPermissions` of the Amp settings file. The schema has no way to
+//! switch a server off, so disabled servers are moved to
+//! `amp-extras/mcp-servers.json` until they are enabled again.
+//!
+//! Entries are kept as written: one invalid server or rule does not stop
+//! the others from being listed and edited.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::model::{McpAction, McpPermission, McpServer, Preview};
+use crate::errors::{AmpError, Result};
+use crate::settings;
+
+pub const SERVERS_KEY: &str = "amp.mcpServers";
+pub const PERMISSIONS_KEY: &str = "amp.mcpPermissions";
+
+/// Where the servers live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpStore {
+    /// Amp settings file
+    pub settings_path: PathBuf,
+    /// Disabled servers
+    pub path: PathBuf,
+}
+
+/// Servers and rules as written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct McpConfig {
+    pub servers: Map<String, Value>,
+    pub disabled: Map<String, Value>,
+    pub permissions: Vec<Value>,
+}
+
+/// The first rule applying to a server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PermissionMatch {
+    pub index: usize,
+    pub action: McpAction,
+}
+
+/// A server as listed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct McpEntry {
+    pub name: String,
+    pub enabled: bool,
+    /// `local` or `remote`; `None` if the config is invalid
+    pub kind: Option<&'static str>,
+    pub config: Value,
+    pub error: Option<String>,
+    pub preview: Option<Preview>,
+    pub permission: Option<PermissionMatch>,
+}
+
+impl McpStore {
+    pub fn new(settings_path: &Path, path: &Path) -> Self {
+        Self {
+            settings_path: settings_path.to_path_buf(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// The Amp settings file and `~/.config/amp-extras/mcp-servers.json`
+    pub fn default_paths() -> Self {
+        Self::new(
+            &settings::settings_path(),
+            &settings::config_home().join("amp-extras/mcp-servers.json"),
+        )
+    }
+
+    pub fn load(&self) -> Result<McpConfig> {
+        let settings = settings::read(&self.settings_path)?;
+        let servers = match settings.get(SERVERS_KEY) {
+            None => Map::new(),
+            Some(Value::Object(map)) => map.clone(),
+            Some(_) => {
+                return Err(AmpError::ConfigError(format!(
+                    "'{}' must be an object of servers",
+                    SERVERS_KEY
+                )))
+            },
+        };
+        let permissions = match settings.get(PERMISSIONS_KEY) {
+            None => Vec::new(),
+            Some(Value::Array(items)) => items.clone(),
+            Some(_) => {
+                return Err(AmpError::ConfigError(format!(
+                    "'{}' must be a list of rules",
+                    PERMISSIONS_KEY
+                )))
+            },
+        };
+        let disabled = match fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| {
+                AmpError::ConfigError(format!("Invalid {}: {}", self.path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(McpConfig {
+            servers,
+            disabled,
+            permissions,
+        })
+    }
+
+    /// Load, change and write back what changed
+    pub fn edit<F>(&self, change: F) -> Result<McpConfig>
+    where
+        F: FnOnce(&mut McpConfig) -> Result<()>,
+    {
+        let before = self.load()?;
+        let mut config = before.clone();
+        change(&mut config)?;
+
+        if config.disabled != before.disabled {
+            let mut text = serde_json::to_string_pretty(&config.disabled)?;
+            text.push('\n');
+            settings::write_atomic(&self.path, &text)?;
+        }
+        if config.servers != before.servers {
+            let servers = Value::Object(config.servers.clone());
+            settings::write_key(&self.settings_path, SERVERS_KEY, &servers)?;
+        }
+        if config.permissions != before.permissions {
+            let permissions = Value::Array(config.permissions.clone());
+            settings::write_key(&self.settings_path, PERMISSIONS_KEY, &permissions)?;
+        }
+        Ok(config)
+    }
+}
+
+impl McpConfig {
+    fn require_new(&self, name: &str) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(AmpError::ValidationError(
+                "MCP server name cannot be empty".into(),
+            ));
+        }
+        if self.servers.contains_key(name) || self.disabled.contains_key(name) {
+            return Err(AmpError::ValidationError(format!(
+                "MCP server '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// The map holding `name`
+    fn holder(&mut self, name: &str) -> Result<&mut Map<String, Value>> {
+        if self.servers.contains_key(name) {
+            Ok(&mut self.servers)
+        } else if self.disabled.contains_key(name) {
+            Ok(&mut self.disabled)
+        } else {
+            Err(AmpError::ValidationError(format!(
+                "MCP server '{}' not found",
+                name
+            )))
+        }
+    }
+
+    pub fn add(&mut self, name: &str, server: &McpServer, enabled: bool) -> Result<()> {
+        self.require_new(name)?;
+        let target = if enabled {
+            &mut self.servers
+        } else {
+            &mut self.disabled
+        };
+        target.insert(name.to_string(), server.to_value());
+        Ok(())
+    }
+
+    /// Apply a patch to a server: each field of `patch` replaces the
+    /// server's, and `null` removes one
+    ///
+    /// Rules written for exactly the old config follow the server.
+    pub fn update(&mut self, name: &str, patch: &Value) -> Result<McpServer> {
+        let patch = patch
+            .as_object()
+            .ok_or_else(|| AmpError::ValidationError("Server patch must be an object".into()))?;
+        let holder = self.holder(name)?;
+        let mut value = holder[name].clone();
+        let fields = value.as_object_mut().ok_or_else(|| {
+            AmpError::ValidationError(format!("MCP server '{}' is not an object", name))
+        })?;
+        for (key, field) in patch {
+            if field.is_null() {
+                fields.remove(key);
+            } else {
+                fields.insert(key.clone(), field.clone());
+            }
+        }
+
+        let server = McpServer::from_value(&value)?;
+        let old = McpServer::from_value(&holder[name]).ok();
+        holder.insert(name.to_string(), server.to_value());
+
+        if let Some(old) = old {
+            for value in &mut self.permissions {
+                let exact = McpPermission::from_value(value)
+                    .ok()
+                    .filter(|rule| *rule == McpPermission::exact(&old, rule.action));
+                if let Some(rule) = exact {
+                    *value = serde_json::to_value(McpPermission::exact(&server, rule.action))?;
+                }
+            }
+        }
+        Ok(server)
+    }
+
+    /// Remove a server and the rules written for exactly it
+    pub fn remove(&mut self, name: &str) -> Result<Value> {
+        let value = self.holder(name)?.remove(name).unwrap_or_default();
+        if let Ok(server) = McpServer::from_value(&value) {
+            self.remove_exact(&server);
+        }
+        Ok(value)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let value = self.holder(name)?.remove(name).unwrap_or_default();
+        let target = if enabled {
+            &mut self.servers
+        } else {
+            &mut self.disabled
+        };
+        target.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Drop the rules written for exactly `server`
+    fn remove_exact(&mut self, server: &McpServer) {
+        self.permissions.retain(|value| {
+            !McpPermission::from_value(value)
+                .is_ok_and(|rule| rule == McpPermission::exact(server, rule.action))
+        });
+    }
+
+    /// Allow or reject exactly `server` ahead of every other rule, or drop
+    /// its rule with `None`
+    pub fn set_permission(&mut self, server: &McpServer, action: Option<McpAction>) {
+        self.remove_exact(server);
+        if let Some(action) = action {
+            let rule =
+                serde_json::to_value(McpPermission::exact(server, action)).unwrap_or_default();
+            self.permissions.insert(0, rule);
+        }
+    }
+
+    /// Valid rules with their index, and errors of the others
+    pub fn permission_rules(&self) -> (Vec<(usize, McpPermission)>, Vec<String>) {
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for (index, value) in self.permissions.iter().enumerate() {
+            match McpPermission::from_value(value) {
+                Ok(rule) => rules.push((index, rule)),
+                Err(e) => errors.push(format!("Rule {}: {}", index, e)),
+            }
+        }
+        (rules, errors)
+    }
+
+    /// Every server, sorted by name
+    pub fn entries(&self, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<McpEntry> {
+        let (rules, _) = self.permission_rules();
+        let mut entries: Vec<McpEntry> = self
+            .servers
+            .iter()
+            .map(|(name, config)| (name, config, true))
+            .chain(
+                self.disabled
+                    .iter()
+                    .map(|(name, config)| (name, config, false)),
+            )
+            .map(|(name, config, enabled)| {
+                let parsed = McpServer::from_value(config);
+                let server = parsed.as_ref().ok();
+                McpEntry {
+                    name: name.clone(),
+                    enabled,
+                    kind: server.map(McpServer::kind),
+                    config: config.clone(),
+                    error: parsed.as_ref().err().map(|e| e.to_string()),
+                    preview: server.map(|s| s.preview(lookup)),
+                    permission: server.and_then(|s| {
+                        rules
+                            .iter()
+                            .find(|(_, rule)| rule.applies(s))
+                            .map(|(index, rule)| PermissionMatch {
+                                index: *index,
+                                action: rule.action,
+                            })
+                    }),
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store(dir: &Path) -> McpStore {
+        McpStore::new(
+            &dir.join("amp/settings.json"),
+            &dir.join("amp-extras/mcp-servers.json"),
+        )
+    }
+
+    fn server(value: Value) -> McpServer {
+        McpServer::from_value(&value).unwrap()
+    }
+
+    #[test]
+    fn test_add_disable_enable_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        fs::create_dir_all(dir.path().join("amp")).unwrap();
+        fs::write(&store.settings_path, "{\n  // keep\n  \"amp.x\": 1\n}\n").unwrap();
+
+        let playwright = server(json!({"command": "npx", "args": ["@playwright/mcp"]}));
+        store
+            .edit(|c| {
+                c.add("playwright", &playwright, true)?;
+                c.set_permission(&playwright, Some(McpAction::Allow));
+                Ok(())
+            })
+            .unwrap();
+        assert!(store
+            .edit(|c| c.add("playwright", &playwright, false))
+            .is_err());
+
+        let text = fs::read_to_string(&store.settings_path).unwrap();
+        assert!(text.starts_with("{\n  // keep\n  \"amp.x\": 1,\n"));
+        let settings = settings::read(&store.settings_path).unwrap();
+        assert_eq!(settings[SERVERS_KEY]["playwright"]["command"], "npx");
+        assert_eq!(settings[PERMISSIONS_KEY][0]["action"], "allow");
+
+        // Disabling moves the server out of the settings file
+        let config = store.edit(|c| c.set_enabled("playwright", false)).unwrap();
+        assert!(config.servers.is_empty());
+        let settings = settings::read(&store.settings_path).unwrap();
+        assert_eq!(settings[SERVERS_KEY], json!({}));
+        let entries = store.load().unwrap().entries(&|_| None);
+        assert!(!entries[0].enabled);
+        assert_eq!(
+            entries[0].permission.as_ref().unwrap().action,
+            McpAction::Allow
+        );
+
+        let config = store.edit(|c| c.set_enabled("playwright", true)).unwrap();
+        assert!(config.disabled.is_empty());
+        assert!(config.servers.contains_key("playwright"));
+
+        let config = store.edit(|c| c.remove("playwright").map(|_| ())).unwrap();
+        assert!(config.servers.is_empty());
+        assert!(config.permissions.is_empty());
+        assert!(store.edit(|c| c.remove("playwright").map(|_| ())).is_err());
+    }
+
+    #[test]
+    fn test_update_keeps_exact_permission() {
+        let mut config = McpConfig::default();
+        let remote = server(json!({"url": "https://a.example.com/mcp"}));
+        config.add("a", &remote, true).unwrap();
+        config.set_permission(&remote, Some(McpAction::Reject));
+        config
+            .permissions
+            .push(json!({"matches": {"url": "*"}, "action": "allow"}));
+
+        let updated = config
+            .update(
+                "a",
+                &json!({"url": "https://b.example.com/mcp", "headers": {"X": "${X}"}}),
+            )
+            .unwrap();
+        assert_eq!(updated.kind(), "remote");
+        assert_eq!(
+            config.permissions,
+            vec![
+                json!({"matches": {"url": "https://b.example.com/mcp"}, "action": "reject"}),
+                json!({"matches": {"url": "*"}, "action": "allow"}),
+            ]
+        );
+
+        // Invalid patches change nothing
+        let before = config.clone();
+        assert!(config.update("a", &json!({"command": "npx"})).is_err());
+        assert!(config.update("a", &json!({"url": null})).is_err());
+        assert_eq!(config, before);
+
+        let entries = config.entries(&|_| None);
+        assert_eq!(entries[0].permission.as_ref().unwrap().index, 0);
+        assert_eq!(entries[0].preview.as_ref().unwrap().variables[0].name, "X");
+    }
+}
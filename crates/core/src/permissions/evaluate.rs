@@ -0,0 +1,300 @@
+//! What Amp does with a tool call
+//!
+//! Rules are tried in order and the first one that applies decides. A rule
+//! applies when its `tool` glob matches the tool name, its `context` (if
+//! any) is the caller's, and every entry of `matches` matches the argument
+//! of that name:
+//!
+//! - a string is a glob where `*` matches anything, or a `/regex/` that
+//!   may match any part of the value; numbers and booleans are matched as
+//!   text
+//! - a list matches if any of its patterns does
+//! - an object matches an object argument field by field
+//! - `true`, `false`, numbers and `null` must equal the argument
+//!
+//! Disabled rules are skipped, as they are not in `amp.permissions`.
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::model::{PermissionAction, PermissionRule, RuleContext};
+
+/// One rule tried during an evaluation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceStep {
+    /// Index of the rule (0-based, disabled rules included)
+    pub index: usize,
+    pub applies: bool,
+    /// Why the rule applies or not
+    pub reason: String,
+}
+
+/// Outcome of a tool call
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub tool: String,
+    pub context: RuleContext,
+    /// Index of the deciding rule, if any
+    pub index: Option<usize>,
+    /// Action of the deciding rule; `None` leaves the call to Amp's defaults
+    pub action: Option<PermissionAction>,
+    /// Delegate program
+    pub to: Option<String>,
+    pub rule: Option<PermissionRule>,
+    pub trace: Vec<TraceStep>,
+}
+
+/// Compile a `/regex/` pattern (`None` for globs)
+pub fn regex_pattern(pattern: &str) -> Option<Result<Regex, regex::Error>> {
+    let body = pattern
+        .strip_prefix('/')
+        .and_then(|p| p.strip_suffix('/'))
+        .filter(|_| pattern.len() >= 2)?;
+    Some(Regex::new(body))
+}
+
+/// Whether `text` matches the glob `pattern` as a whole
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the pattern is the text
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Whether one string pattern matches `text`, or why not
+fn match_text(pattern: &str, text: &str) -> Result<bool, String> {
+    match regex_pattern(pattern) {
+        Some(Ok(regex)) => Ok(regex.is_match(text)),
+        Some(Err(e)) => Err(format!("invalid regex {}: {}", pattern, e)),
+        None => Ok(glob_matches(pattern, text)),
+    }
+}
+
+fn show(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() > 80 {
+        let short: String = text.chars().take(77).collect();
+        format!("{}...", short)
+    } else {
+        text
+    }
+}
+
+/// Check `actual` (the argument at `path`) against `pattern`
+fn match_value(pattern: &Value, actual: Option<&Value>, path: &str) -> Result<(), String> {
+    match pattern {
+        Value::String(_) | Value::Array(_) => {
+            let text = match actual {
+                Some(Value::String(s)) => s.clone(),
+                Some(v @ (Value::Number(_) | Value::Bool(_))) => v.to_string(),
+                Some(v) => return Err(format!("{}: {} is not text", path, show(v))),
+                None => return Err(format!("{}: missing", path)),
+            };
+            let patterns: Vec<&str> = match pattern {
+                Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+                _ => pattern.as_str().into_iter().collect(),
+            };
+            for p in &patterns {
+                if match_text(p, &text).map_err(|e| format!("{}: {}", path, e))? {
+                    return Ok(());
+                }
+            }
+            Err(format!(
+                "{}: {} does not match {}",
+                path,
+                show(&Value::String(text)),
+                show(pattern)
+            ))
+        },
+        Value::Object(fields) => {
+            let Some(Value::Object(actual)) = actual else {
+                return Err(match actual {
+                    Some(v) => format!("{}: {} is not an object", path, show(v)),
+                    None => format!("{}: missing", path),
+                });
+            };
+            fields.iter().try_for_each(|(key, sub)| {
+                match_value(sub, actual.get(key), &format!("{}.{}", path, key))
+            })
+        },
+        Value::Null if matches!(actual, None | Some(Value::Null)) => Ok(()),
+        _ if actual == Some(pattern) => Ok(()),
+        _ => Err(format!(
+            "{}: {} is not {}",
+            path,
+            actual.map_or_else(|| "missing".to_string(), show),
+            show(pattern)
+        )),
+    }
+}
+
+/// Whether `rule` applies to the call, with the reason
+fn applies(
+    rule: &PermissionRule,
+    tool: &str,
+    args: &Value,
+    context: RuleContext,
+) -> (bool, String) {
+    if !rule.enabled {
+        return (false, "disabled".into());
+    }
+    if !glob_matches(&rule.tool, tool) {
+        return (
+            false,
+            format!("tool {:?} does not match {:?}", tool, rule.tool),
+        );
+    }
+    if let Some(only) = rule.context.filter(|c| *c != context) {
+        return (
+            false,
+            format!("only applies to {} calls", context_name(only)),
+        );
+    }
+
+    for (key, pattern) in rule.matches.iter().flatten() {
+        if let Err(reason) = match_value(pattern, args.get(key), key) {
+            return (false, reason);
+        }
+    }
+
+    let reason = match rule.matches.as_ref().filter(|m| !m.is_empty()) {
+        Some(m) => {
+            let keys: Vec<&str> = m.keys().map(String::as_str).collect();
+            format!("tool and {} match", keys.join(", "))
+        },
+        None => format!("tool matches {:?}", rule.tool),
+    };
+    (true, reason)
+}
+
+fn context_name(context: RuleContext) -> &'static str {
+    match context {
+        RuleContext::Thread => "thread",
+        RuleContext::Subagent => "subagent",
+    }
+}
+
+/// Walk `rules` for a call of `tool` with `args`
+pub fn evaluate(
+    rules: &[PermissionRule],
+    tool: &str,
+    args: &Value,
+    context: RuleContext,
+) -> Evaluation {
+    let mut evaluation = Evaluation {
+        tool: tool.to_string(),
+        context,
+        index: None,
+        action: None,
+        to: None,
+        rule: None,
+        trace: Vec::new(),
+    };
+
+    for (index, rule) in rules.iter().enumerate() {
+        let (applies, reason) = applies(rule, tool, args, context);
+        evaluation.trace.push(TraceStep {
+            index,
+            applies,
+            reason,
+        });
+        if applies {
+            evaluation.index = Some(index);
+            evaluation.action = Some(rule.action);
+            evaluation.to = rule.to.clone();
+            evaluation.rule = Some(rule.clone());
+            break;
+        }
+    }
+    evaluation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(values: Value) -> Vec<PermissionRule> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_matches("Bash", "Bash"));
+        assert!(!glob_matches("Bash", "Bash2"));
+        assert!(glob_matches("mcp__*", "mcp__github__search"));
+        assert!(glob_matches("*git push*", "cd x && git push origin"));
+        assert!(!glob_matches("*git push*", "git pull"));
+        assert!(glob_matches("a*b*c", "abc"));
+        assert!(!glob_matches("a*b*c", "acb"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let rules = rules(json!([
+            {"tool": "Bash", "matches": {"cmd": "*rm -rf*"}, "action": "reject", "enabled": false},
+            {"tool": "Bash", "matches": {"cmd": ["*git push*", "/^git\\s+commit/"]}, "action": "ask"},
+            {"tool": "Bash", "action": "allow", "context": "subagent"},
+            {"tool": "edit_*", "matches": {"opts": {"force": true}}, "action": "delegate", "to": "guard"},
+            {"tool": "*", "action": "allow"}
+        ]));
+
+        let e = evaluate(
+            &rules,
+            "Bash",
+            &json!({"cmd": "git   commit -m x"}),
+            RuleContext::Thread,
+        );
+        assert_eq!(e.index, Some(1));
+        assert_eq!(e.action, Some(PermissionAction::Ask));
+        assert_eq!(e.trace[0].reason, "disabled");
+
+        let e = evaluate(
+            &rules,
+            "Bash",
+            &json!({"cmd": "rm -rf /"}),
+            RuleContext::Subagent,
+        );
+        assert_eq!(e.index, Some(2));
+        let e = evaluate(
+            &rules,
+            "Bash",
+            &json!({"cmd": "rm -rf /"}),
+            RuleContext::Thread,
+        );
+        assert_eq!(e.index, Some(4));
+        assert_eq!(e.trace[2].reason, "only applies to subagent calls");
+        assert!(e.trace[1].reason.contains("does not match"));
+
+        let args = json!({"path": "a.rs", "opts": {"force": true}});
+        let e = evaluate(&rules, "edit_file", &args, RuleContext::Thread);
+        assert_eq!(e.to.as_deref(), Some("guard"));
+        let e = evaluate(
+            &rules,
+            "edit_file",
+            &json!({"opts": {}}),
+            RuleContext::Thread,
+        );
+        assert_eq!(e.index, Some(4));
+        assert_eq!(e.trace[3].reason, "opts.force: missing is not true");
+
+        let e = evaluate(&rules[..2], "Read", &json!({}), RuleContext::Thread);
+        assert_eq!((e.index, e.action), (None, None));
+    }
+}
@@ -0,0 +1,396 @@
+//! Finding rules that cannot work as intended
+//!
+//! [`lint`] reports:
+//!
+//! - rules identical to an earlier rule (for the CLI, so `note` and
+//!   `enabled` aside)
+//! - enabled rules that never apply, because an earlier enabled rule
+//!   matches every call they match
+//! - `/regex/` patterns that do not compile
+//! - `delegate` rules whose program is not on `$PATH`
+//! - tool names or globs that match no tool listed by `amp tools list`
+//!
+//! Shadowing is decided conservatively: a rule is only reported when the
+//! earlier rule's patterns cover its patterns as written (a glob covering
+//! a narrower glob, a list covering each pattern of a list, ...), so a
+//! rule that can still apply is never reported.
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::evaluate::{glob_matches, regex_pattern};
+use super::model::{PermissionAction, PermissionRule};
+use super::store::RuleLocation;
+use crate::threads::launch::AMP_BIN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    Duplicate,
+    Shadowed,
+    InvalidRegex,
+    DelegateNotFound,
+    UnknownTool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    /// Index of the rule (0-based)
+    pub index: usize,
+    pub kind: LintKind,
+    pub severity: Severity,
+    pub message: String,
+    /// Earlier rule that duplicates or shadows this one
+    pub related: Option<usize>,
+    /// Where the rule is written, if known
+    #[serde(flatten)]
+    pub location: Option<RuleLocation>,
+}
+
+/// What the checks look up outside the rules
+#[derive(Debug, Clone, Default)]
+pub struct LintEnv {
+    /// Known tool names; `None` skips the unknown tool check
+    pub tools: Option<Vec<String>>,
+    /// Why the tools could not be listed
+    pub tools_error: Option<String>,
+    /// Search path for delegate programs
+    pub path: Option<OsString>,
+}
+
+#[derive(Deserialize)]
+struct ToolListItem {
+    name: String,
+}
+
+impl LintEnv {
+    /// The process `$PATH`, without tools
+    pub fn from_system_path() -> Self {
+        Self {
+            path: std::env::var_os("PATH"),
+            ..Default::default()
+        }
+    }
+
+    /// Take the tools from `amp tools list --json`
+    pub fn load_tools(&mut self) {
+        match list_tools() {
+            Ok(tools) => self.tools = Some(tools),
+            Err(e) => self.tools_error = Some(e),
+        }
+    }
+}
+
+fn list_tools() -> std::result::Result<Vec<String>, String> {
+    let output = std::process::Command::new(AMP_BIN)
+        .args(["tools", "list", "--json"])
+        .output()
+        .map_err(|e| format!("Failed to run {} tools list: {}", AMP_BIN, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} tools list failed: {}",
+            AMP_BIN,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let items: Vec<ToolListItem> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected {} tools list output: {}", AMP_BIN, e))?;
+    Ok(items.into_iter().map(|t| t.name).collect())
+}
+
+/// Whether `program` can be run: a path to an executable file, or the name
+/// of one in a directory of `path`
+pub fn find_program(program: &str, path: Option<&OsStr>) -> bool {
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+    path.into_iter()
+        .flat_map(std::env::split_paths)
+        .any(|dir| is_executable(&dir.join(program)))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether every text matching glob `inner` matches glob `outer`, i.e.
+/// `inner` read as plain text matches `outer`
+fn glob_covers(outer: &str, inner: &str) -> bool {
+    glob_matches(outer, inner)
+}
+
+/// Whether every argument value matching `inner` also matches `outer`
+fn value_covers(outer: &Value, inner: &Value) -> bool {
+    match (outer, inner) {
+        // A list matches when one of its patterns does
+        (_, Value::Array(items)) => items.iter().all(|item| value_covers(outer, item)),
+        (Value::Array(patterns), _) => patterns.iter().any(|p| value_covers(p, inner)),
+        (Value::String(outer), Value::String(inner)) => {
+            match (regex_pattern(outer), regex_pattern(inner)) {
+                (None, None) => glob_covers(outer, inner),
+                (None, Some(_)) => outer == "*",
+                (Some(_), Some(_)) => outer == inner,
+                // A regex covers a literal it matches
+                (Some(regex), None) => {
+                    !inner.contains('*') && regex.is_ok_and(|r| r.is_match(inner))
+                },
+            }
+        },
+        // Numbers and booleans are matched as text
+        (Value::String(outer), Value::Number(_) | Value::Bool(_)) => {
+            let text = inner.to_string();
+            match regex_pattern(outer) {
+                Some(regex) => regex.is_ok_and(|r| r.is_match(&text)),
+                None => glob_matches(outer, &text),
+            }
+        },
+        (Value::Object(outer), Value::Object(inner)) => outer
+            .iter()
+            .all(|(key, o)| inner.get(key).is_some_and(|i| value_covers(o, i))),
+        _ => outer == inner,
+    }
+}
+
+/// Whether `earlier` applies to every call `later` applies to
+fn covers(earlier: &PermissionRule, later: &PermissionRule) -> bool {
+    if !glob_covers(&earlier.tool, &later.tool) {
+        return false;
+    }
+    if earlier.context.is_some() && earlier.context != later.context {
+        return false;
+    }
+    earlier.matches.iter().flatten().all(|(key, outer)| {
+        later
+            .matches
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .is_some_and(|inner| value_covers(outer, inner))
+    })
+}
+
+/// Bad `/regex/` patterns in `value`, with their argument path
+fn invalid_regexes(value: &Value, path: &str, found: &mut Vec<(String, String, String)>) {
+    match value {
+        Value::String(pattern) => {
+            if let Some(Err(e)) = regex_pattern(pattern) {
+                found.push((path.to_string(), pattern.clone(), e.to_string()));
+            }
+        },
+        Value::Array(items) => {
+            for item in items {
+                invalid_regexes(item, path, found);
+            }
+        },
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                invalid_regexes(field, &format!("{}.{}", path, key), found);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn describe(index: usize, rule: &PermissionRule) -> String {
+    format!("rule {} ({} {})", index, rule.action.as_str(), rule.tool)
+}
+
+/// Check `rules`; issues are ordered by rule index
+pub fn lint(rules: &[PermissionRule], env: &LintEnv) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |index, kind, severity, message, related| {
+        issues.push(LintIssue {
+            index,
+            kind,
+            severity,
+            message,
+            related,
+            location: None,
+        })
+    };
+
+    for (index, rule) in rules.iter().enumerate() {
+        let duplicate = rules[..index]
+            .iter()
+            .position(|earlier| earlier.same_cli_rule(rule));
+        if let Some(earlier) = duplicate {
+            issue(
+                index,
+                LintKind::Duplicate,
+                Severity::Warning,
+                format!("Same as {}", describe(earlier, &rules[earlier])),
+                Some(earlier),
+            );
+        } else if rule.enabled {
+            let shadow = rules[..index]
+                .iter()
+                .position(|earlier| earlier.enabled && covers(earlier, rule));
+            if let Some(earlier) = shadow {
+                let outcome = if rules[earlier].action == rule.action {
+                    "redundant"
+                } else {
+                    "never applies"
+                };
+                issue(
+                    index,
+                    LintKind::Shadowed,
+                    Severity::Warning,
+                    format!(
+                        "Rule is {}: {} matches every call it does",
+                        outcome,
+                        describe(earlier, &rules[earlier])
+                    ),
+                    Some(earlier),
+                );
+            }
+        }
+
+        let mut regexes = Vec::new();
+        for (key, value) in rule.matches.iter().flatten() {
+            invalid_regexes(value, key, &mut regexes);
+        }
+        for (path, pattern, error) in regexes {
+            issue(
+                index,
+                LintKind::InvalidRegex,
+                Severity::Error,
+                format!("Invalid regex {} for {}: {}", pattern, path, error),
+                None,
+            );
+        }
+
+        if let (PermissionAction::Delegate, Some(to)) = (rule.action, rule.to.as_deref()) {
+            if !find_program(to, env.path.as_deref()) {
+                issue(
+                    index,
+                    LintKind::DelegateNotFound,
+                    Severity::Error,
+                    format!("Delegate program '{}' is not on $PATH", to),
+                    None,
+                );
+            }
+        }
+
+        if let Some(tools) = &env.tools {
+            if !tools.iter().any(|t| glob_matches(&rule.tool, t)) {
+                let message = if rule.tool.contains('*') {
+                    format!("'{}' matches no known tool", rule.tool)
+                } else {
+                    format!("Unknown tool '{}'", rule.tool)
+                };
+                issue(
+                    index,
+                    LintKind::UnknownTool,
+                    Severity::Warning,
+                    message,
+                    None,
+                );
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(values: Value) -> Vec<PermissionRule> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    fn kinds(issues: &[LintIssue]) -> Vec<(usize, LintKind, Option<usize>)> {
+        issues
+            .iter()
+            .map(|i| (i.index, i.kind, i.related))
+            .collect()
+    }
+
+    #[test]
+    fn test_shadowed_and_duplicate_rules() {
+        let rules = rules(json!([
+            {"tool": "Bash", "matches": {"cmd": ["*git*", "/^ls/"]}, "action": "ask"},
+            {"tool": "Bash", "matches": {"cmd": "*git push*"}, "action": "reject"},
+            {"tool": "Bash", "matches": {"cmd": ["*git status*", "ls -la"]}, "action": "allow"},
+            {"tool": "Bash", "matches": {"cmd": "*rm*"}, "action": "allow"},
+            {"tool": "Bash", "matches": {"cmd": ["*git*", "/^ls/"]}, "action": "ask", "note": "again"},
+            {"tool": "mcp__*", "action": "ask", "context": "thread"},
+            {"tool": "mcp__github__*", "action": "allow"},
+            {"tool": "mcp__github__*", "action": "allow", "context": "thread"},
+            {"tool": "Read", "matches": {"path": "/tmp/*"}, "action": "allow", "enabled": false},
+            {"tool": "Read", "matches": {"path": "/tmp/x", "opts": {"a": 1}}, "action": "ask"}
+        ]));
+        let env = LintEnv::default();
+
+        assert_eq!(
+            kinds(&lint(&rules, &env)),
+            vec![
+                (1, LintKind::Shadowed, Some(0)),
+                (2, LintKind::Shadowed, Some(0)),
+                (4, LintKind::Duplicate, Some(0)),
+                (7, LintKind::Shadowed, Some(5)),
+            ]
+        );
+        let issues = lint(&rules, &env);
+        assert!(issues[0].message.contains("never applies"));
+        assert!(issues[3].message.contains("rule 5 (ask mcp__*)"));
+    }
+
+    #[test]
+    fn test_regexes_delegates_and_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let guard = dir.path().join("guard");
+        std::fs::write(&guard, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&guard, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let rules = rules(json!([
+            {"tool": "Bash", "matches": {"cmd": ["/(unclosed/", "ok"]}, "action": "ask"},
+            {"tool": "Bash", "action": "delegate", "to": "guard"},
+            {"tool": "Bash", "action": "delegate", "to": "missing-guard"},
+            {"tool": "edit_fiel", "action": "allow"},
+            {"tool": "mcp__*", "action": "allow"}
+        ]));
+        let env = LintEnv {
+            tools: Some(vec!["Bash".into(), "edit_file".into()]),
+            tools_error: None,
+            path: Some(dir.path().as_os_str().to_owned()),
+        };
+
+        let issues = lint(&rules, &env);
+        assert_eq!(
+            kinds(&issues),
+            vec![
+                (0, LintKind::InvalidRegex, None),
+                (2, LintKind::Shadowed, Some(1)),
+                (2, LintKind::DelegateNotFound, None),
+                (3, LintKind::UnknownTool, None),
+                (4, LintKind::UnknownTool, None),
+            ]
+        );
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert!(issues[0].message.contains("for cmd"));
+        assert!(find_program(guard.to_str().unwrap(), None));
+    }
+}
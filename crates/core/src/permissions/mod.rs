@@ -0,0 +1,13 @@
+//! Amp permission rules
+//!
+//! [`model`] is the typed rule format and [`store`] keeps the plugin's rule
+//! list, which adds disabled rules and notes to what the Amp CLI reads from
+//! `amp.permissions`. [`evaluate`] replays Amp's decision for a tool call,
+//! [`lint`] finds rules that cannot work as intended and [`suggest`]
+//! proposes rules from the tool calls in thread history.
+
+pub mod evaluate;
+pub mod lint;
+pub mod model;
+pub mod store;
+pub mod suggest;
@@ -0,0 +1,201 @@
+//! Typed permission rules (`schemas/amp-extras-permission-rule.json`)
+//!
+//! A rule is an Amp rule (`schemas/permission-rule.json`) plus the
+//! amp-extras-only `enabled` and `note` fields, which [`PermissionRule::to_cli`]
+//! leaves out of what the Amp CLI sees.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::errors::{AmpError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionAction {
+    Allow,
+    Reject,
+    Ask,
+    /// Ask the program named by `to`
+    Delegate,
+}
+
+impl PermissionAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionAction::Allow => "allow",
+            PermissionAction::Reject => "reject",
+            PermissionAction::Ask => "ask",
+            PermissionAction::Delegate => "delegate",
+        }
+    }
+}
+
+/// Where a rule applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleContext {
+    /// The main thread only
+    Thread,
+    /// Subagents only
+    Subagent,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+/// A permission rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    /// Tool name or glob pattern
+    pub tool: String,
+    /// Tool argument names to match values (globs, `/regex/`, lists, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<Map<String, Value>>,
+    pub action: PermissionAction,
+    /// Delegate program, for `delegate` rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<RuleContext>,
+    /// amp-extras only: disabled rules are not written for the CLI
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    /// amp-extras only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Fields added by newer Amp versions, kept as they are
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl PermissionRule {
+    /// Check what the schema requires beyond field types
+    pub fn validate(&self) -> Result<()> {
+        if self.tool.trim().is_empty() {
+            return Err(AmpError::ValidationError(
+                "Rule tool cannot be empty".into(),
+            ));
+        }
+
+        match (self.action, self.to.as_deref()) {
+            (PermissionAction::Delegate, None) => {
+                return Err(AmpError::ValidationError(
+                    "Delegate rules need a 'to' program".into(),
+                ));
+            },
+            (PermissionAction::Delegate, Some(to)) if to.trim().is_empty() => {
+                return Err(AmpError::ValidationError(
+                    "Delegate rules need a 'to' program".into(),
+                ));
+            },
+            (action, Some(_)) if action != PermissionAction::Delegate => {
+                return Err(AmpError::ValidationError(format!(
+                    "Only delegate rules take 'to' (this rule is '{}')",
+                    action.as_str()
+                )));
+            },
+            _ => {},
+        }
+
+        for (key, value) in self.matches.iter().flatten() {
+            if let Value::Array(items) = value {
+                if !items.iter().all(Value::is_string) {
+                    return Err(AmpError::ValidationError(format!(
+                        "Match '{}' must be a list of patterns (strings)",
+                        key
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rule as the Amp CLI reads it, without amp-extras fields
+    pub fn to_cli(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(map) = value.as_object_mut() {
+            map.remove("enabled");
+            map.remove("note");
+        }
+        value
+    }
+
+    /// Parse and validate a rule
+    pub fn from_value(value: Value) -> Result<Self> {
+        let rule: Self = serde_json::from_value(value)
+            .map_err(|e| AmpError::ValidationError(format!("Invalid rule: {}", e)))?;
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    /// Same rule for the CLI (ignoring `enabled` and `note`)
+    pub fn same_cli_rule(&self, other: &Self) -> bool {
+        self.to_cli() == other.to_cli()
+    }
+}
+
+/// Enabled rules as the value of `amp.permissions`
+pub fn cli_rules(rules: &[PermissionRule]) -> Value {
+    Value::Array(
+        rules
+            .iter()
+            .filter(|r| r.enabled)
+            .map(PermissionRule::to_cli)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_cli_projection() {
+        let rule = PermissionRule::from_value(json!({
+            "tool": "Bash",
+            "matches": {"cmd": ["*git push*"]},
+            "action": "ask",
+            "enabled": false,
+            "note": "Review pushes",
+            "future": 1
+        }))
+        .unwrap();
+        assert!(!rule.enabled);
+        assert_eq!(rule.extra["future"], 1);
+        assert_eq!(
+            rule.to_cli(),
+            json!({"tool": "Bash", "matches": {"cmd": ["*git push*"]}, "action": "ask", "future": 1})
+        );
+
+        let enabled =
+            PermissionRule::from_value(json!({"tool": "Read", "action": "allow"})).unwrap();
+        assert!(enabled.enabled);
+        assert_eq!(
+            cli_rules(&[rule, enabled]),
+            json!([{"tool": "Read", "action": "allow"}])
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = [
+            json!({"tool": " ", "action": "allow"}),
+            json!({"tool": "Bash", "action": "delegate"}),
+            json!({"tool": "Bash", "action": "allow", "to": "x"}),
+            json!({"tool": "Bash", "action": "allow", "matches": {"cmd": [1]}}),
+            json!({"tool": "Bash", "action": "maybe"}),
+        ];
+        for rule in invalid {
+            assert!(
+                PermissionRule::from_value(rule.clone()).is_err(),
+                "{}",
+                rule
+            );
+        }
+
+        let delegate = json!({"tool": "Bash", "action": "delegate", "to": "amp-guard"});
+        assert!(PermissionRule::from_value(delegate).is_ok());
+    }
+}
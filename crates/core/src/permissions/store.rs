@@ -0,0 +1,423 @@
+//! Reading and writing the rule list
+//!
+//! The full list, disabled rules and notes included, is kept in
+//! `amp-extras/permissions.json` (`schemas/amp-extras-permissions.json`).
+//! Every change also rewrites `amp.permissions` in the Amp settings file with
+//! the enabled rules only.
+//!
+//! When `amp.permissions` was edited outside the plugin, it wins: [`load`]
+//! takes its rules (keeping the note of any rule it still recognizes) and
+//! puts each disabled rule back after the rule it used to follow.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::model::{cli_rules, PermissionRule};
+use crate::errors::{AmpError, Result};
+use crate::settings::{self, jsonc};
+
+/// Settings key of the rules
+pub const SETTINGS_KEY: &str = "amp.permissions";
+
+/// Where the rules live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionStore {
+    /// Amp settings file
+    pub settings_path: PathBuf,
+    /// amp-extras rule list
+    pub path: PathBuf,
+}
+
+impl PermissionStore {
+    pub fn new(settings_path: &Path, path: &Path) -> Self {
+        Self {
+            settings_path: settings_path.to_path_buf(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// The Amp settings file and `~/.config/amp-extras/permissions.json`
+    pub fn default_paths() -> Self {
+        Self::new(
+            &settings::settings_path(),
+            &settings::config_home().join("amp-extras/permissions.json"),
+        )
+    }
+
+    /// The rules, reconciled with `amp.permissions`
+    pub fn load(&self) -> Result<Vec<PermissionRule>> {
+        let cli = self.cli_rules()?;
+        let Some(stored) = self.stored_rules()? else {
+            return Ok(cli);
+        };
+
+        let enabled: Vec<&PermissionRule> = stored.iter().filter(|r| r.enabled).collect();
+        let in_sync =
+            enabled.len() == cli.len() && enabled.iter().zip(&cli).all(|(a, b)| a.same_cli_rule(b));
+        if in_sync {
+            return Ok(stored);
+        }
+        Ok(reconcile(&stored, cli))
+    }
+
+    /// Save the rules and write the enabled ones to the settings file
+    pub fn save(&self, rules: &[PermissionRule]) -> Result<()> {
+        for (index, rule) in rules.iter().enumerate() {
+            rule.validate()
+                .map_err(|e| AmpError::ValidationError(format!("Rule {}: {}", index, e)))?;
+        }
+
+        let mut text = serde_json::to_string_pretty(rules)?;
+        text.push('\n');
+        settings::write_atomic(&self.path, &text)?;
+        settings::write_key(&self.settings_path, SETTINGS_KEY, &cli_rules(rules))
+    }
+
+    /// Load, change and save the rules
+    pub fn edit<F>(&self, change: F) -> Result<Vec<PermissionRule>>
+    where
+        F: FnOnce(&mut Vec<PermissionRule>) -> Result<()>,
+    {
+        let mut rules = self.load()?;
+        change(&mut rules)?;
+        self.save(&rules)?;
+        Ok(rules)
+    }
+
+    /// Where each of `rules` (as returned by [`load`](Self::load)) is written:
+    /// enabled rules in the settings file, disabled ones in the rule list
+    ///
+    /// A rule has no location when its file does not hold the same number
+    /// of rules, e.g. before the first save.
+    pub fn locate(&self, rules: &[PermissionRule]) -> Vec<Option<RuleLocation>> {
+        let settings = items_at(&self.settings_path, Some(SETTINGS_KEY));
+        let stored = items_at(&self.path, None);
+        let enabled_count = rules.iter().filter(|r| r.enabled).count();
+
+        let mut enabled_seen = 0;
+        rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                if rule.enabled {
+                    enabled_seen += 1;
+                    if let Some(loc) = settings
+                        .as_ref()
+                        .filter(|(_, items)| items.len() == enabled_count)
+                        .map(|(file, items)| file.at(items[enabled_seen - 1]))
+                    {
+                        return Some(loc);
+                    }
+                }
+                stored
+                    .as_ref()
+                    .filter(|(_, items)| items.len() == rules.len())
+                    .map(|(file, items)| file.at(items[index]))
+            })
+            .collect()
+    }
+
+    fn cli_rules(&self) -> Result<Vec<PermissionRule>> {
+        let settings = settings::read(&self.settings_path)?;
+        let Some(value) = settings.get(SETTINGS_KEY) else {
+            return Ok(Vec::new());
+        };
+        let items = value.as_array().ok_or_else(|| {
+            AmpError::ConfigError(format!("'{}' must be a list of rules", SETTINGS_KEY))
+        })?;
+
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let mut rule: PermissionRule =
+                    serde_json::from_value(item.clone()).map_err(|e| {
+                        AmpError::ConfigError(format!(
+                            "Invalid rule {} in '{}': {}",
+                            index, SETTINGS_KEY, e
+                        ))
+                    })?;
+                // Only amp-extras can disable rules
+                rule.enabled = true;
+                rule.note = None;
+                Ok(rule)
+            })
+            .collect()
+    }
+
+    fn stored_rules(&self) -> Result<Option<Vec<PermissionRule>>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| {
+                AmpError::ConfigError(format!("Invalid {}: {}", self.path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Position of a rule in a file, for quickfix lists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleLocation {
+    pub file: PathBuf,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub col: usize,
+}
+
+struct LoadedFile<'a> {
+    path: &'a Path,
+    text: String,
+}
+
+impl LoadedFile<'_> {
+    fn at(&self, offset: usize) -> RuleLocation {
+        let (line, col) = jsonc::line_col(&self.text, offset);
+        RuleLocation {
+            file: self.path.to_path_buf(),
+            line,
+            col,
+        }
+    }
+}
+
+/// Offsets of the items of the rule list in `path`
+fn items_at<'a>(path: &'a Path, key: Option<&str>) -> Option<(LoadedFile<'a>, Vec<usize>)> {
+    let text = fs::read_to_string(path).ok()?;
+    let items = jsonc::array_items(&text, key).ok()??;
+    Some((LoadedFile { path, text }, items))
+}
+
+/// Rules of `cli`, with the notes and disabled rules of `stored`
+fn reconcile(stored: &[PermissionRule], cli: Vec<PermissionRule>) -> Vec<PermissionRule> {
+    let mut used = vec![false; stored.len()];
+    let mut rules: Vec<(Option<usize>, PermissionRule)> = cli
+        .into_iter()
+        .map(|mut rule| {
+            let known = stored
+                .iter()
+                .enumerate()
+                .position(|(i, s)| !used[i] && s.enabled && s.same_cli_rule(&rule));
+            if let Some(i) = known {
+                used[i] = true;
+                rule.note = stored[i].note.clone();
+            }
+            (known, rule)
+        })
+        .collect();
+
+    for (i, disabled) in stored.iter().enumerate().filter(|(_, r)| !r.enabled) {
+        // After the closest earlier rule that is still there
+        let anchor = (0..i)
+            .rev()
+            .find_map(|j| rules.iter().position(|(origin, _)| *origin == Some(j)));
+        let at = anchor.map_or(0, |a| a + 1);
+        rules.insert(at, (Some(i), disabled.clone()));
+    }
+
+    rules.into_iter().map(|(_, rule)| rule).collect()
+}
+
+/// Check a 0-based rule index
+pub fn require_index(rules: &[PermissionRule], index: usize) -> Result<()> {
+    if index >= rules.len() {
+        return Err(AmpError::ValidationError(format!(
+            "Rule {} not found ({} rules)",
+            index,
+            rules.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Insert `rule` at `index` (appended if `None`)
+pub fn add(
+    rules: &mut Vec<PermissionRule>,
+    rule: PermissionRule,
+    index: Option<usize>,
+) -> Result<()> {
+    rule.validate()?;
+    let index = index.unwrap_or(rules.len()).min(rules.len());
+    rules.insert(index, rule);
+    Ok(())
+}
+
+/// Apply a patch to a rule: each field of `patch` replaces the rule's, and
+/// `null` removes an optional field
+pub fn update(rules: &mut [PermissionRule], index: usize, patch: &Value) -> Result<()> {
+    require_index(rules, index)?;
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| AmpError::ValidationError("Rule patch must be an object".into()))?;
+
+    let mut value = serde_json::to_value(&rules[index])?;
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| AmpError::Other("Rule is not an object".into()))?;
+    for (key, field) in patch {
+        if field.is_null() {
+            fields.remove(key);
+        } else {
+            fields.insert(key.clone(), field.clone());
+        }
+    }
+
+    rules[index] = PermissionRule::from_value(value)?;
+    Ok(())
+}
+
+pub fn remove(rules: &mut Vec<PermissionRule>, index: usize) -> Result<PermissionRule> {
+    require_index(rules, index)?;
+    Ok(rules.remove(index))
+}
+
+/// Put the rules in a new order; `order` lists every current index once
+pub fn reorder(rules: &mut Vec<PermissionRule>, order: &[usize]) -> Result<()> {
+    let mut seen = vec![false; rules.len()];
+    let valid = order.len() == rules.len()
+        && order
+            .iter()
+            .all(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true));
+    if !valid {
+        return Err(AmpError::ValidationError(format!(
+            "Order must list each of the {} rules exactly once",
+            rules.len()
+        )));
+    }
+
+    let mut old: Vec<Option<PermissionRule>> = rules.drain(..).map(Some).collect();
+    rules.extend(order.iter().filter_map(|&i| old[i].take()));
+    Ok(())
+}
+
+/// Enable or disable a rule (flip it if `enabled` is `None`); returns the
+/// new state
+pub fn toggle(rules: &mut [PermissionRule], index: usize, enabled: Option<bool>) -> Result<bool> {
+    require_index(rules, index)?;
+    let rule = &mut rules[index];
+    rule.enabled = enabled.unwrap_or(!rule.enabled);
+    Ok(rule.enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(value: Value) -> PermissionRule {
+        PermissionRule::from_value(value).unwrap()
+    }
+
+    fn store(dir: &Path) -> PermissionStore {
+        PermissionStore::new(
+            &dir.join("amp/settings.json"),
+            &dir.join("amp-extras/permissions.json"),
+        )
+    }
+
+    #[test]
+    fn test_save_strips_disabled_rules_and_keeps_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        fs::create_dir_all(dir.path().join("amp")).unwrap();
+        fs::write(
+            &store.settings_path,
+            "{\n  // mine\n  \"amp.todos.enabled\": false\n}\n",
+        )
+        .unwrap();
+
+        let rules = store
+            .edit(|rules| {
+                add(
+                    rules,
+                    rule(json!({"tool": "Bash", "action": "ask", "note": "n"})),
+                    None,
+                )?;
+                add(
+                    rules,
+                    rule(json!({"tool": "Read", "action": "allow"})),
+                    Some(0),
+                )?;
+                toggle(rules, 1, Some(false))?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(rules[0].tool, "Read");
+
+        let text = fs::read_to_string(&store.settings_path).unwrap();
+        assert!(text.starts_with("{\n  // mine\n  \"amp.todos.enabled\": false,\n"));
+        let settings = settings::read(&store.settings_path).unwrap();
+        assert_eq!(
+            settings[SETTINGS_KEY],
+            json!([{"tool": "Read", "action": "allow"}])
+        );
+
+        // The disabled rule and its note survive a reload
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded, rules);
+        assert_eq!(loaded[1].note.as_deref(), Some("n"));
+
+        // Enabled rules point into settings.json, disabled ones into the list
+        let locations = store.locate(&loaded);
+        let settings_loc = locations[0].as_ref().unwrap();
+        assert_eq!(settings_loc.file, store.settings_path);
+        assert_eq!((settings_loc.line, settings_loc.col), (5, 5));
+        let stored_loc = locations[1].as_ref().unwrap();
+        assert_eq!(stored_loc.file, store.path);
+        assert_eq!((stored_loc.line, stored_loc.col), (7, 3));
+    }
+
+    #[test]
+    fn test_external_edits_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        store
+            .save(&[
+                rule(json!({"tool": "A", "action": "allow", "note": "keep"})),
+                rule(json!({"tool": "B", "action": "reject", "enabled": false})),
+                rule(json!({"tool": "C", "action": "ask"})),
+            ])
+            .unwrap();
+
+        // Someone removes C and adds D in settings.json
+        settings::write_key(
+            &store.settings_path,
+            SETTINGS_KEY,
+            &json!([{"tool": "A", "action": "allow"}, {"tool": "D", "action": "ask"}]),
+        )
+        .unwrap();
+
+        let rules = store.load().unwrap();
+        let tools: Vec<_> = rules.iter().map(|r| r.tool.as_str()).collect();
+        assert_eq!(tools, vec!["A", "B", "D"]);
+        assert_eq!(rules[0].note.as_deref(), Some("keep"));
+        assert!(!rules[1].enabled);
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut rules = vec![
+            rule(json!({"tool": "A", "action": "allow"})),
+            rule(json!({"tool": "B", "action": "ask", "context": "thread"})),
+        ];
+
+        update(&mut rules, 1, &json!({"action": "reject", "context": null})).unwrap();
+        assert_eq!(rules[1].context, None);
+        assert_eq!(rules[1].action.as_str(), "reject");
+        assert!(update(&mut rules, 1, &json!({"action": "delegate"})).is_err());
+        assert!(update(&mut rules, 5, &json!({})).is_err());
+
+        reorder(&mut rules, &[1, 0]).unwrap();
+        assert_eq!(rules[0].tool, "B");
+        assert!(reorder(&mut rules, &[0, 0]).is_err());
+        assert!(reorder(&mut rules, &[0]).is_err());
+
+        assert!(!toggle(&mut rules, 0, None).unwrap());
+        assert_eq!(remove(&mut rules, 0).unwrap().tool, "B");
+        assert_eq!(rules.len(), 1);
+    }
+}
@@ -0,0 +1,442 @@
+//! Rules learned from thread history
+//!
+//! [`suggest`] turns every tool call recorded in local threads into a
+//! candidate rule:
+//!
+//! - shell commands (`cmd`) match their program and subcommand, e.g.
+//!   `cargo test --all` gives `["cargo test", "cargo test *"]`; chained,
+//!   piped or redirected commands are left out
+//! - calls with an absolute path match everything under the thread's
+//!   workspace, or under the file's directory outside it
+//! - other calls match the tool alone
+//!
+//! Candidates seen at least `min_count` times are proposed, with the number
+//! of past calls each would have covered. Reading tools and well-known
+//! read-only commands are proposed as `allow`, the rest as `ask`. Calls the
+//! configured rules already decide are not counted.
+//!
+//! A command glob such as `cat *` also matches `cat x && curl ... | sh`, so
+//! `allow` command rules come with a guard: an `ask` rule for chained,
+//! piped or redirected commands, to be added ahead of the rule.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::evaluate::evaluate;
+use super::model::{PermissionAction, PermissionRule, RuleContext};
+use crate::errors::Result;
+use crate::threads::index::tool_access;
+use crate::threads::model::ContentBlock;
+use crate::threads::render::PATH_KEYS;
+use crate::threads::store;
+
+/// Tool input keys holding a shell command
+const COMMAND_KEYS: &[&str] = &["cmd", "command"];
+
+/// Commands proposed as `allow`; build and test commands run arbitrary code
+/// (build scripts, tests) and are left to `ask`
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "cat",
+    "echo",
+    "git diff",
+    "git log",
+    "git show",
+    "git status",
+    "grep",
+    "head",
+    "ls",
+    "pwd",
+    "rg",
+    "tail",
+    "wc",
+    "which",
+];
+
+/// Shell syntax running more than one program or touching files
+const SHELL_OPERATORS: &[&str] = &["&&", "||", ";", "|", ">", "<", "`", "$(", "\n"];
+
+/// Examples kept per suggestion
+const MAX_EXAMPLES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestOptions {
+    /// Calls a candidate needs to be proposed
+    pub min_count: usize,
+    /// Suggestions returned, most used first
+    pub limit: usize,
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self {
+            min_count: 3,
+            limit: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub rule: PermissionRule,
+    /// Rule to add ahead of an `allow` command rule, so that chained
+    /// commands its glob also matches are asked about
+    pub guard: Option<PermissionRule>,
+    /// Past calls the rule would have covered
+    pub count: usize,
+    /// Threads those calls are in
+    pub threads: usize,
+    /// A few of the covered calls
+    pub examples: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SuggestReport {
+    pub suggestions: Vec<Suggestion>,
+    /// Threads read
+    pub threads: usize,
+    /// Tool calls read
+    pub calls: usize,
+    /// Calls the configured rules already decide
+    pub decided: usize,
+    /// Shell commands too complex to generalize
+    pub skipped: usize,
+    /// Files that could not be parsed
+    pub errors: Vec<String>,
+}
+
+struct Call {
+    tool: String,
+    input: Value,
+    thread: usize,
+}
+
+/// `program subcommand` (or `program`) of a simple command
+fn command_prefix(cmd: &str) -> Option<String> {
+    if SHELL_OPERATORS.iter().any(|op| cmd.contains(op)) {
+        return None;
+    }
+
+    let mut words = cmd.split_whitespace();
+    let program = words.next()?;
+    // Leading `VAR=value` assignments change what the command does
+    if program.contains('=') {
+        return None;
+    }
+    let is_subcommand = |w: &&str| {
+        w.starts_with(|c: char| c.is_ascii_alphabetic())
+            && w.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-:".contains(c))
+    };
+    Some(match words.next().filter(is_subcommand) {
+        Some(sub) => format!("{} {}", program, sub),
+        None => program.to_string(),
+    })
+}
+
+fn access_action(tool: &str) -> PermissionAction {
+    match tool_access(tool) {
+        "read" => PermissionAction::Allow,
+        _ => PermissionAction::Ask,
+    }
+}
+
+/// The candidate rule for a call; `None` for commands left out
+fn candidate(tool: &str, input: &Value, workspace: Option<&str>) -> Option<PermissionRule> {
+    let text_arg = |keys: &[&'static str]| {
+        keys.iter()
+            .find_map(|k| input.get(*k).and_then(|v| v.as_str()).map(|v| (*k, v)))
+    };
+
+    let rule = if let Some((key, cmd)) = text_arg(COMMAND_KEYS) {
+        let prefix = command_prefix(cmd)?;
+        let action = if READ_ONLY_COMMANDS.contains(&prefix.as_str()) {
+            PermissionAction::Allow
+        } else {
+            PermissionAction::Ask
+        };
+        let patterns = json!([prefix, format!("{} *", prefix)]);
+        json!({"tool": tool, "matches": {key: patterns}, "action": action})
+    } else if let Some((key, path)) = text_arg(PATH_KEYS).filter(|(_, p)| p.starts_with('/')) {
+        let root = workspace
+            .map(|w| w.trim_end_matches('/'))
+            .filter(|w| path.starts_with(&format!("{}/", w)));
+        let dir = match root {
+            Some(root) => root.to_string(),
+            None => path[..path.rfind('/').unwrap_or(0)].to_string(),
+        };
+        let glob = format!("{}/*", dir);
+        json!({"tool": tool, "matches": {key: glob}, "action": access_action(tool)})
+    } else {
+        json!({"tool": tool, "action": access_action(tool)})
+    };
+    PermissionRule::from_value(rule).ok()
+}
+
+/// `ask` for commands of `rule` using shell operators, if `rule` allows a
+/// command glob
+fn guard(rule: &PermissionRule) -> Option<PermissionRule> {
+    if rule.action != PermissionAction::Allow {
+        return None;
+    }
+    let key = COMMAND_KEYS
+        .iter()
+        .find(|k| rule.matches.as_ref().is_some_and(|m| m.contains_key(**k)))?;
+    let patterns: Vec<String> = SHELL_OPERATORS
+        .iter()
+        .map(|op| format!("*{}*", op))
+        .collect();
+    let guard = json!({"tool": rule.tool, "matches": {*key: patterns}, "action": "ask"});
+    PermissionRule::from_value(guard).ok()
+}
+
+/// Short text of a call for examples
+fn example(input: &Value) -> String {
+    let text = [COMMAND_KEYS, PATH_KEYS]
+        .concat()
+        .iter()
+        .find_map(|k| input.get(*k).and_then(|v| v.as_str()).map(String::from))
+        .unwrap_or_else(|| input.to_string());
+    if text.chars().count() > 80 {
+        let short: String = text.chars().take(77).collect();
+        format!("{}...", short)
+    } else {
+        text
+    }
+}
+
+/// Propose rules from the threads in `dirs`, leaving out calls `existing`
+/// rules decide
+pub fn suggest(
+    dirs: &[&Path],
+    existing: &[PermissionRule],
+    options: SuggestOptions,
+) -> Result<SuggestReport> {
+    let mut report = SuggestReport::default();
+    let mut calls: Vec<Call> = Vec::new();
+    // Candidate rules by their CLI form, with the calls they came from
+    let mut candidates: BTreeMap<String, (PermissionRule, usize)> = BTreeMap::new();
+
+    for dir in dirs {
+        for path in store::thread_files(dir)? {
+            let thread = match store::load_thread(&path) {
+                Ok(thread) => thread,
+                Err(e) => {
+                    report.errors.push(e.to_string());
+                    continue;
+                },
+            };
+            let workspace = thread.workspace();
+            let thread_index = report.threads;
+            report.threads += 1;
+
+            for message in &thread.messages {
+                for block in message.content() {
+                    let ContentBlock::ToolUse { name, input, .. } = block else {
+                        continue;
+                    };
+                    report.calls += 1;
+                    if evaluate(existing, name, input, RuleContext::Thread)
+                        .index
+                        .is_some()
+                    {
+                        report.decided += 1;
+                        continue;
+                    }
+                    let Some(rule) = candidate(name, input, workspace.as_deref()) else {
+                        report.skipped += 1;
+                        continue;
+                    };
+
+                    let key = rule.to_cli().to_string();
+                    candidates.entry(key).or_insert((rule, 0)).1 += 1;
+                    calls.push(Call {
+                        tool: name.clone(),
+                        input: input.clone(),
+                        thread: thread_index,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut suggestions: Vec<Suggestion> = candidates
+        .into_values()
+        .filter(|(_, seen)| *seen >= options.min_count)
+        .map(|(rule, _)| {
+            let rules = std::slice::from_ref(&rule);
+            let covered: Vec<&Call> = calls
+                .iter()
+                // Candidates name a single tool
+                .filter(|c| c.tool == rule.tool)
+                .filter(|c| {
+                    evaluate(rules, &c.tool, &c.input, RuleContext::Thread)
+                        .index
+                        .is_some()
+                })
+                .collect();
+
+            let threads: BTreeSet<usize> = covered.iter().map(|c| c.thread).collect();
+            let mut examples: Vec<String> = Vec::new();
+            for call in &covered {
+                let text = example(&call.input);
+                if examples.len() < MAX_EXAMPLES && !examples.contains(&text) {
+                    examples.push(text);
+                }
+            }
+            Suggestion {
+                guard: guard(&rule),
+                count: covered.len(),
+                threads: threads.len(),
+                examples,
+                rule,
+            }
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.rule.tool.cmp(&b.rule.tool))
+    });
+    suggestions.truncate(options.limit);
+    report.suggestions = suggestions;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(dir: &Path, n: u32, calls: &[(&str, Value)]) {
+        let id = format!("T-{:08x}-0000-4000-8000-000000000000", n);
+        let content: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (name, input))| {
+                json!({"type": "tool_use", "id": format!("t{}", i), "name": name, "input": input})
+            })
+            .collect();
+        let thread = json!({
+            "v": 1,
+            "id": id,
+            "created": 1_700_000_000_000i64,
+            "messages": [{"role": "assistant", "messageId": 0, "content": content}],
+            "env": {"initial": {"trees": [{"displayName": "repo", "uri": "file:///src/repo"}]}},
+        });
+        fs::write(store::thread_path(dir, &id), thread.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_command_prefix() {
+        assert_eq!(
+            command_prefix("cargo test --all").as_deref(),
+            Some("cargo test")
+        );
+        assert_eq!(command_prefix("ls -la src").as_deref(), Some("ls"));
+        assert_eq!(command_prefix("git status").as_deref(), Some("git status"));
+        assert_eq!(command_prefix("cargo test && rm -rf x"), None);
+        assert_eq!(command_prefix("cat a | wc -l"), None);
+        assert_eq!(command_prefix("RUST_LOG=debug cargo run"), None);
+        assert_eq!(command_prefix("  "), None);
+    }
+
+    #[test]
+    fn test_suggest_from_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let bash = |cmd: &str| ("Bash", json!({"cmd": cmd}));
+        write(
+            dir.path(),
+            1,
+            &[
+                bash("cargo test"),
+                bash("cargo test -p core"),
+                bash("npm publish"),
+                bash("make && make install"),
+                ("Read", json!({"path": "/src/repo/a.rs"})),
+                ("edit_file", json!({"path": "/src/repo/a.rs"})),
+            ],
+        );
+        write(
+            dir.path(),
+            2,
+            &[
+                bash("cargo test --all"),
+                bash("npm publish"),
+                ("Read", json!({"path": "/src/repo/b/c.rs"})),
+                ("Read", json!({"path": "/etc/hosts"})),
+                ("Read", json!({"path": "/src/repo/d.rs"})),
+            ],
+        );
+
+        let existing: Vec<PermissionRule> =
+            serde_json::from_value(json!([{"tool": "edit_file", "action": "ask"}])).unwrap();
+        let options = SuggestOptions {
+            min_count: 2,
+            limit: 10,
+        };
+        let report = suggest(&[dir.path()], &existing, options).unwrap();
+
+        assert_eq!(report.threads, 2);
+        assert_eq!(report.calls, 11);
+        assert_eq!((report.decided, report.skipped), (1, 1));
+
+        let found: Vec<_> = report
+            .suggestions
+            .iter()
+            .map(|s| (s.rule.to_cli(), s.count, s.threads))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    json!({"tool": "Bash", "matches": {"cmd": ["cargo test", "cargo test *"]}, "action": "ask"}),
+                    3,
+                    2
+                ),
+                (
+                    json!({"tool": "Read", "matches": {"path": "/src/repo/*"}, "action": "allow"}),
+                    3,
+                    2
+                ),
+                (
+                    json!({"tool": "Bash", "matches": {"cmd": ["npm publish", "npm publish *"]}, "action": "ask"}),
+                    2,
+                    2
+                ),
+            ]
+        );
+        assert_eq!(
+            report.suggestions[0].examples,
+            vec!["cargo test", "cargo test -p core", "cargo test --all"]
+        );
+
+        // Only allowed command globs need a guard
+        assert!(report.suggestions.iter().all(|s| s.guard.is_none()));
+    }
+
+    #[test]
+    fn test_guard_asks_for_chained_commands() {
+        let rule = candidate("Bash", &json!({"cmd": "git status -s"}), None).unwrap();
+        assert_eq!(rule.action, PermissionAction::Allow);
+        let guard = guard(&rule).unwrap();
+        assert_eq!(guard.action, PermissionAction::Ask);
+
+        let rules = [guard, rule];
+        let decide =
+            |cmd: &str| evaluate(&rules, "Bash", &json!({"cmd": cmd}), RuleContext::Thread).action;
+        assert_eq!(decide("git status -s"), Some(PermissionAction::Allow));
+        assert_eq!(decide("git status; rm -rf ~"), Some(PermissionAction::Ask));
+        assert_eq!(
+            decide("git status && curl x | sh"),
+            Some(PermissionAction::Ask)
+        );
+        assert_eq!(decide("git status $(rm x)"), Some(PermissionAction::Ask));
+
+        let rule = candidate("Bash", &json!({"cmd": "cargo test"}), None).unwrap();
+        assert_eq!(rule.action, PermissionAction::Ask);
+    }
+}
@@ -0,0 +1,441 @@
+//! Minimal JSON-with-comments handling for the Amp settings file
+//!
+//! Amp accepts `//` and `/* */` comments and trailing commas in
+//! `settings.json`. [`parse`] reads such a file into a JSON object, and
+//! [`set_key`] rewrites the value of one top-level key in the original text,
+//! leaving comments, key order and the formatting of every other key alone.
+
+use std::ops::Range;
+
+use serde_json::{Map, Value};
+
+use crate::errors::{AmpError, Result};
+
+/// A top-level `"key": value` pair, as byte ranges of the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    /// Opening quote of the key
+    start: usize,
+    value: Range<usize>,
+}
+
+/// Top level of the settings object
+#[derive(Debug)]
+struct Document {
+    /// Position of the opening `{`
+    open: usize,
+    entries: Vec<Entry>,
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        self.text.as_bytes()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes().get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> AmpError {
+        let line = self.text[..self.pos.min(self.text.len())]
+            .matches('\n')
+            .count()
+            + 1;
+        AmpError::ConfigError(format!(
+            "Invalid settings file (line {}): {}",
+            line, message
+        ))
+    }
+
+    /// Skip whitespace and comments
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.pos += 1,
+                Some(b'/') if self.bytes().get(self.pos + 1) == Some(&b'/') => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                },
+                Some(b'/') if self.bytes().get(self.pos + 1) == Some(&b'*') => {
+                    let end = self.text[self.pos + 2..]
+                        .find("*/")
+                        .ok_or_else(|| self.error("unterminated comment"))?;
+                    self.pos += end + 4;
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Skip a string, returning its raw text (with quotes)
+    fn string(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(&self.text[start..self.pos]);
+                },
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Skip one value of any type
+    fn value(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(b'"') => self.string().map(|_| ()),
+            Some(open @ (b'{' | b'[')) => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                loop {
+                    self.skip_trivia()?;
+                    match self.peek() {
+                        Some(c) if c == close => {
+                            self.pos += 1;
+                            return Ok(());
+                        },
+                        Some(b',') => self.pos += 1,
+                        Some(_) if open == b'{' => {
+                            self.string()?;
+                            self.skip_trivia()?;
+                            self.expect(b':')?;
+                            self.skip_trivia()?;
+                            self.value()?;
+                        },
+                        Some(_) => self.value()?,
+                        None => return Err(self.error("unexpected end of file")),
+                    }
+                }
+            },
+            Some(_) => {
+                // Numbers and literals
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || b"+-.".contains(&c))
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a value"));
+                }
+                Ok(())
+            },
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+}
+
+fn document(text: &str) -> Result<Document> {
+    let mut scanner = Scanner::new(text);
+    scanner.skip_trivia()?;
+    let open = scanner.pos;
+    scanner.expect(b'{')?;
+
+    let mut entries = Vec::new();
+    loop {
+        scanner.skip_trivia()?;
+        match scanner.peek() {
+            Some(b'}') => break,
+            Some(b',') => scanner.pos += 1,
+            Some(b'"') => {
+                let start = scanner.pos;
+                let raw = scanner.string()?;
+                let key: String =
+                    serde_json::from_str(raw).map_err(|_| scanner.error("invalid key"))?;
+                scanner.skip_trivia()?;
+                scanner.expect(b':')?;
+                scanner.skip_trivia()?;
+                let value_start = scanner.pos;
+                scanner.value()?;
+                entries.push(Entry {
+                    key,
+                    start,
+                    value: value_start..scanner.pos,
+                });
+            },
+            Some(_) => return Err(scanner.error("expected a key")),
+            None => return Err(scanner.error("unexpected end of file")),
+        }
+    }
+
+    Ok(Document { open, entries })
+}
+
+/// `text` without comments and trailing commas, as plain JSON
+fn strip(text: &str) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut scanner = Scanner::new(text);
+
+    while let Some(c) = scanner.peek() {
+        match c {
+            b'"' => out.push_str(scanner.string()?),
+            b'/' => {
+                let start = scanner.pos;
+                scanner.skip_trivia()?;
+                if scanner.pos == start {
+                    return Err(scanner.error("unexpected '/'"));
+                }
+                out.push(' ');
+            },
+            b',' => {
+                scanner.pos += 1;
+                let after = scanner.pos;
+                scanner.skip_trivia()?;
+                if !matches!(scanner.peek(), Some(b'}' | b']')) {
+                    out.push(',');
+                }
+                // Comments after the comma are handled by the next turn
+                scanner.pos = after;
+            },
+            _ => {
+                let ch = text[scanner.pos..].chars().next().unwrap_or_default();
+                out.push(ch);
+                scanner.pos += ch.len_utf8();
+            },
+        }
+    }
+    Ok(out)
+}
+
+/// Parse settings text (an empty file is an empty object)
+pub fn parse(text: &str) -> Result<Map<String, Value>> {
+    if text.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    let json = strip(text)?;
+    match serde_json::from_str(&json) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(AmpError::ConfigError(
+            "Invalid settings file: expected an object".into(),
+        )),
+        Err(e) => Err(AmpError::ConfigError(format!(
+            "Invalid settings file: {}",
+            e
+        ))),
+    }
+}
+
+/// Set top-level `key` to `value` in `text`, touching nothing else
+///
+/// An existing value is replaced where it stands; a new key is added after
+/// the last one. Values are pretty-printed with the file's indentation.
+pub fn set_key(text: &str, key: &str, value: &Value) -> Result<String> {
+    let text = if text.trim().is_empty() { "{}\n" } else { text };
+    let document = document(text)?;
+
+    let indent = document
+        .entries
+        .first()
+        .map(|e| line_indent(text, e.start))
+        .filter(|i| !i.is_empty())
+        .unwrap_or_else(|| "  ".to_string());
+
+    let mut out = String::with_capacity(text.len() + 64);
+    match document.entries.iter().find(|e| e.key == key) {
+        Some(entry) => {
+            let base = line_indent(text, entry.start);
+            out.push_str(&text[..entry.value.start]);
+            out.push_str(&format_value(value, &base, &indent)?);
+            out.push_str(&text[entry.value.end..]);
+        },
+        None => {
+            let pair = format!(
+                "{}{}: {}",
+                indent,
+                serde_json::to_string(key)?,
+                format_value(value, &indent, &indent)?
+            );
+            match document.entries.last() {
+                Some(last) => {
+                    // After the last value, and after its trailing comma if any
+                    let mut scanner = Scanner::new(text);
+                    scanner.pos = last.value.end;
+                    scanner.skip_trivia()?;
+                    let (at, prefix) = if scanner.peek() == Some(b',') {
+                        (scanner.pos + 1, "\n")
+                    } else {
+                        (last.value.end, ",\n")
+                    };
+                    out.push_str(&text[..at]);
+                    out.push_str(prefix);
+                    out.push_str(&pair);
+                    out.push_str(&text[at..]);
+                },
+                None => {
+                    let at = document.open + 1;
+                    let mut scanner = Scanner::new(text);
+                    scanner.pos = at;
+                    scanner.skip_trivia()?;
+                    out.push_str(&text[..at]);
+                    out.push('\n');
+                    out.push_str(&pair);
+                    out.push('\n');
+                    out.push_str(&text[scanner.pos..]);
+                },
+            }
+        },
+    }
+    Ok(out)
+}
+
+/// Start offsets of the items of an array: the value of top-level `key`, or
+/// the whole text if `key` is `None`
+///
+/// `None` if the key is missing or its value is not an array.
+pub fn array_items(text: &str, key: Option<&str>) -> Result<Option<Vec<usize>>> {
+    let mut scanner = Scanner::new(text);
+    match key {
+        Some(key) => match document(text)?.entries.into_iter().find(|e| e.key == key) {
+            Some(entry) => scanner.pos = entry.value.start,
+            None => return Ok(None),
+        },
+        None => scanner.skip_trivia()?,
+    }
+    if scanner.peek() != Some(b'[') {
+        return Ok(None);
+    }
+    scanner.pos += 1;
+
+    let mut items = Vec::new();
+    loop {
+        scanner.skip_trivia()?;
+        match scanner.peek() {
+            Some(b']') => return Ok(Some(items)),
+            Some(b',') => scanner.pos += 1,
+            Some(_) => {
+                items.push(scanner.pos);
+                scanner.value()?;
+            },
+            None => return Err(scanner.error("unexpected end of file")),
+        }
+    }
+}
+
+/// 1-based line and column (in characters) of byte offset `pos`
+pub fn line_col(text: &str, pos: usize) -> (usize, usize) {
+    let before = &text[..pos.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Leading whitespace of the line containing `pos`
+fn line_indent(text: &str, pos: usize) -> String {
+    let line_start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    text[line_start..pos]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect()
+}
+
+/// Pretty JSON indented by `unit`, continuation lines prefixed with `base`
+fn format_value(value: &Value, base: &str, unit: &str) -> Result<String> {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(unit.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    serde::Serialize::serialize(value, &mut serializer)?;
+    let pretty = String::from_utf8(buf).map_err(|e| AmpError::Other(e.to_string()))?;
+    Ok(pretty.replace('\n', &format!("\n{}", base)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SETTINGS: &str = r#"{
+    // Editor settings
+    "amp.todos.enabled": false,
+    "amp.permissions": [ /* old */ {"tool": "Bash", "action": "ask"} ],
+    "amp.tools.disable": ["browser_*",], /* trailing */
+}
+"#;
+
+    #[test]
+    fn test_parse_jsonc() {
+        let map = parse(SETTINGS).unwrap();
+        assert_eq!(map["amp.todos.enabled"], json!(false));
+        assert_eq!(map["amp.tools.disable"], json!(["browser_*"]));
+        assert_eq!(map["amp.permissions"][0]["tool"], "Bash");
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("[1]").is_err());
+        assert!(parse("{ \"a\": /* open").is_err());
+    }
+
+    #[test]
+    fn test_set_existing_key_in_place() {
+        let text = set_key(SETTINGS, "amp.permissions", &json!([{"tool": "Read"}])).unwrap();
+        assert_eq!(
+            text,
+            r#"{
+    // Editor settings
+    "amp.todos.enabled": false,
+    "amp.permissions": [
+        {
+            "tool": "Read"
+        }
+    ],
+    "amp.tools.disable": ["browser_*",], /* trailing */
+}
+"#
+        );
+        assert_eq!(parse(&text).unwrap()["amp.permissions"][0]["tool"], "Read");
+    }
+
+    #[test]
+    fn test_add_key() {
+        // After a trailing comma
+        let text = set_key("{\n  \"a\": 1,\n}\n", "b", &json!(true)).unwrap();
+        assert_eq!(text, "{\n  \"a\": 1,\n  \"b\": true\n}\n");
+
+        let text = set_key("{\n  \"a\": 1\n}", "b", &json!([])).unwrap();
+        assert_eq!(text, "{\n  \"a\": 1,\n  \"b\": []\n}");
+
+        let text = set_key("", "b", &json!(1)).unwrap();
+        assert_eq!(text, "{\n  \"b\": 1\n}\n");
+        assert_eq!(parse(&text).unwrap()["b"], 1);
+    }
+
+    #[test]
+    fn test_array_items() {
+        let items = array_items(SETTINGS, Some("amp.permissions"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(line_col(SETTINGS, items[0]), (4, 36));
+        assert_eq!(
+            array_items(SETTINGS, Some("amp.todos.enabled")).unwrap(),
+            None
+        );
+        assert_eq!(array_items(SETTINGS, Some("missing")).unwrap(), None);
+
+        let text = "[\n  {\"a\": [1, 2]},\n  // note\n  \"x\"\n]";
+        let items = array_items(text, None).unwrap().unwrap();
+        let positions: Vec<_> = items.iter().map(|&i| line_col(text, i)).collect();
+        assert_eq!(positions, vec![(2, 3), (4, 3)]);
+    }
+}
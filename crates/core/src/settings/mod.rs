@@ -0,0 +1,105 @@
+//! The Amp CLI settings file
+//!
+//! Amp reads its settings from `~/.config/amp/settings.json` (or the file
+//! named by `AMP_SETTINGS_FILE`), a JSON object with comments allowed and
+//! flat keys such as `amp.permissions`. Editors of one key go through
+//! [`write_key`], which rewrites only that key's value (see [`jsonc`]).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::errors::Result;
+
+pub mod jsonc;
+
+/// Environment variable overriding the settings file, as for the Amp CLI
+pub const SETTINGS_FILE_ENV: &str = "AMP_SETTINGS_FILE";
+
+/// `$XDG_CONFIG_HOME`, else `~/.config` (also on macOS, where
+/// `dirs::config_dir` would be Application Support)
+pub fn config_home() -> PathBuf {
+    std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Path of the Amp settings file
+pub fn settings_path() -> PathBuf {
+    match std::env::var(SETTINGS_FILE_ENV) {
+        Ok(path) if !path.is_empty() => PathBuf::from(path),
+        _ => config_home().join("amp/settings.json"),
+    }
+}
+
+/// Settings in `path` (empty if the file does not exist)
+pub fn read(path: &Path) -> Result<Map<String, Value>> {
+    match fs::read_to_string(path) {
+        Ok(text) => jsonc::parse(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Set one top-level key of the settings file in `path`, creating the file
+/// if needed
+///
+/// The file is replaced atomically, so Amp never reads a half-written file.
+pub fn write_key(path: &Path, key: &str, value: &Value) -> Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let updated = jsonc::set_key(&text, key, value)?;
+    write_atomic(path, &updated)
+}
+
+/// Write `contents` to a sibling temporary file, then rename it over `path`
+///
+/// A symlinked `path` (e.g. settings kept in a dotfiles repo) is resolved
+/// first, so the link survives and its target is updated.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let resolved = fs::canonicalize(path).ok();
+    let path = resolved.as_deref().unwrap_or(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("dotfiles/settings.json");
+        let link = dir.path().join("amp/settings.json");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        fs::write(&target, "{}\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_key(&link, "amp.x", &serde_json::json!(1)).unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(read(&target).unwrap()["amp.x"], 1);
+        assert!(!link.with_file_name(".settings.json.tmp").exists());
+    }
+}
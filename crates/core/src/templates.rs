@@ -0,0 +1,449 @@
+//! Prompt templates
+//!
+//! Prompt content may contain `{{ name }}` placeholders. Values come from, in
+//! order of precedence:
+//! 1. Variables passed explicitly by the caller
+//! 2. Editor context (`filename`/`file`, `filetype`, `selection`,
+//!    `diagnostics`)
+//! 3. The `default` of the variable's declaration
+//!
+//! A placeholder that resolves to nothing is an error unless its declaration
+//! marks it as not required, in which case it renders as an empty string.
+//! Write `\{{` to produce a literal `{{`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::{AmpError, Result};
+
+/// Type of a template variable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+}
+
+/// Declaration of a template variable, stored with the prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariableDecl {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: VariableType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl VariableType {
+    fn as_str(self) -> &'static str {
+        match self {
+            VariableType::String => "string",
+            VariableType::Number => "number",
+            VariableType::Boolean => "boolean",
+        }
+    }
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// A diagnostic as returned by `vim.diagnostic.get()`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// 0-based line number
+    #[serde(default)]
+    pub lnum: u32,
+    /// 0-based column
+    #[serde(default)]
+    pub col: u32,
+    /// `vim.diagnostic.severity` number (1 = ERROR .. 4 = HINT)
+    #[serde(default)]
+    pub severity: Option<u8>,
+    pub message: String,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Editor state available to templates as built-in variables
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EditorContext {
+    pub filename: Option<String>,
+    pub filetype: Option<String>,
+    pub selection: Option<String>,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl EditorContext {
+    /// Value of a built-in variable, if the context provides it
+    fn lookup(&self, name: &str) -> Option<String> {
+        match name {
+            "filename" | "file" => self.filename.clone(),
+            "filetype" => self.filetype.clone(),
+            "selection" => self.selection.clone(),
+            "diagnostics" if !self.diagnostics.is_empty() => Some(
+                self.diagnostics
+                    .iter()
+                    .map(format_diagnostic)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the built-in editor context variables
+pub const CONTEXT_VARIABLES: &[&str] =
+    &["filename", "file", "filetype", "selection", "diagnostics"];
+
+fn format_diagnostic(diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Some(1) => "ERROR",
+        Some(2) => "WARN",
+        Some(3) => "INFO",
+        Some(4) => "HINT",
+        _ => "DIAGNOSTIC",
+    };
+    let source = diagnostic
+        .source
+        .as_deref()
+        .map(|s| format!(" ({})", s))
+        .unwrap_or_default();
+
+    format!(
+        "{}:{}: {}: {}{}",
+        diagnostic.lnum + 1,
+        diagnostic.col + 1,
+        severity,
+        diagnostic.message,
+        source
+    )
+}
+
+/// A parsed piece of template text
+#[derive(Debug, Clone, PartialEq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+}
+
+/// Split a template into literal text and `{{ name }}` placeholders
+///
+/// An unterminated `{{` is kept as literal text.
+fn parse(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 && rest.as_bytes()[start - 1] == b'\\' {
+            segments.push(Segment::Text(&rest[..start - 1]));
+            segments.push(Segment::Text("{{"));
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+
+        segments.push(Segment::Text(&rest[..start]));
+        segments.push(Segment::Variable(rest[start + 2..start + 2 + len].trim()));
+        rest = &rest[start + 2 + len + 2..];
+    }
+
+    segments.push(Segment::Text(rest));
+    segments.retain(|s| *s != Segment::Text(""));
+    segments
+}
+
+/// Distinct placeholder names used by a template, in order of appearance
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for segment in parse(template) {
+        if let Segment::Variable(name) = segment {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Check variable declarations before storing them
+pub fn validate_declarations(declarations: &[VariableDecl]) -> Result<()> {
+    let mut seen: Vec<&str> = Vec::new();
+
+    for decl in declarations {
+        if !is_valid_name(&decl.name) {
+            return Err(AmpError::ValidationError(format!(
+                "Invalid variable name '{}' (use letters, digits, '_', '-' or '.')",
+                decl.name
+            )));
+        }
+        if seen.contains(&decl.name.as_str()) {
+            return Err(AmpError::ValidationError(format!(
+                "Variable '{}' is declared more than once",
+                decl.name
+            )));
+        }
+        if let Some(default) = &decl.default {
+            coerce(decl, default)?;
+        }
+        seen.push(&decl.name);
+    }
+
+    Ok(())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Render a template
+///
+/// Returns `AmpError::ValidationError` listing every missing required
+/// variable, or the first value that does not match its declared type.
+pub fn render(
+    template: &str,
+    declarations: &[VariableDecl],
+    variables: &HashMap<String, Value>,
+    context: &EditorContext,
+) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut missing: Vec<&str> = Vec::new();
+
+    for segment in parse(template) {
+        let name = match segment {
+            Segment::Text(text) => {
+                output.push_str(text);
+                continue;
+            },
+            Segment::Variable(name) => name,
+        };
+
+        let decl = declarations.iter().find(|d| d.name == name);
+        let value = match variables.get(name).filter(|v| !v.is_null()) {
+            Some(value) => Some(match decl {
+                Some(decl) => coerce(decl, value)?,
+                None => to_text(value),
+            }),
+            None => context
+                .lookup(name)
+                .or_else(|| decl.and_then(|d| d.default.as_ref()).map(to_text)),
+        };
+
+        match value {
+            Some(value) => output.push_str(&value),
+            None if decl.is_some_and(|d| !d.required) => {},
+            None => {
+                if !missing.contains(&name) {
+                    missing.push(name);
+                }
+            },
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(AmpError::ValidationError(format!(
+            "Missing required template variables: {}",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(output)
+}
+
+/// Check a value against its declared type and convert it to text
+fn coerce(decl: &VariableDecl, value: &Value) -> Result<String> {
+    let ok = match decl.kind {
+        VariableType::String => true,
+        VariableType::Number => {
+            value.is_number()
+                || value
+                    .as_str()
+                    .is_some_and(|s| s.trim().parse::<f64>().is_ok())
+        },
+        VariableType::Boolean => {
+            value.is_boolean() || matches!(value.as_str(), Some("true") | Some("false"))
+        },
+    };
+
+    if !ok {
+        return Err(AmpError::ValidationError(format!(
+            "Variable '{}' expects a {} value, got {}",
+            decl.name,
+            decl.kind.as_str(),
+            value
+        )));
+    }
+
+    Ok(match (decl.kind, value) {
+        (VariableType::Number, Value::String(s)) => s.trim().to_string(),
+        _ => to_text(value),
+    })
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join("\n"),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn decl(
+        name: &str,
+        kind: VariableType,
+        default: Option<Value>,
+        required: bool,
+    ) -> VariableDecl {
+        VariableDecl {
+            name: name.to_string(),
+            kind,
+            default,
+            required,
+            description: None,
+        }
+    }
+
+    fn vars(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders("{{a}} and {{ b }} then {{a}} \\{{c}}"),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_render_precedence() {
+        let decls = vec![decl(
+            "lang",
+            VariableType::String,
+            Some(json!("rust")),
+            true,
+        )];
+        let context = EditorContext {
+            filename: Some("src/lib.rs".into()),
+            ..Default::default()
+        };
+
+        let text = render(
+            "Review {{file}} in {{ lang }}",
+            &decls,
+            &HashMap::new(),
+            &context,
+        );
+        assert_eq!(text.unwrap(), "Review src/lib.rs in rust");
+
+        let explicit = vars(json!({"lang": "go", "file": "main.go"}));
+        let text = render("Review {{file}} in {{ lang }}", &decls, &explicit, &context);
+        assert_eq!(text.unwrap(), "Review main.go in go");
+    }
+
+    #[test]
+    fn test_render_missing_variables() {
+        let decls = vec![decl("note", VariableType::String, None, false)];
+        let err = render(
+            "{{selection}} {{goal}} {{note}} {{goal}}",
+            &decls,
+            &HashMap::new(),
+            &EditorContext::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.category(), "validation");
+        assert!(err.to_string().ends_with("selection, goal"));
+    }
+
+    #[test]
+    fn test_render_type_checks() {
+        let decls = vec![
+            decl("count", VariableType::Number, None, true),
+            decl("strict", VariableType::Boolean, None, true),
+        ];
+
+        let ok = render(
+            "{{count}}/{{strict}}",
+            &decls,
+            &vars(json!({"count": " 3 ", "strict": true})),
+            &EditorContext::default(),
+        );
+        assert_eq!(ok.unwrap(), "3/true");
+
+        let err = render(
+            "{{count}}",
+            &decls,
+            &vars(json!({"count": "three"})),
+            &EditorContext::default(),
+        );
+        assert_eq!(err.unwrap_err().category(), "validation");
+    }
+
+    #[test]
+    fn test_render_diagnostics_and_escapes() {
+        let context = EditorContext {
+            diagnostics: vec![Diagnostic {
+                lnum: 9,
+                col: 4,
+                severity: Some(1),
+                message: "unused variable".into(),
+                source: Some("rustc".into()),
+            }],
+            ..Default::default()
+        };
+
+        let text = render(
+            "Fix:\n{{diagnostics}}\n\\{{literal}}",
+            &[],
+            &HashMap::new(),
+            &context,
+        );
+        assert_eq!(
+            text.unwrap(),
+            "Fix:\n10:5: ERROR: unused variable (rustc)\n{{literal}}"
+        );
+    }
+
+    #[test]
+    fn test_validate_declarations() {
+        let dup = vec![
+            decl("a", VariableType::String, None, true),
+            decl("a", VariableType::Number, None, true),
+        ];
+        assert!(validate_declarations(&dup).is_err());
+
+        let bad_name = vec![decl("1a", VariableType::String, None, true)];
+        assert!(validate_declarations(&bad_name).is_err());
+
+        let bad_default = vec![decl("n", VariableType::Number, Some(json!("x")), true)];
+        assert!(validate_declarations(&bad_default).is_err());
+
+        let parsed: Vec<VariableDecl> =
+            serde_json::from_value(json!([{"name": "n", "type": "number", "default": 2}])).unwrap();
+        assert!(parsed[0].required);
+        assert!(validate_declarations(&parsed).is_ok());
+    }
+}
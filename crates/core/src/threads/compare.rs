@@ -0,0 +1,412 @@
+//! Side-by-side comparison of threads
+//!
+//! [`compare`] digests two threads, or two message ranges of one thread, into
+//! their messages, tool calls, changed files, usage and final result, and
+//! lines them up: messages are paired turn by turn (a turn starts with each
+//! user message that has text), and the final results are diffed line by
+//! line.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::index::file_refs;
+use super::model::{ContentBlock, Message, Thread};
+use super::usage::{records, UsageTotals};
+use crate::db::revisions::{line_diff, DiffLine};
+
+/// Length of message summaries
+const SUMMARY_CHARS: usize = 120;
+
+/// Messages of a thread to compare, by message id (inclusive)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageRange {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl MessageRange {
+    fn contains(&self, message_id: u64) -> bool {
+        self.from.is_none_or(|from| message_id >= from) && self.to.is_none_or(|to| message_id <= to)
+    }
+
+    fn is_all(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+}
+
+/// One message in a side-by-side row
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageDigest {
+    pub message_id: u64,
+    pub role: &'static str,
+    /// First line of the text, else the tools called or results returned
+    pub summary: String,
+    pub tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThreadDigest {
+    pub thread_id: String,
+    pub title: String,
+    pub range: MessageRange,
+    pub message_count: usize,
+    /// Tool calls by tool name
+    pub tools: BTreeMap<String, usize>,
+    /// Files edited or created by tool calls, sorted
+    pub files_changed: Vec<String>,
+    /// Files only read or attached, sorted
+    pub files_read: Vec<String>,
+    pub usage: UsageTotals,
+    /// Text of the last assistant message with text
+    pub result: Option<String>,
+}
+
+/// A row of the side-by-side message view
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageRow {
+    /// 0-based turn of both sides
+    pub turn: usize,
+    pub left: Option<MessageDigest>,
+    pub right: Option<MessageDigest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolCount {
+    pub name: String,
+    pub left: usize,
+    pub right: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileComparison {
+    pub both: Vec<String>,
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+}
+
+/// Right minus left
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct UsageDelta {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub credits: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThreadComparison {
+    pub left: ThreadDigest,
+    pub right: ThreadDigest,
+    pub messages: Vec<MessageRow>,
+    /// Every tool called on either side, by name
+    pub tools: Vec<ToolCount>,
+    pub files_changed: FileComparison,
+    pub usage_delta: UsageDelta,
+    /// Line diff of the final results (left is old, right is new)
+    pub result_diff: Vec<DiffLine>,
+}
+
+/// Compare `left` and `right` within their message ranges
+pub fn compare(
+    left: &Thread,
+    left_range: MessageRange,
+    right: &Thread,
+    right_range: MessageRange,
+) -> ThreadComparison {
+    let left_turns = turns(left, left_range);
+    let right_turns = turns(right, right_range);
+    let left_digest = digest(left, left_range, &left_turns);
+    let right_digest = digest(right, right_range, &right_turns);
+
+    let mut messages = Vec::new();
+    for turn in 0..left_turns.len().max(right_turns.len()) {
+        let l = left_turns.get(turn).map(Vec::as_slice).unwrap_or_default();
+        let r = right_turns.get(turn).map(Vec::as_slice).unwrap_or_default();
+        for row in 0..l.len().max(r.len()) {
+            messages.push(MessageRow {
+                turn,
+                left: l.get(row).cloned(),
+                right: r.get(row).cloned(),
+            });
+        }
+    }
+
+    let names: BTreeSet<&String> = left_digest
+        .tools
+        .keys()
+        .chain(right_digest.tools.keys())
+        .collect();
+    let tools = names
+        .into_iter()
+        .map(|name| ToolCount {
+            name: name.clone(),
+            left: left_digest.tools.get(name).copied().unwrap_or(0),
+            right: right_digest.tools.get(name).copied().unwrap_or(0),
+        })
+        .collect();
+
+    let left_files: BTreeSet<&String> = left_digest.files_changed.iter().collect();
+    let right_files: BTreeSet<&String> = right_digest.files_changed.iter().collect();
+    let files_changed = FileComparison {
+        both: left_files
+            .intersection(&right_files)
+            .map(|f| f.to_string())
+            .collect(),
+        only_left: left_files
+            .difference(&right_files)
+            .map(|f| f.to_string())
+            .collect(),
+        only_right: right_files
+            .difference(&left_files)
+            .map(|f| f.to_string())
+            .collect(),
+    };
+
+    let usage_delta = UsageDelta {
+        input_tokens: right_digest.usage.input_tokens as i64
+            - left_digest.usage.input_tokens as i64,
+        output_tokens: right_digest.usage.output_tokens as i64
+            - left_digest.usage.output_tokens as i64,
+        credits: right_digest.usage.credits - left_digest.usage.credits,
+    };
+
+    let result_diff = line_diff(
+        left_digest.result.as_deref().unwrap_or_default(),
+        right_digest.result.as_deref().unwrap_or_default(),
+    );
+
+    ThreadComparison {
+        left: left_digest,
+        right: right_digest,
+        messages,
+        tools,
+        files_changed,
+        usage_delta,
+        result_diff,
+    }
+}
+
+/// Messages of `thread` within `range`, grouped into turns
+fn turns(thread: &Thread, range: MessageRange) -> Vec<Vec<MessageDigest>> {
+    let mut turns: Vec<Vec<MessageDigest>> = Vec::new();
+
+    for (position, message) in thread.messages.iter().enumerate() {
+        let message_id = message.message_id().unwrap_or(position as u64);
+        if !range.contains(message_id) {
+            continue;
+        }
+
+        let has_text = message
+            .content()
+            .iter()
+            .any(|b| matches!(b, ContentBlock::Text { text } if !text.trim().is_empty()));
+        let digest = message_digest(message, message_id);
+        match turns.last_mut() {
+            Some(turn) if !(digest.role == "user" && has_text) => turn.push(digest),
+            _ => turns.push(vec![digest]),
+        }
+    }
+    turns
+}
+
+fn message_digest(message: &Message, message_id: u64) -> MessageDigest {
+    let mut text = None;
+    let mut tools = Vec::new();
+    let mut results = 0;
+    for block in message.content() {
+        match block {
+            ContentBlock::Text { text: t } if text.is_none() && !t.trim().is_empty() => {
+                text = t.lines().find(|l| !l.trim().is_empty()).map(str::trim);
+            },
+            ContentBlock::ToolUse { name, .. } => tools.push(name.clone()),
+            ContentBlock::ToolResult { .. } => results += 1,
+            _ => {},
+        }
+    }
+
+    let summary = match text {
+        Some(text) => {
+            let mut summary: String = text.chars().take(SUMMARY_CHARS).collect();
+            if summary.len() < text.len() {
+                summary.push('…');
+            }
+            summary
+        },
+        None if !tools.is_empty() => format!("→ {}", tools.join(", ")),
+        None if results > 0 => format!("← {} tool result(s)", results),
+        None => String::new(),
+    };
+
+    MessageDigest {
+        message_id,
+        role: match message {
+            Message::User(_) => "user",
+            Message::Assistant(_) => "assistant",
+        },
+        summary,
+        tools,
+    }
+}
+
+fn digest(thread: &Thread, range: MessageRange, turns: &[Vec<MessageDigest>]) -> ThreadDigest {
+    let mut tools = BTreeMap::new();
+    for name in turns.iter().flatten().flat_map(|m| &m.tools) {
+        *tools.entry(name.clone()).or_insert(0) += 1;
+    }
+
+    let mut changed = BTreeSet::new();
+    let mut read = BTreeSet::new();
+    for file in file_refs(thread)
+        .into_iter()
+        .filter(|f| range.contains(f.message_id))
+    {
+        if file.access == "write" {
+            changed.insert(file.path);
+        } else {
+            read.insert(file.path);
+        }
+    }
+    let read = read.difference(&changed).cloned().collect();
+
+    let mut usage = UsageTotals::default();
+    for record in records(thread) {
+        let included = match record.message_id {
+            Some(id) => range.contains(id),
+            None => range.is_all(),
+        };
+        if included {
+            usage.add(&record);
+        }
+    }
+
+    let result = thread
+        .messages
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(position, m)| range.contains(m.message_id().unwrap_or(*position as u64)))
+        .find_map(|(_, m)| match m {
+            Message::Assistant(assistant) => {
+                let text: Vec<&str> = assistant
+                    .content
+                    .iter()
+                    .filter_map(|b| match b {
+                        ContentBlock::Text { text } if !text.trim().is_empty() => Some(text.trim()),
+                        _ => None,
+                    })
+                    .collect();
+                (!text.is_empty()).then(|| text.join("\n\n"))
+            },
+            Message::User(_) => None,
+        });
+
+    ThreadDigest {
+        thread_id: thread.id.clone(),
+        title: thread.display_title(),
+        range,
+        message_count: turns.iter().map(Vec::len).sum(),
+        tools,
+        files_changed: changed.into_iter().collect(),
+        files_read: read,
+        usage,
+        result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn thread(id: &str, edit: &str, result: &str, credits: f64) -> Thread {
+        serde_json::from_value(json!({
+            "v": 1,
+            "id": id,
+            "created": 1_700_000_000_000i64,
+            "title": "Retry",
+            "env": {"initial": {"trees": [{"uri": "file:///w"}]}},
+            "messages": [
+                {"role": "user", "messageId": 0, "content": [{"type": "text", "text": "Fix the bug"}]},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "tool_use", "id": "t1", "name": "Read", "input": {"path": "/w/a.rs"}},
+                    {"type": "tool_use", "id": "t2", "name": "edit_file", "input": {"path": edit}}
+                ], "usage": {"inputTokens": 100, "outputTokens": 10, "credits": credits}},
+                {"role": "user", "messageId": 2, "content": [
+                    {"type": "tool_result", "toolUseId": "t1", "content": "ok"},
+                    {"type": "tool_result", "toolUseId": "t2", "content": "ok"}
+                ]},
+                {"role": "assistant", "messageId": 3, "content": [{"type": "text", "text": result}]},
+                {"role": "user", "messageId": 4, "content": [{"type": "text", "text": "Thanks"}]}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compare_two_threads() {
+        let left = thread("T-1", "/w/a.rs", "Fixed it.\nAll tests pass.", 1.0);
+        let right = thread("T-2", "b.rs", "Fixed it.\nOne test fails.", 1.5);
+        let all = MessageRange::default();
+        let comparison = compare(&left, all, &right, all);
+
+        assert_eq!(comparison.left.message_count, 5);
+        assert_eq!(comparison.left.files_changed, vec!["/w/a.rs"]);
+        // A changed file is not listed as read too
+        assert!(comparison.left.files_read.is_empty());
+        assert_eq!(comparison.right.files_read, vec!["/w/a.rs"]);
+        assert_eq!(
+            comparison.files_changed,
+            FileComparison {
+                both: vec![],
+                only_left: vec!["/w/a.rs".into()],
+                only_right: vec!["/w/b.rs".into()],
+            }
+        );
+        assert_eq!(comparison.tools[0].name, "Read");
+        assert_eq!(
+            (comparison.tools[0].left, comparison.tools[0].right),
+            (1, 1)
+        );
+        assert_eq!(comparison.usage_delta.credits, 0.5);
+        assert_eq!(comparison.usage_delta.input_tokens, 0);
+
+        // Two turns: the first with four messages, then "Thanks"
+        assert_eq!(comparison.messages.len(), 5);
+        assert_eq!(comparison.messages[4].turn, 1);
+        let row = &comparison.messages[1];
+        assert_eq!(row.left.as_ref().unwrap().summary, "→ Read, edit_file");
+        assert_eq!(
+            comparison.messages[2].right.as_ref().unwrap().summary,
+            "← 2 tool result(s)"
+        );
+
+        let kinds: Vec<_> = comparison.result_diff.iter().map(|l| l.kind).collect();
+        assert_eq!(kinds, vec!["equal", "delete", "insert"]);
+    }
+
+    #[test]
+    fn test_compare_ranges_of_one_thread() {
+        let thread = thread("T-1", "/w/a.rs", "Done", 1.0);
+        let comparison = compare(
+            &thread,
+            MessageRange {
+                from: None,
+                to: Some(1),
+            },
+            &thread,
+            MessageRange {
+                from: Some(2),
+                to: None,
+            },
+        );
+
+        assert_eq!(comparison.left.message_count, 2);
+        assert_eq!(comparison.left.usage.credits, 1.0);
+        assert_eq!(comparison.left.result, None);
+        assert_eq!(comparison.right.message_count, 3);
+        assert_eq!(comparison.right.usage.events, 0);
+        assert!(comparison.right.files_changed.is_empty());
+        assert_eq!(comparison.right.result.as_deref(), Some("Done"));
+    }
+}
@@ -0,0 +1,462 @@
+//! Export of threads for sharing
+//!
+//! A thread can be written as Markdown (the [`render`](super::render)
+//! output), a self-contained HTML page or JSONL in the `amp --stream-json`
+//! message shape (`schemas/stream-json.json`). Thinking blocks and tool
+//! outputs can be redacted first with [`ExportOptions`].
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::model::{ContentBlock, Message, Thread};
+use super::render::{self, tool_output, RenderOptions};
+use crate::errors::{AmpError, Result};
+
+/// Text that replaces redacted content
+pub const REDACTED: &str = "[redacted]";
+
+/// Format of an exported thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Markdown,
+    /// A single HTML page with inline styles
+    Html,
+    /// One `amp --stream-json` message per line
+    Jsonl,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "jsonl" => Ok(Format::Jsonl),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown format '{}' (expected markdown, html or jsonl)",
+                other
+            ))),
+        }
+    }
+
+    /// Guess the format from a path's extension (Markdown if unknown)
+    pub fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("html") | Some("htm") => Format::Html,
+            Some("jsonl") => Format::Jsonl,
+            _ => Format::Markdown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Replace the text of thinking blocks
+    pub redact_thinking: bool,
+    /// Replace the content of tool results
+    pub redact_tool_output: bool,
+}
+
+/// Encode `thread` in `format`
+pub fn encode(thread: &Thread, format: Format, options: &ExportOptions) -> Result<String> {
+    let thread = redact(thread, options);
+    match format {
+        Format::Markdown => Ok(markdown(&thread)),
+        Format::Html => Ok(html(&thread)),
+        Format::Jsonl => jsonl(&thread),
+    }
+}
+
+/// Encode `thread` and write it to `path`
+pub fn write(thread: &Thread, path: &Path, format: Format, options: &ExportOptions) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, encode(thread, format, options)?)?;
+    Ok(())
+}
+
+/// Copy of `thread` with the redacted blocks replaced
+fn redact(thread: &Thread, options: &ExportOptions) -> Thread {
+    let mut thread = thread.clone();
+    for message in &mut thread.messages {
+        let content = match message {
+            Message::User(m) => &mut m.content,
+            Message::Assistant(m) => &mut m.content,
+        };
+        for block in content {
+            match block {
+                ContentBlock::Thinking {
+                    thinking,
+                    signature,
+                    ..
+                } if options.redact_thinking => {
+                    *thinking = REDACTED.to_string();
+                    *signature = None;
+                },
+                ContentBlock::ToolResult { content, .. } if options.redact_tool_output => {
+                    *content = Some(Value::String(REDACTED.to_string()));
+                },
+                _ => {},
+            }
+        }
+    }
+    thread
+}
+
+fn markdown(thread: &Thread) -> String {
+    let options = RenderOptions {
+        max_tool_lines: None,
+        ..Default::default()
+    };
+    let mut text = render::render(thread, &options).lines.join("\n");
+    text.push('\n');
+    text
+}
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;max-width:50rem;margin:2rem auto;padding:0 1rem;color:#222}\
+header dl{display:grid;grid-template-columns:max-content 1fr;gap:.2rem 1rem;color:#555}\
+header dd{margin:0}\
+section{border-top:1px solid #ddd;padding:.5rem 0}\
+section h2{font-size:1rem;color:#555}\
+section.user h2{color:#0b5cad}\
+.text{white-space:pre-wrap}\
+details{margin:.5rem 0;border:1px solid #ddd;border-radius:4px;padding:.3rem .6rem}\
+details.error{border-color:#c33}\
+summary{cursor:pointer;color:#555}\
+pre{overflow-x:auto;background:#f6f6f6;padding:.5rem}";
+
+fn html(thread: &Thread) -> String {
+    let mut out = String::new();
+    let title = escape_html(&thread.display_title());
+
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", title));
+    out.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+
+    out.push_str(&format!("<header>\n<h1>{}</h1>\n<dl>\n", title));
+    let mut field = |name: &str, value: &str| {
+        out.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            name,
+            escape_html(value)
+        ));
+    };
+    field("Thread", &thread.id);
+    field("Created", &render::format_time(thread.created));
+    field("Agent mode", thread.agent_mode.as_str());
+    if let Some(workspace) = thread.workspace() {
+        field("Workspace", &workspace);
+    }
+    out.push_str("</dl>\n</header>\n");
+
+    for (position, message) in thread.messages.iter().enumerate() {
+        let id = message.message_id().unwrap_or(position as u64);
+        let role = match message {
+            Message::User(_) => "user",
+            Message::Assistant(_) => "assistant",
+        };
+        out.push_str(&format!(
+            "<section class=\"{}\" id=\"message-{}\">\n<h2>{} #{}</h2>\n",
+            role,
+            id,
+            if role == "user" { "User" } else { "Assistant" },
+            id
+        ));
+        for block in message.content() {
+            html_block(&mut out, block);
+        }
+        out.push_str("</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn html_block(out: &mut String, block: &ContentBlock) {
+    let details = |out: &mut String, class: &str, summary: &str, body: &str| {
+        out.push_str(&format!(
+            "<details class=\"{}\"><summary>{}</summary><pre>{}</pre></details>\n",
+            class,
+            escape_html(summary),
+            escape_html(body)
+        ));
+    };
+
+    match block {
+        ContentBlock::Text { text } => {
+            out.push_str(&format!(
+                "<div class=\"text\">{}</div>\n",
+                escape_html(text)
+            ));
+        },
+        ContentBlock::File { file_uri, text } => {
+            let path = super::model::uri_to_path(file_uri);
+            details(out, "file", &format!("File: {}", path), text);
+        },
+        ContentBlock::Thinking { thinking, .. } => details(out, "thinking", "Thinking", thinking),
+        ContentBlock::ToolUse { id, name, input } => {
+            let input = serde_json::to_string_pretty(input).unwrap_or_default();
+            details(out, "tool-use", &format!("Tool: {} ({})", name, id), &input);
+        },
+        ContentBlock::ToolResult {
+            tool_use_id,
+            content,
+            is_error,
+        } => {
+            let (class, label) = if is_error.unwrap_or(false) {
+                ("error", "Error")
+            } else {
+                ("tool-result", "Result")
+            };
+            let summary = match tool_use_id {
+                Some(id) => format!("{} ({})", label, id),
+                None => label.to_string(),
+            };
+            details(out, class, &summary, &tool_output(content.as_ref()));
+        },
+        ContentBlock::Unknown => {},
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Stream JSON messages: a `system` init, the conversation and a `result`
+///
+/// The stream shape has no thinking or file blocks, so those are left out,
+/// as are tool results without a tool use id.
+fn jsonl(thread: &Thread) -> Result<String> {
+    let mut lines = Vec::new();
+
+    let mut tools: Vec<&str> = Vec::new();
+    for block in thread.messages.iter().flat_map(|m| m.content()) {
+        if let ContentBlock::ToolUse { name, .. } = block {
+            if !tools.contains(&name.as_str()) {
+                tools.push(name);
+            }
+        }
+    }
+    lines.push(json!({
+        "type": "system",
+        "subtype": "init",
+        "cwd": thread.workspace().unwrap_or_default(),
+        "session_id": thread.id,
+        "tools": tools,
+    }));
+
+    let mut turns = 0;
+    let mut last_text = String::new();
+    let mut last_sent = thread.created;
+    for message in &thread.messages {
+        match message {
+            Message::User(user) => {
+                if let Some(sent) = user.meta.as_ref().and_then(|m| m.sent_at) {
+                    last_sent = last_sent.max(sent);
+                }
+                let content: Vec<Value> = user
+                    .content
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => {
+                            Some(json!({ "type": "text", "text": text }))
+                        },
+                        ContentBlock::ToolResult {
+                            tool_use_id: Some(id),
+                            content,
+                            is_error,
+                        } => Some(json!({
+                            "type": "tool_result",
+                            "tool_use_id": id,
+                            "content": tool_output(content.as_ref()),
+                            "is_error": is_error.unwrap_or(false),
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+                lines.push(json!({
+                    "type": "user",
+                    "message": { "role": "user", "content": content },
+                    "parent_tool_use_id": null,
+                    "session_id": thread.id,
+                }));
+            },
+            Message::Assistant(assistant) => {
+                turns += 1;
+                let content: Vec<Value> = assistant
+                    .content
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => {
+                            last_text = text.clone();
+                            Some(json!({ "type": "text", "text": text }))
+                        },
+                        ContentBlock::ToolUse { id, name, input } => Some(json!({
+                            "type": "tool_use",
+                            "id": id,
+                            "name": name,
+                            "input": input,
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+                let stop_reason = assistant
+                    .state
+                    .as_ref()
+                    .and_then(|s| s.stop_reason.as_deref())
+                    .filter(|r| matches!(*r, "end_turn" | "tool_use" | "max_tokens"));
+
+                let mut body = json!({
+                    "type": "message",
+                    "role": "assistant",
+                    "content": content,
+                    "stop_reason": stop_reason,
+                });
+                if let Some(usage) = &assistant.usage {
+                    body["usage"] = json!({
+                        "input_tokens": usage.input_tokens.unwrap_or(0),
+                        "output_tokens": usage.output_tokens.unwrap_or(0),
+                        "cache_creation_input_tokens": usage.cache_creation_input_tokens.unwrap_or(0),
+                        "cache_read_input_tokens": usage.cache_read_input_tokens.unwrap_or(0),
+                    });
+                }
+                lines.push(json!({
+                    "type": "assistant",
+                    "message": body,
+                    "parent_tool_use_id": null,
+                    "session_id": thread.id,
+                }));
+            },
+        }
+    }
+
+    lines.push(json!({
+        "type": "result",
+        "subtype": "success",
+        "duration_ms": (last_sent - thread.created).max(0),
+        "is_error": false,
+        "num_turns": turns,
+        "result": last_text,
+        "session_id": thread.id,
+    }));
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&serde_json::to_string(&line)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread() -> Thread {
+        serde_json::from_value(json!({
+            "v": 1,
+            "id": "T-00000001-0000-4000-8000-000000000000",
+            "created": 1_730_000_000_000i64,
+            "agentMode": "smart",
+            "title": "Fix <b>the</b> parser",
+            "messages": [
+                {"role": "user", "messageId": 0, "content": [
+                    {"type": "text", "text": "Read the file"}
+                ], "meta": {"sentAt": 1_730_000_005_000i64}},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "thinking", "thinking": "secret plan", "signature": "sig"},
+                    {"type": "tool_use", "id": "t1", "name": "Read", "input": {"path": "/w/a.rs"}},
+                ], "state": {"type": "complete", "stopReason": "tool_use"},
+                   "usage": {"model": "m", "inputTokens": 10, "outputTokens": 5}},
+                {"role": "user", "messageId": 2, "content": [
+                    {"type": "tool_result", "toolUseId": "t1", "content": "fn main() {}"}
+                ]},
+                {"role": "assistant", "messageId": 3, "content": [
+                    {"type": "text", "text": "Done"}
+                ]}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_redaction() {
+        let options = ExportOptions {
+            redact_thinking: true,
+            redact_tool_output: true,
+        };
+        for format in [Format::Markdown, Format::Html, Format::Jsonl] {
+            let text = encode(&thread(), format, &options).unwrap();
+            assert!(!text.contains("secret plan"), "{:?}", format);
+            assert!(!text.contains("fn main"), "{:?}", format);
+        }
+
+        let text = encode(&thread(), Format::Markdown, &ExportOptions::default()).unwrap();
+        assert!(text.contains("secret plan"));
+        assert!(text.contains("fn main() {}"));
+    }
+
+    #[test]
+    fn test_html_is_escaped() {
+        let text = encode(&thread(), Format::Html, &ExportOptions::default()).unwrap();
+        assert!(text.starts_with("<!DOCTYPE html>"));
+        assert!(text.contains("<h1>Fix &lt;b&gt;the&lt;/b&gt; parser</h1>"));
+        assert!(text.contains("<section class=\"assistant\" id=\"message-3\">"));
+    }
+
+    #[test]
+    fn test_jsonl_stream_shape() {
+        let text = encode(&thread(), Format::Jsonl, &ExportOptions::default()).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        let types: Vec<_> = lines.iter().map(|l| l["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            vec!["system", "user", "assistant", "user", "assistant", "result"]
+        );
+        assert_eq!(lines[0]["tools"], json!(["Read"]));
+
+        // Thinking has no place in the stream shape
+        let assistant = &lines[2]["message"];
+        assert_eq!(assistant["content"].as_array().unwrap().len(), 1);
+        assert_eq!(assistant["stop_reason"], "tool_use");
+        assert_eq!(assistant["usage"]["input_tokens"], 10);
+
+        let result = &lines[3]["message"]["content"][0];
+        assert_eq!(result["tool_use_id"], "t1");
+        assert_eq!(result["content"], "fn main() {}");
+
+        let end = &lines[5];
+        assert_eq!(end["num_turns"], 2);
+        assert_eq!(end["result"], "Done");
+        assert_eq!(end["duration_ms"], 5000);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::parse("md").unwrap(), Format::Markdown);
+        assert!(Format::parse("pdf").is_err());
+        assert_eq!(Format::detect(Path::new("a/t.jsonl")), Format::Jsonl);
+        assert_eq!(Format::detect(Path::new("t.htm")), Format::Html);
+    }
+}
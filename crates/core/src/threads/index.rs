@@ -0,0 +1,803 @@
+//! Full-text index over thread history
+//!
+//! Thread files are indexed into their own SQLite database (`threads.db`,
+//! next to `prompts.db`), one FTS5 row per message with the message text,
+//! tool-use inputs and tool results in separate columns. [`ThreadIndex::refresh`]
+//! is incremental: only files whose modification time or size changed since
+//! the last run are parsed again, and rows of deleted files are dropped.
+//!
+//! The same refresh records which files each message read, edited or
+//! attached, for [`ThreadIndex::threads_for_file`].
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{sqlite::SqlitePoolOptions, FromRow, SqlitePool};
+use tokio::sync::Mutex;
+
+use super::model::{uri_to_path, ContentBlock, Message, Thread};
+use super::render::PATH_KEYS;
+use super::store::{self, millis};
+use crate::db::migrations::{self, Migration};
+use crate::db::prompts::{fts_query, SearchOptions};
+use crate::errors::{AmpError, Result};
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "thread_messages_fts",
+        sql: THREAD_MESSAGES_FTS,
+    },
+    Migration {
+        version: 2,
+        name: "thread_file_refs",
+        sql: THREAD_FILE_REFS,
+    },
+];
+
+/// v1: indexed files and per-message FTS rows
+const THREAD_MESSAGES_FTS: &str = "
+CREATE TABLE IF NOT EXISTS indexed_threads (
+    path TEXT PRIMARY KEY,        -- Thread file
+    thread_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    mtime INTEGER NOT NULL,       -- File modification time (milliseconds)
+    size INTEGER NOT NULL,        -- File size (bytes)
+    indexed_at INTEGER NOT NULL   -- Unix timestamp (seconds)
+);
+
+CREATE INDEX IF NOT EXISTS idx_indexed_threads_thread ON indexed_threads(thread_id);
+
+-- thread_id, message_id and role are stored but not tokenized
+CREATE VIRTUAL TABLE IF NOT EXISTS thread_messages_fts USING fts5(
+    thread_id UNINDEXED,
+    message_id UNINDEXED,
+    role UNINDEXED,
+    text,
+    tool_input,
+    tool_result,
+    tokenize = 'porter unicode61'
+);
+";
+
+/// v2: files referenced by each message
+const THREAD_FILE_REFS: &str = "
+CREATE TABLE IF NOT EXISTS thread_file_refs (
+    thread_id TEXT NOT NULL,
+    message_id INTEGER NOT NULL,
+    path TEXT NOT NULL,           -- Absolute path where the workspace is known
+    access TEXT NOT NULL,         -- read, write or attached
+    tool TEXT,                    -- Tool name (NULL for attached files)
+    at INTEGER NOT NULL           -- When the message was sent (milliseconds)
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_file_refs_path ON thread_file_refs(path);
+CREATE INDEX IF NOT EXISTS idx_thread_file_refs_thread ON thread_file_refs(thread_id);
+
+-- Reindex every thread on the next refresh to fill the new table
+DELETE FROM thread_messages_fts;
+DELETE FROM indexed_threads;
+";
+
+/// A message matched by [`ThreadIndex::search`]
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ThreadSearchHit {
+    pub thread_id: String,
+    pub message_id: i64,
+    /// `user` or `assistant`
+    pub role: String,
+    pub title: String,
+    /// BM25 score (lower is a better match)
+    pub rank: f64,
+    /// Best matching fragment with matched terms wrapped in highlight markers
+    pub snippet: String,
+}
+
+/// Outcome of a [`ThreadIndex::refresh`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexStats {
+    /// Files parsed and (re)indexed
+    pub indexed: usize,
+    /// Files whose rows were dropped because they no longer exist
+    pub removed: usize,
+    /// Files skipped because they did not change
+    pub unchanged: usize,
+    /// Files that could not be parsed, as `path: reason`
+    pub errors: Vec<String>,
+}
+
+/// A thread that referenced a file, from [`ThreadIndex::threads_for_file`]
+#[derive(Debug, Clone, Serialize)]
+pub struct FileThread {
+    pub thread_id: String,
+    pub title: String,
+    /// Latest reference, Unix timestamp (milliseconds)
+    pub last_at: i64,
+    /// Messages referencing the file, oldest first
+    pub message_ids: Vec<i64>,
+    /// Kinds of access (`read`, `write`, `attached`), sorted
+    pub access: Vec<String>,
+}
+
+pub struct ThreadIndex {
+    pool: SqlitePool,
+    /// Serializes refreshes, so a search never races the background indexer
+    refresh_lock: Mutex<()>,
+}
+
+static INDEX: OnceLock<ThreadIndex> = OnceLock::new();
+
+/// Open the global index at `path` (first call wins)
+pub async fn init(path: &Path) -> Result<()> {
+    if INDEX.get().is_some() {
+        return Ok(());
+    }
+    let index = ThreadIndex::open(path).await?;
+    let _ = INDEX.set(index);
+    Ok(())
+}
+
+/// The global index, once [`init`] has run
+pub fn global() -> Result<&'static ThreadIndex> {
+    INDEX
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("Thread index not initialized").into())
+}
+
+impl ThreadIndex {
+    /// Open (creating and migrating if needed) the index database at `path`
+    pub async fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create index directory: {}", e))?;
+        }
+
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+
+        // Migrate on a dedicated connection, as for prompts.db
+        {
+            let setup = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options.clone())
+                .await?;
+            migrations::run(&setup, MIGRATIONS).await?;
+            setup.close().await;
+        }
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self {
+            pool,
+            refresh_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Bring the index up to date with the thread files of `dir`
+    ///
+    /// With `full`, every file is reindexed regardless of its timestamp.
+    pub async fn refresh(&self, dir: &Path, full: bool) -> Result<IndexStats> {
+        let _lock = self.refresh_lock.lock().await;
+        let mut stats = IndexStats::default();
+
+        let paths = store::thread_files(dir)?;
+        let indexed: Vec<(String, i64, i64)> =
+            sqlx::query_as("SELECT path, mtime, size FROM indexed_threads")
+                .fetch_all(&self.pool)
+                .await?;
+
+        for (path, _, _) in &indexed {
+            let in_dir = Path::new(path).starts_with(dir);
+            if in_dir && !paths.iter().any(|p| p.to_string_lossy() == path.as_str()) {
+                self.remove(path).await?;
+                stats.removed += 1;
+            }
+        }
+
+        for path in paths {
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let mtime = millis(metadata.modified()?);
+            let size = metadata.len() as i64;
+            let key = path.to_string_lossy().into_owned();
+
+            let current = indexed
+                .iter()
+                .any(|(p, m, s)| *p == key && *m == mtime && *s == size);
+            if current && !full {
+                stats.unchanged += 1;
+                continue;
+            }
+
+            match store::load_thread(&path) {
+                Ok(thread) => {
+                    self.index_thread(&key, &thread, mtime, size).await?;
+                    stats.indexed += 1;
+                },
+                Err(e) => {
+                    // Keep the last good rows; a file mid-rewrite is retried
+                    // on the next refresh
+                    stats.errors.push(e.to_string());
+                },
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Replace the rows of one thread file
+    async fn index_thread(&self, path: &str, thread: &Thread, mtime: i64, size: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM thread_messages_fts WHERE thread_id IN
+             (SELECT thread_id FROM indexed_threads WHERE path = ?) OR thread_id = ?",
+        )
+        .bind(path)
+        .bind(&thread.id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM thread_file_refs WHERE thread_id IN
+             (SELECT thread_id FROM indexed_threads WHERE path = ?) OR thread_id = ?",
+        )
+        .bind(path)
+        .bind(&thread.id)
+        .execute(&mut *tx)
+        .await?;
+
+        for file in file_refs(thread) {
+            sqlx::query(
+                "INSERT INTO thread_file_refs (thread_id, message_id, path, access, tool, at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&thread.id)
+            .bind(file.message_id as i64)
+            .bind(&file.path)
+            .bind(file.access)
+            .bind(file.tool)
+            .bind(file.at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for (position, message) in thread.messages.iter().enumerate() {
+            let doc = MessageDocument::from_message(message);
+            if doc.is_empty() {
+                continue;
+            }
+            let (role, message_id) = match message {
+                Message::User(_) => ("user", message.message_id()),
+                Message::Assistant(_) => ("assistant", message.message_id()),
+            };
+
+            sqlx::query(
+                "INSERT INTO thread_messages_fts
+                     (thread_id, message_id, role, text, tool_input, tool_result)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&thread.id)
+            .bind(message_id.unwrap_or(position as u64) as i64)
+            .bind(role)
+            .bind(&doc.text)
+            .bind(&doc.tool_input)
+            .bind(&doc.tool_result)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO indexed_threads (path, thread_id, title, mtime, size, indexed_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(path) DO UPDATE SET
+                 thread_id = excluded.thread_id,
+                 title = excluded.title,
+                 mtime = excluded.mtime,
+                 size = excluded.size,
+                 indexed_at = excluded.indexed_at",
+        )
+        .bind(path)
+        .bind(&thread.id)
+        .bind(thread.display_title())
+        .bind(mtime)
+        .bind(size)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Drop the rows of a thread file
+    async fn remove(&self, path: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM thread_messages_fts WHERE thread_id IN
+             (SELECT thread_id FROM indexed_threads WHERE path = ?)",
+        )
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM thread_file_refs WHERE thread_id IN
+             (SELECT thread_id FROM indexed_threads WHERE path = ?)",
+        )
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM indexed_threads WHERE path = ?")
+            .bind(path)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Search message text, tool inputs and tool results (best matches
+    /// first)
+    ///
+    /// Without `options.raw`, `query` is escaped like prompt searches; see
+    /// [`fts_query`].
+    pub async fn search(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<ThreadSearchHit>> {
+        let match_expr = if options.raw {
+            query.trim().to_string()
+        } else {
+            fts_query(query)
+        };
+
+        if match_expr.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let hits = sqlx::query_as::<_, ThreadSearchHit>(
+            "SELECT f.thread_id,
+                    CAST(f.message_id AS INTEGER) AS message_id,
+                    f.role,
+                    COALESCE(t.title, '') AS title,
+                    bm25(thread_messages_fts, 0.0, 0.0, 0.0, 4.0, 1.0, 1.0) AS rank,
+                    snippet(thread_messages_fts, -1, ?, ?, '…', 16) AS snippet
+             FROM thread_messages_fts f
+             LEFT JOIN indexed_threads t ON t.thread_id = f.thread_id
+             WHERE thread_messages_fts MATCH ?
+             ORDER BY rank
+             LIMIT ?",
+        )
+        .bind(&options.highlight_start)
+        .bind(&options.highlight_end)
+        .bind(&match_expr)
+        .bind(options.limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| match e {
+            // Malformed raw FTS5 syntax is the caller's mistake
+            sqlx::Error::Database(db) if options.raw => {
+                AmpError::ValidationError(format!("Invalid search query: {}", db.message()))
+            },
+            e => e.into(),
+        })?;
+
+        Ok(hits)
+    }
+
+    /// Threads whose messages read, edited or attached `path` (most recent
+    /// reference first)
+    pub async fn threads_for_file(&self, path: &str, limit: i64) -> Result<Vec<FileThread>> {
+        let rows: Vec<(String, String, i64, i64, String)> = sqlx::query_as(
+            "SELECT r.thread_id, COALESCE(t.title, ''), r.message_id, r.at, r.access
+             FROM thread_file_refs r
+             LEFT JOIN indexed_threads t ON t.thread_id = r.thread_id
+             WHERE r.path = ?
+             ORDER BY r.message_id",
+        )
+        .bind(path)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut threads: Vec<FileThread> = Vec::new();
+        for (thread_id, title, message_id, at, access) in rows {
+            let position = match threads.iter().position(|t| t.thread_id == thread_id) {
+                Some(position) => position,
+                None => {
+                    threads.push(FileThread {
+                        thread_id,
+                        title,
+                        last_at: at,
+                        message_ids: Vec::new(),
+                        access: Vec::new(),
+                    });
+                    threads.len() - 1
+                },
+            };
+            let thread = &mut threads[position];
+            thread.last_at = thread.last_at.max(at);
+            if !thread.message_ids.contains(&message_id) {
+                thread.message_ids.push(message_id);
+            }
+            if !thread.access.contains(&access) {
+                thread.access.push(access);
+                thread.access.sort();
+            }
+        }
+
+        threads.sort_by(|a, b| {
+            b.last_at
+                .cmp(&a.last_at)
+                .then_with(|| a.thread_id.cmp(&b.thread_id))
+        });
+        threads.truncate(limit.max(0) as usize);
+        Ok(threads)
+    }
+}
+
+/// A file referenced by one message
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FileRef {
+    pub message_id: u64,
+    pub path: String,
+    /// `read`, `write` or `attached`
+    pub access: &'static str,
+    pub tool: Option<String>,
+    pub at: i64,
+}
+
+/// Files referenced by the tool calls and attached files of `thread`
+///
+/// Relative tool paths are resolved against the thread's workspace. Tool
+/// calls are `write` access when the tool name suggests a change (edit,
+/// create, write, ...) and `read` access otherwise.
+pub(crate) fn file_refs(thread: &Thread) -> Vec<FileRef> {
+    let workspace = thread.workspace();
+    let mut refs: Vec<FileRef> = Vec::new();
+    let mut at = thread.created;
+
+    for (position, message) in thread.messages.iter().enumerate() {
+        let message_id = message.message_id().unwrap_or(position as u64);
+        if let Message::User(user) = message {
+            at = user.meta.as_ref().and_then(|m| m.sent_at).unwrap_or(at);
+        }
+
+        for block in message.content() {
+            let (path, access, tool) = match block {
+                ContentBlock::File { file_uri, .. } => (uri_to_path(file_uri), "attached", None),
+                ContentBlock::ToolUse { name, input, .. } => {
+                    let Some(path) = PATH_KEYS
+                        .iter()
+                        .find_map(|k| input.get(*k).and_then(|v| v.as_str()))
+                    else {
+                        continue;
+                    };
+                    (
+                        resolve(path, workspace.as_deref()),
+                        tool_access(name),
+                        Some(name.clone()),
+                    )
+                },
+                _ => continue,
+            };
+
+            let file = FileRef {
+                message_id,
+                path,
+                access,
+                tool,
+                at,
+            };
+            if !refs.contains(&file) {
+                refs.push(file);
+            }
+        }
+    }
+    refs
+}
+
+/// Access of a tool call, guessed from the tool name
+pub(crate) fn tool_access(tool: &str) -> &'static str {
+    const WRITE_WORDS: &[&str] = &[
+        "edit", "create", "write", "delete", "remove", "rename", "move", "format", "undo",
+    ];
+    let tool = tool.to_lowercase();
+    if WRITE_WORDS.iter().any(|w| tool.contains(w)) {
+        "write"
+    } else {
+        "read"
+    }
+}
+
+/// `path` made absolute against `workspace`, without `.` components
+fn resolve(path: &str, workspace: Option<&str>) -> String {
+    let path = Path::new(path);
+    let joined = match workspace {
+        Some(root) if path.is_relative() => Path::new(root).join(path),
+        _ => path.to_path_buf(),
+    };
+    joined
+        .components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect::<std::path::PathBuf>()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Searchable text of one message
+#[derive(Debug, Default, PartialEq)]
+struct MessageDocument {
+    text: String,
+    tool_input: String,
+    tool_result: String,
+}
+
+impl MessageDocument {
+    fn from_message(message: &Message) -> Self {
+        let mut doc = Self::default();
+        for block in message.content() {
+            match block {
+                ContentBlock::Text { text } => push_line(&mut doc.text, text),
+                ContentBlock::ToolUse { name, input, .. } => {
+                    push_line(&mut doc.tool_input, name);
+                    push_strings(&mut doc.tool_input, input);
+                },
+                ContentBlock::ToolResult {
+                    content: Some(content),
+                    ..
+                } => push_strings(&mut doc.tool_result, content),
+                _ => {},
+            }
+        }
+        doc
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.tool_input.is_empty() && self.tool_result.is_empty()
+    }
+}
+
+fn push_line(out: &mut String, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(text);
+}
+
+/// Append the string leaves of a JSON value (keys and punctuation are noise
+/// for search)
+fn push_strings(out: &mut String, value: &Value) {
+    match value {
+        Value::String(s) => push_line(out, s),
+        Value::Array(items) => items.iter().for_each(|v| push_strings(out, v)),
+        Value::Object(map) => map.values().for_each(|v| push_strings(out, v)),
+        Value::Number(n) => push_line(out, &n.to_string()),
+        Value::Bool(_) | Value::Null => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime;
+    use crate::threads::store::thread_path;
+    use serde_json::json;
+
+    fn write(dir: &Path, id: &str, title: &str, messages: Value) {
+        let thread = json!({
+            "v": 1,
+            "id": id,
+            "created": 1_700_000_000_000i64,
+            "nextMessageId": 2,
+            "agentMode": "smart",
+            "title": title,
+            "messages": messages,
+        });
+        fs::write(thread_path(dir, id), thread.to_string()).unwrap();
+    }
+
+    const MIGRATION_BUG: &str = "T-00000001-0000-4000-8000-000000000000";
+    const OTHER: &str = "T-00000002-0000-4000-8000-000000000000";
+
+    fn fixtures(dir: &Path) {
+        write(
+            dir,
+            MIGRATION_BUG,
+            "Schema work",
+            json!([
+                {"role": "user", "messageId": 0, "content": [
+                    {"type": "text", "text": "The migration fails on startup"}
+                ]},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "text", "text": "Let me look at the schema."},
+                    {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"cmd": "cargo test migrations"}}
+                ]},
+                {"role": "user", "messageId": 2, "content": [
+                    {"type": "tool_result", "toolUseId": "t1", "content": "error: no such table prompts_fts"}
+                ]}
+            ]),
+        );
+        write(
+            dir,
+            OTHER,
+            "Lualine",
+            json!([
+                {"role": "user", "messageId": 0, "content": [
+                    {"type": "text", "text": "Add a lualine component"}
+                ]}
+            ]),
+        );
+    }
+
+    async fn open(dir: &Path) -> Result<ThreadIndex> {
+        ThreadIndex::open(&dir.join("index/threads.db")).await
+    }
+
+    #[test]
+    fn test_search_text_tools_and_results() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        fixtures(dir.path());
+
+        runtime::block_on(async {
+            let index = open(dir.path()).await?;
+            let stats = index.refresh(dir.path(), false).await?;
+            assert_eq!(stats.indexed, 2);
+
+            let options = SearchOptions::default();
+            let hits = index.search("migration", &options).await?;
+            assert_eq!(hits[0].thread_id, MIGRATION_BUG);
+            assert_eq!(hits[0].message_id, 0);
+            assert_eq!(hits[0].title, "Schema work");
+            assert!(hits[0].snippet.contains("**migration**"));
+
+            // Tool inputs and results are searchable too
+            let hits = index.search("cargo test", &options).await?;
+            assert_eq!((hits.len(), hits[0].message_id), (1, 1));
+            let hits = index.search("prompts_fts", &options).await?;
+            assert_eq!(hits[0].message_id, 2);
+
+            assert!(index.search("  ", &options).await?.is_empty());
+            let raw = SearchOptions {
+                raw: true,
+                ..Default::default()
+            };
+            let err = index.search("\"unbalanced", &raw).await.unwrap_err();
+            assert_eq!(err.category(), "validation");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_refresh_is_incremental() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        fixtures(dir.path());
+
+        runtime::block_on(async {
+            let index = open(dir.path()).await?;
+            index.refresh(dir.path(), false).await?;
+
+            let stats = index.refresh(dir.path(), false).await?;
+            assert_eq!((stats.indexed, stats.unchanged), (0, 2));
+
+            // Rewriting a thread replaces its rows
+            write(
+                dir.path(),
+                OTHER,
+                "Lualine",
+                json!([{"role": "user", "messageId": 0, "content": [
+                    {"type": "text", "text": "Add a statusline widget instead"}
+                ]}]),
+            );
+            let stats = index.refresh(dir.path(), false).await?;
+            assert_eq!(stats.indexed, 1);
+            let options = SearchOptions::default();
+            assert!(index.search("lualine", &options).await?.is_empty());
+            assert_eq!(index.search("widget", &options).await?.len(), 1);
+
+            fs::remove_file(thread_path(dir.path(), MIGRATION_BUG)).unwrap();
+            let stats = index.refresh(dir.path(), false).await?;
+            assert_eq!(stats.removed, 1);
+            assert!(index.search("migration", &options).await?.is_empty());
+
+            let stats = index.refresh(dir.path(), true).await?;
+            assert_eq!(stats.indexed, 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_threads_for_file() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |id: &str, messages: Value| {
+            let thread = json!({
+                "v": 1,
+                "id": id,
+                "created": 1_700_000_000_000i64,
+                "agentMode": "smart",
+                "env": {"initial": {"trees": [{"uri": "file:///src/repo"}]}},
+                "messages": messages,
+            });
+            fs::write(thread_path(dir.path(), id), thread.to_string()).unwrap();
+        };
+        write(
+            MIGRATION_BUG,
+            json!([
+                {"role": "user", "messageId": 0, "meta": {"sentAt": 1_700_000_100_000i64}, "content": [
+                    {"type": "file", "fileUri": "file:///src/repo/src/lib.rs", "text": "fn a() {}"}
+                ]},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "tool_use", "id": "t1", "name": "edit_file",
+                     "input": {"path": "./src/lib.rs", "old_str": "a", "new_str": "b"}}
+                ]}
+            ]),
+        );
+        write(
+            OTHER,
+            json!([
+                {"role": "user", "messageId": 0, "meta": {"sentAt": 1_700_000_200_000i64}, "content": [
+                    {"type": "text", "text": "What does lib.rs do?"}
+                ]},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "tool_use", "id": "t1", "name": "Read", "input": {"path": "/src/repo/src/lib.rs"}},
+                    {"type": "tool_use", "id": "t2", "name": "Read", "input": {"path": "/src/repo/README.md"}}
+                ]}
+            ]),
+        );
+
+        runtime::block_on(async {
+            let index = open(dir.path()).await?;
+            index.refresh(dir.path(), false).await?;
+
+            let threads = index.threads_for_file("/src/repo/src/lib.rs", 10).await?;
+            let ids: Vec<_> = threads.iter().map(|t| t.thread_id.as_str()).collect();
+            // Most recent reference first
+            assert_eq!(ids, vec![OTHER, MIGRATION_BUG]);
+            assert_eq!(threads[0].access, vec!["read"]);
+            assert_eq!(threads[0].last_at, 1_700_000_200_000);
+            assert_eq!(threads[1].message_ids, vec![0, 1]);
+            assert_eq!(threads[1].access, vec!["attached", "write"]);
+
+            assert_eq!(
+                index
+                    .threads_for_file("/src/repo/src/lib.rs", 1)
+                    .await?
+                    .len(),
+                1
+            );
+            assert!(index
+                .threads_for_file("/src/repo/other.rs", 10)
+                .await?
+                .is_empty());
+
+            fs::remove_file(thread_path(dir.path(), OTHER)).unwrap();
+            index.refresh(dir.path(), false).await?;
+            assert!(index
+                .threads_for_file("/src/repo/README.md", 10)
+                .await?
+                .is_empty());
+
+            Ok(())
+        })
+    }
+}
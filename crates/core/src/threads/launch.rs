@@ -0,0 +1,413 @@
+//! Continuing and forking threads with the Amp CLI
+//!
+//! [`launch`] runs `amp threads continue|fork <id> --execute <message>` on
+//! the global runtime and tracks the process in a registry: its status, exit
+//! code and the last lines of its output stay available through
+//! [`processes`] until [`MAX_FINISHED`] newer runs have ended, and a running
+//! process can be stopped with [`kill`].
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+use super::store;
+use crate::errors::{AmpError, Result};
+use crate::runtime;
+
+/// Amp CLI executable, looked up on `PATH`
+pub const AMP_BIN: &str = "amp";
+
+/// Output lines kept per process
+const MAX_OUTPUT_LINES: usize = 200;
+
+/// How long output is still read once a process has ended
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Finished processes kept in the registry
+pub const MAX_FINISHED: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchAction {
+    /// Add a message to the thread
+    Continue,
+    /// Copy the thread into a new one and add the message there
+    Fork,
+}
+
+impl LaunchAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LaunchAction::Continue => "continue",
+            LaunchAction::Fork => "fork",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum ProcessStatus {
+    Running,
+    /// Ended on its own (`code` is `None` when killed by a signal)
+    Exited {
+        code: Option<i32>,
+    },
+    /// Stopped with [`kill`]
+    Killed,
+    /// Could not be waited on
+    Failed {
+        message: String,
+    },
+}
+
+/// A tracked Amp CLI process
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    /// Registry id (not the OS pid)
+    pub id: u64,
+    pub action: LaunchAction,
+    pub thread_id: String,
+    pub pid: Option<u32>,
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// Unix timestamp (milliseconds)
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub status: ProcessStatus,
+    /// Last lines of stdout and stderr, interleaved as they arrived
+    pub output: VecDeque<String>,
+}
+
+/// Thread id from an id or a thread URL (`https://ampcode.com/threads/T-…`)
+///
+/// The id must match the `threadId` pattern of `schemas/common.json`.
+pub fn resolve_thread_id(input: &str) -> Result<String> {
+    let input = input.trim().trim_end_matches('/');
+    let id = input
+        .rsplit('/')
+        .next()
+        .unwrap_or(input)
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    store::validate_thread_id(id)?;
+    Ok(id.to_string())
+}
+
+/// Amp CLI arguments of an action
+pub fn command_args(action: LaunchAction, thread_id: &str, message: &str) -> Vec<String> {
+    vec![
+        "threads".to_string(),
+        action.as_str().to_string(),
+        thread_id.to_string(),
+        "--execute".to_string(),
+        message.to_string(),
+    ]
+}
+
+struct Tracked {
+    info: Arc<Mutex<ProcessInfo>>,
+    kill: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+pub struct ProcessRegistry {
+    next_id: AtomicU64,
+    processes: Mutex<Vec<Tracked>>,
+}
+
+impl ProcessRegistry {
+    /// Start `program` and track it until it exits
+    pub fn spawn(
+        &self,
+        program: &str,
+        args: Vec<String>,
+        cwd: Option<&Path>,
+        action: LaunchAction,
+        thread_id: &str,
+    ) -> Result<ProcessInfo> {
+        let mut command = Command::new(program);
+        command
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = cwd {
+            command.current_dir(cwd);
+        }
+
+        // Child processes are reaped by the runtime's driver
+        let mut child = {
+            let _guard = runtime::RUNTIME.enter();
+            command
+                .spawn()
+                .map_err(|e| AmpError::AmpCliError(format!("Failed to start {}: {}", program, e)))?
+        };
+
+        let info = Arc::new(Mutex::new(ProcessInfo {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst) + 1,
+            action,
+            thread_id: thread_id.to_string(),
+            pid: child.id(),
+            program: program.to_string(),
+            args,
+            cwd: cwd.map(Path::to_path_buf),
+            started_at: Utc::now().timestamp_millis(),
+            ended_at: None,
+            status: ProcessStatus::Running,
+            output: VecDeque::new(),
+        }));
+
+        let stdout = child.stdout.take().map(|s| capture(s, info.clone()));
+        let stderr = child.stderr.take().map(|s| capture(s, info.clone()));
+        let (kill_tx, kill_rx) = oneshot::channel();
+
+        let task_info = info.clone();
+        runtime::spawn(async move {
+            let status = tokio::select! {
+                result = child.wait() => match result {
+                    Ok(status) => ProcessStatus::Exited { code: status.code() },
+                    Err(e) => ProcessStatus::Failed { message: e.to_string() },
+                },
+                _ = kill_rx => {
+                    let _ = child.kill().await;
+                    ProcessStatus::Killed
+                },
+            };
+            // Let the readers drain what the process wrote before exiting.
+            // Children it left behind may hold the pipes open, so give up
+            // after a moment rather than waiting for them.
+            for mut reader in [stdout, stderr].into_iter().flatten() {
+                if tokio::time::timeout(DRAIN_TIMEOUT, &mut reader)
+                    .await
+                    .is_err()
+                {
+                    reader.abort();
+                }
+            }
+
+            let mut info = task_info.lock().unwrap_or_else(|e| e.into_inner());
+            info.status = status;
+            info.ended_at = Some(Utc::now().timestamp_millis());
+        });
+
+        let snapshot = lock(&info).clone();
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        processes.push(Tracked {
+            info,
+            kill: Some(kill_tx),
+        });
+        prune(&mut processes);
+        Ok(snapshot)
+    }
+
+    /// Every tracked process, oldest first
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        let processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        processes.iter().map(|p| lock(&p.info).clone()).collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<ProcessInfo> {
+        let processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        processes
+            .iter()
+            .map(|p| lock(&p.info))
+            .find(|info| info.id == id)
+            .map(|info| info.clone())
+    }
+
+    /// Stop a running process; `false` if it is unknown or already ended
+    pub fn kill(&self, id: u64) -> bool {
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(process) = processes.iter_mut().find(|p| lock(&p.info).id == id) else {
+            return false;
+        };
+        if lock(&process.info).status != ProcessStatus::Running {
+            return false;
+        }
+        process
+            .kill
+            .take()
+            .is_some_and(|kill| kill.send(()).is_ok())
+    }
+}
+
+fn lock(info: &Mutex<ProcessInfo>) -> std::sync::MutexGuard<'_, ProcessInfo> {
+    info.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Drop the oldest finished processes beyond [`MAX_FINISHED`]
+fn prune(processes: &mut Vec<Tracked>) {
+    let finished = processes
+        .iter()
+        .filter(|p| lock(&p.info).status != ProcessStatus::Running)
+        .count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED);
+    processes.retain(|p| {
+        if excess > 0 && lock(&p.info).status != ProcessStatus::Running {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+/// Append the lines of a process stream to its output
+fn capture<R>(stream: R, info: Arc<Mutex<ProcessInfo>>) -> tokio::task::JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    runtime::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let mut info = lock(&info);
+            info.output.push_back(line);
+            if info.output.len() > MAX_OUTPUT_LINES {
+                info.output.pop_front();
+            }
+        }
+    })
+}
+
+/// The registry used by [`launch`]
+static REGISTRY: Lazy<ProcessRegistry> = Lazy::new(Default::default);
+
+/// Continue or fork `thread_id` with `message` in the Amp CLI
+///
+/// Without a `cwd`, the process runs in the thread's workspace when it is a
+/// local directory.
+pub fn launch(
+    action: LaunchAction,
+    thread_id: &str,
+    message: &str,
+    cwd: Option<&Path>,
+) -> Result<ProcessInfo> {
+    let thread_id = resolve_thread_id(thread_id)?;
+    if message.trim().is_empty() {
+        return Err(AmpError::ValidationError("Message is empty".into()));
+    }
+
+    let workspace = store::get_thread(&super::threads_dir(), &thread_id)
+        .ok()
+        .and_then(|t| t.workspace())
+        .map(PathBuf::from)
+        .filter(|p| p.is_dir());
+    let cwd = cwd.map(Path::to_path_buf).or(workspace);
+
+    REGISTRY.spawn(
+        AMP_BIN,
+        command_args(action, &thread_id, message),
+        cwd.as_deref(),
+        action,
+        &thread_id,
+    )
+}
+
+/// Processes started by [`launch`], oldest first
+pub fn processes() -> Vec<ProcessInfo> {
+    REGISTRY.list()
+}
+
+pub fn process(id: u64) -> Option<ProcessInfo> {
+    REGISTRY.get(id)
+}
+
+/// Stop a process started by [`launch`]
+pub fn kill(id: u64) -> bool {
+    REGISTRY.kill(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const ID: &str = "T-00000001-0000-4000-8000-000000000000";
+
+    fn wait_until_done(registry: &ProcessRegistry, id: u64) -> ProcessInfo {
+        for _ in 0..100 {
+            let info = registry.get(id).unwrap();
+            if info.status != ProcessStatus::Running {
+                return info;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("process {} did not finish", id);
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_resolve_thread_id() {
+        assert_eq!(resolve_thread_id(ID).unwrap(), ID);
+        let url = format!("https://ampcode.com/threads/{}?tab=1", ID);
+        assert_eq!(resolve_thread_id(&url).unwrap(), ID);
+        assert!(resolve_thread_id("T-123").is_err());
+        assert!(resolve_thread_id("../T-00000001").is_err());
+
+        assert_eq!(
+            command_args(LaunchAction::Fork, ID, "try again"),
+            vec!["threads", "fork", ID, "--execute", "try again"]
+        );
+    }
+
+    #[test]
+    fn test_tracks_output_and_exit() {
+        let registry = ProcessRegistry::default();
+        let dir = tempfile::tempdir().unwrap();
+        let started = registry
+            .spawn(
+                "sh",
+                sh("pwd; echo oops >&2; exit 3"),
+                Some(dir.path()),
+                LaunchAction::Continue,
+                ID,
+            )
+            .unwrap();
+        assert_eq!(started.status, ProcessStatus::Running);
+        assert!(started.pid.is_some());
+
+        let done = wait_until_done(&registry, started.id);
+        assert_eq!(done.status, ProcessStatus::Exited { code: Some(3) });
+        assert!(done.ended_at.is_some());
+        assert!(done.output.contains(&"oops".to_string()));
+        let cwd = dir.path().canonicalize().unwrap();
+        assert!(done.output.contains(&cwd.to_string_lossy().into_owned()));
+
+        // Finished processes cannot be killed
+        assert!(!registry.kill(started.id));
+    }
+
+    #[test]
+    fn test_kill() {
+        let registry = ProcessRegistry::default();
+        let info = registry
+            .spawn("sh", sh("sleep 30"), None, LaunchAction::Fork, ID)
+            .unwrap();
+
+        assert!(registry.kill(info.id));
+        assert_eq!(
+            wait_until_done(&registry, info.id).status,
+            ProcessStatus::Killed
+        );
+        assert!(!registry.kill(99));
+
+        let err = registry
+            .spawn("/nonexistent/amp", vec![], None, LaunchAction::Fork, ID)
+            .unwrap_err();
+        assert!(err.to_string().contains("Failed to start"));
+    }
+}
@@ -0,0 +1,469 @@
+//! Archiving and deleting threads
+//!
+//! Threads are moved between three directories ("areas"): the Amp thread
+//! directory, an archive and a trash, the latter two next to it
+//! (`threads-archive` and `threads-trash`), so moves are plain renames on one
+//! file system. Amp only reads its own directory, so archived and trashed
+//! threads disappear from it until they are unarchived or restored.
+//!
+//! The trash keeps a `.manifest` of where and when each thread was trashed,
+//! used by [`restore`] and [`purge`].
+//!
+//! Every operation takes a [`Selection`]: explicit ids, or a [`ThreadFilter`]
+//! for bulk changes. Ids are processed independently; failures are reported
+//! per id in the [`BulkResult`] rather than aborting the batch.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::store::{self, ThreadSummary};
+use crate::errors::{AmpError, Result};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Name of the trash manifest (not a `.json` file, so it is never listed as
+/// a thread)
+const MANIFEST: &str = ".manifest";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Area {
+    Threads,
+    Archive,
+    Trash,
+}
+
+impl Area {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "threads" => Ok(Area::Threads),
+            "archive" => Ok(Area::Archive),
+            "trash" => Ok(Area::Trash),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown area '{}' (expected threads, archive or trash)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Directories of the three areas
+#[derive(Debug, Clone)]
+pub struct ThreadAreas {
+    pub threads: PathBuf,
+    pub archive: PathBuf,
+    pub trash: PathBuf,
+}
+
+impl ThreadAreas {
+    /// Areas around the thread directory `threads`
+    pub fn new(threads: &Path) -> Self {
+        let sibling = |name: &str| match threads.parent() {
+            Some(parent) => parent.join(name),
+            None => threads.join(name),
+        };
+        Self {
+            threads: threads.to_path_buf(),
+            archive: sibling("threads-archive"),
+            trash: sibling("threads-trash"),
+        }
+    }
+
+    pub fn dir(&self, area: Area) -> &Path {
+        match area {
+            Area::Threads => &self.threads,
+            Area::Archive => &self.archive,
+            Area::Trash => &self.trash,
+        }
+    }
+}
+
+/// Criteria for bulk operations; at least one must be set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ThreadFilter {
+    /// Only threads last modified (in the trash: trashed) at least this many
+    /// days ago
+    pub older_than_days: Option<f64>,
+    /// Only threads whose first workspace root has this path or name
+    pub workspace: Option<String>,
+}
+
+impl ThreadFilter {
+    fn validate(&self) -> Result<()> {
+        if self.older_than_days.is_none() && self.workspace.is_none() {
+            return Err(AmpError::ValidationError(
+                "A thread filter needs older_than_days or workspace".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `since` is the time the age is measured from (milliseconds)
+    fn matches(&self, summary: &ThreadSummary, since: i64, now: i64) -> bool {
+        let old_enough = self
+            .older_than_days
+            .is_none_or(|days| now - since >= (days * DAY_MS as f64) as i64);
+        let in_workspace = self.workspace.as_deref().is_none_or(|w| {
+            summary.workspace.as_deref() == Some(w) || summary.workspace_name.as_deref() == Some(w)
+        });
+        old_enough && in_workspace
+    }
+}
+
+/// Threads an operation applies to
+#[derive(Debug, Clone)]
+pub enum Selection {
+    Ids(Vec<String>),
+    Filter(ThreadFilter),
+}
+
+/// Outcome of an operation on several threads
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BulkResult {
+    /// Threads changed (or, in a dry run, that would be)
+    pub ids: Vec<String>,
+    pub failed: Vec<BulkFailure>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkFailure {
+    pub id: String,
+    pub message: String,
+}
+
+/// A thread in the trash
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedThread {
+    #[serde(flatten)]
+    pub summary: ThreadSummary,
+    /// Unix timestamp (milliseconds)
+    pub deleted_at: Option<i64>,
+    /// Area the thread is restored to
+    pub origin: Area,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct TrashEntry {
+    deleted_at: i64,
+    origin: Area,
+}
+
+type Manifest = BTreeMap<String, TrashEntry>;
+
+/// Move threads to the archive
+pub fn archive(areas: &ThreadAreas, selection: &Selection, dry_run: bool) -> Result<BulkResult> {
+    let targets = resolve(areas, selection, &[Area::Threads])?;
+    apply(targets, dry_run, |id, _| {
+        move_file(areas, id, Area::Threads, Area::Archive)
+    })
+}
+
+/// Move archived threads back to the thread directory
+pub fn unarchive(areas: &ThreadAreas, selection: &Selection, dry_run: bool) -> Result<BulkResult> {
+    let targets = resolve(areas, selection, &[Area::Archive])?;
+    apply(targets, dry_run, |id, _| {
+        move_file(areas, id, Area::Archive, Area::Threads)
+    })
+}
+
+/// Move threads (active or archived) to the trash
+pub fn delete(areas: &ThreadAreas, selection: &Selection, dry_run: bool) -> Result<BulkResult> {
+    let targets = resolve(areas, selection, &[Area::Threads, Area::Archive])?;
+    let mut manifest = read_manifest(areas)?;
+
+    let result = apply(targets, dry_run, |id, origin| {
+        move_file(areas, id, origin, Area::Trash)?;
+        let deleted_at = Utc::now().timestamp_millis();
+        manifest.insert(id.to_string(), TrashEntry { deleted_at, origin });
+        Ok(())
+    })?;
+
+    if !dry_run {
+        write_manifest(areas, &manifest)?;
+    }
+    Ok(result)
+}
+
+/// Move trashed threads back to where they were deleted from
+pub fn restore(areas: &ThreadAreas, selection: &Selection, dry_run: bool) -> Result<BulkResult> {
+    let targets = resolve(areas, selection, &[Area::Trash])?;
+    let mut manifest = read_manifest(areas)?;
+
+    let result = apply(targets, dry_run, |id, _| {
+        let origin = manifest.get(id).map_or(Area::Threads, |e| e.origin);
+        move_file(areas, id, Area::Trash, origin)?;
+        manifest.remove(id);
+        Ok(())
+    })?;
+
+    if !dry_run {
+        write_manifest(areas, &manifest)?;
+    }
+    Ok(result)
+}
+
+/// Permanently delete trashed threads
+pub fn purge(areas: &ThreadAreas, selection: &Selection, dry_run: bool) -> Result<BulkResult> {
+    let targets = resolve(areas, selection, &[Area::Trash])?;
+    let mut manifest = read_manifest(areas)?;
+
+    let result = apply(targets, dry_run, |id, _| {
+        fs::remove_file(store::thread_path(&areas.trash, id))?;
+        manifest.remove(id);
+        Ok(())
+    })?;
+
+    if !dry_run {
+        write_manifest(areas, &manifest)?;
+    }
+    Ok(result)
+}
+
+/// Threads in the trash, most recently deleted first
+pub fn list_trash(areas: &ThreadAreas) -> Result<(Vec<TrashedThread>, Vec<String>)> {
+    let manifest = read_manifest(areas)?;
+    let (summaries, errors) = store::summaries(&areas.trash)?;
+
+    let mut trashed: Vec<TrashedThread> = summaries
+        .into_iter()
+        .map(|summary| {
+            let entry = manifest.get(&summary.id);
+            TrashedThread {
+                deleted_at: entry.map(|e| e.deleted_at),
+                origin: entry.map_or(Area::Threads, |e| e.origin),
+                summary,
+            }
+        })
+        .collect();
+    trashed.sort_by_key(|t| std::cmp::Reverse(t.deleted_at.unwrap_or(t.summary.updated)));
+
+    Ok((trashed, errors))
+}
+
+/// Threads selected in the `from` areas, with the area each was found in
+///
+/// Ids that are malformed or not found are returned with an error instead of
+/// failing the whole selection.
+fn resolve(
+    areas: &ThreadAreas,
+    selection: &Selection,
+    from: &[Area],
+) -> Result<Vec<(String, Result<Area>)>> {
+    match selection {
+        Selection::Ids(ids) => {
+            if ids.is_empty() {
+                return Err(AmpError::ValidationError("No thread ids given".to_string()));
+            }
+            Ok(ids
+                .iter()
+                .map(|id| {
+                    let found = store::validate_thread_id(id).and_then(|_| {
+                        from.iter()
+                            .copied()
+                            .find(|area| store::thread_path(areas.dir(*area), id).is_file())
+                            .ok_or_else(|| {
+                                AmpError::ValidationError(format!("Thread '{}' not found", id))
+                            })
+                    });
+                    (id.clone(), found)
+                })
+                .collect())
+        },
+        Selection::Filter(filter) => {
+            filter.validate()?;
+            let now = Utc::now().timestamp_millis();
+            let manifest = read_manifest(areas)?;
+
+            let mut targets = Vec::new();
+            for area in from {
+                let (mut summaries, _) = store::summaries(areas.dir(*area))?;
+                summaries.sort_by(|a, b| a.id.cmp(&b.id));
+                for summary in summaries {
+                    let since = match area {
+                        Area::Trash => manifest
+                            .get(&summary.id)
+                            .map_or(summary.updated, |e| e.deleted_at),
+                        _ => summary.updated,
+                    };
+                    if filter.matches(&summary, since, now) {
+                        targets.push((summary.id, Ok(*area)));
+                    }
+                }
+            }
+            Ok(targets)
+        },
+    }
+}
+
+/// Run `op` on each resolved thread, collecting failures per id
+fn apply<F>(targets: Vec<(String, Result<Area>)>, dry_run: bool, mut op: F) -> Result<BulkResult>
+where
+    F: FnMut(&str, Area) -> Result<()>,
+{
+    let mut result = BulkResult {
+        dry_run,
+        ..Default::default()
+    };
+
+    for (id, found) in targets {
+        let outcome = found.and_then(|area| if dry_run { Ok(()) } else { op(&id, area) });
+        match outcome {
+            Ok(()) => result.ids.push(id),
+            Err(e) => result.failed.push(BulkFailure {
+                id,
+                message: e.user_message(),
+            }),
+        }
+    }
+
+    Ok(result)
+}
+
+fn move_file(areas: &ThreadAreas, id: &str, from: Area, to: Area) -> Result<()> {
+    let source = store::thread_path(areas.dir(from), id);
+    let target = store::thread_path(areas.dir(to), id);
+    if target.exists() {
+        return Err(AmpError::ValidationError(format!(
+            "Thread '{}' already exists in {}",
+            id,
+            areas.dir(to).display()
+        )));
+    }
+
+    fs::create_dir_all(areas.dir(to))?;
+    fs::rename(source, target)?;
+    Ok(())
+}
+
+fn read_manifest(areas: &ThreadAreas) -> Result<Manifest> {
+    match fs::read_to_string(areas.trash.join(MANIFEST)) {
+        Ok(text) => Ok(serde_json::from_str(&text)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_manifest(areas: &ThreadAreas, manifest: &Manifest) -> Result<()> {
+    fs::create_dir_all(&areas.trash)?;
+    fs::write(
+        areas.trash.join(MANIFEST),
+        serde_json::to_string_pretty(manifest)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threads::store::tests::write_thread;
+
+    fn setup() -> (tempfile::TempDir, ThreadAreas) {
+        let root = tempfile::tempdir().unwrap();
+        let threads = root.path().join("threads");
+        fs::create_dir_all(&threads).unwrap();
+        let areas = ThreadAreas::new(&threads);
+        (root, areas)
+    }
+
+    fn ids_in(dir: &Path) -> Vec<String> {
+        store::thread_files(dir)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_stem().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_and_unarchive() {
+        let (_root, areas) = setup();
+        let a = write_thread(&areas.threads, 1, "a", 1);
+        let b = write_thread(&areas.threads, 2, "b", 1);
+
+        let ids = Selection::Ids(vec![a.clone(), "T-nope".into(), "../etc/passwd".into()]);
+        let result = archive(&areas, &ids, false).unwrap();
+        assert_eq!(result.ids, vec![a.clone()]);
+        assert_eq!(result.failed.len(), 2);
+        assert_eq!(ids_in(&areas.threads), vec![b.clone()]);
+        assert_eq!(ids_in(&areas.archive), vec![a.clone()]);
+
+        let back = unarchive(&areas, &Selection::Ids(vec![a.clone()]), false).unwrap();
+        assert_eq!(back.ids, vec![a.clone()]);
+        assert_eq!(ids_in(&areas.threads), vec![a, b]);
+    }
+
+    #[test]
+    fn test_delete_restore_and_purge() {
+        let (_root, areas) = setup();
+        let active = write_thread(&areas.threads, 1, "active", 1);
+        let archived = write_thread(&areas.threads, 2, "archived", 1);
+        archive(&areas, &Selection::Ids(vec![archived.clone()]), false).unwrap();
+
+        let both = Selection::Ids(vec![active.clone(), archived.clone()]);
+        assert_eq!(delete(&areas, &both, false).unwrap().ids.len(), 2);
+        assert!(ids_in(&areas.threads).is_empty());
+
+        let (trash, _) = list_trash(&areas).unwrap();
+        assert_eq!(trash.len(), 2);
+        let origin = |id: &str| trash.iter().find(|t| t.summary.id == id).unwrap().origin;
+        assert_eq!(origin(&archived), Area::Archive);
+
+        // Restored threads go back where they came from
+        restore(&areas, &both, false).unwrap();
+        assert_eq!(ids_in(&areas.threads), vec![active.clone()]);
+        assert_eq!(ids_in(&areas.archive), vec![archived.clone()]);
+
+        delete(&areas, &Selection::Ids(vec![active.clone()]), false).unwrap();
+        let recent = Selection::Filter(ThreadFilter {
+            older_than_days: Some(1.0),
+            ..Default::default()
+        });
+        // Just trashed, so not old enough
+        assert!(purge(&areas, &recent, false).unwrap().ids.is_empty());
+        purge(&areas, &Selection::Ids(vec![active]), false).unwrap();
+        assert!(list_trash(&areas).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_bulk_filters_and_dry_run() {
+        let (_root, areas) = setup();
+        write_thread(&areas.threads, 1, "a", 1);
+        write_thread(&areas.threads, 2, "b", 1);
+
+        let by_workspace = Selection::Filter(ThreadFilter {
+            workspace: Some("repo".into()),
+            ..Default::default()
+        });
+        let preview = archive(&areas, &by_workspace, true).unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.ids.len(), 2);
+        assert_eq!(ids_in(&areas.threads).len(), 2);
+
+        let elsewhere = Selection::Filter(ThreadFilter {
+            workspace: Some("/other".into()),
+            ..Default::default()
+        });
+        assert!(archive(&areas, &elsewhere, false).unwrap().ids.is_empty());
+
+        // Files were just written, so nothing is a day old
+        let old = Selection::Filter(ThreadFilter {
+            older_than_days: Some(1.0),
+            workspace: Some("/src/repo".into()),
+        });
+        assert!(archive(&areas, &old, false).unwrap().ids.is_empty());
+        let now = Selection::Filter(ThreadFilter {
+            older_than_days: Some(0.0),
+            ..Default::default()
+        });
+        assert_eq!(archive(&areas, &now, false).unwrap().ids.len(), 2);
+
+        let empty = Selection::Filter(ThreadFilter::default());
+        let err = archive(&areas, &empty, false).unwrap_err();
+        assert_eq!(err.category(), "validation");
+    }
+}
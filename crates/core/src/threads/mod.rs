@@ -0,0 +1,75 @@
+//! Local Amp threads
+//!
+//! The Amp CLI keeps each thread as a `T-<uuid>.json` file in
+//! `~/.local/share/amp/threads`. [`model`] is the typed file format
+//! (`schemas/thread.json`), [`store`] lists and loads the files, [`index`]
+//! keeps a full-text index of their messages, [`render`] turns a thread into
+//! Markdown and [`export`] into shareable files, [`compare`] lines two up
+//! side by side, [`usage`] sums token and
+//! credit spend, [`watcher`] reports changes as they happen, [`manage`]
+//! archives and deletes threads and [`launch`] continues or forks them with
+//! the Amp CLI.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+pub mod compare;
+pub mod export;
+pub mod index;
+pub mod launch;
+pub mod manage;
+pub mod model;
+pub mod render;
+pub mod store;
+pub mod usage;
+pub mod watcher;
+
+/// `threads` section of the plugin configuration
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ThreadSettings {
+    /// Thread directory, if not the Amp CLI default
+    pub dir: Option<PathBuf>,
+    /// Watch the directory and fire `User AmpThreadsChanged` on changes
+    pub watch: bool,
+}
+
+impl Default for ThreadSettings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            watch: true,
+        }
+    }
+}
+
+static SETTINGS: OnceLock<ThreadSettings> = OnceLock::new();
+
+/// Set the thread settings (first call wins, like the rest of the config)
+pub fn configure(settings: ThreadSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+pub fn settings() -> ThreadSettings {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+/// Directory holding the thread files
+///
+/// The configured directory, else `$XDG_DATA_HOME/amp/threads`, else
+/// `~/.local/share/amp/threads` (also on macOS, where the Amp CLI uses the
+/// same layout).
+pub fn threads_dir() -> PathBuf {
+    if let Some(dir) = settings().dir {
+        return dir;
+    }
+
+    std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("amp/threads")
+}
@@ -0,0 +1,425 @@
+//! Typed thread file format (`schemas/thread.json`)
+//!
+//! Fields are optional wherever the schema allows it, and unknown content
+//! block types are kept as [`ContentBlock::Unknown`] so threads written by
+//! newer Amp versions still load.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A thread file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Thread {
+    /// Schema version
+    pub v: u32,
+    /// `T-<uuid>`
+    pub id: String,
+    /// Unix timestamp (milliseconds)
+    pub created: i64,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub next_message_id: u64,
+    #[serde(default)]
+    pub agent_mode: AgentMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<ThreadEnvironment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_ledger: Option<UsageLedger>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentMode {
+    #[default]
+    Smart,
+    Fast,
+    /// A mode this version does not know about
+    #[serde(other)]
+    Other,
+}
+
+impl AgentMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentMode::Smart => "smart",
+            AgentMode::Fast => "fast",
+            AgentMode::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum Message {
+    User(UserMessage),
+    Assistant(AssistantMessage),
+}
+
+impl Message {
+    pub fn message_id(&self) -> Option<u64> {
+        match self {
+            Message::User(m) => Some(m.message_id),
+            Message::Assistant(m) => m.message_id,
+        }
+    }
+
+    pub fn content(&self) -> &[ContentBlock] {
+        match self {
+            Message::User(m) => &m.content,
+            Message::Assistant(m) => &m.content,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessage {
+    pub message_id: u64,
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_state: Option<UserState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_mode: Option<AgentMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<MessageMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssistantMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<u64>,
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<MessageState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<MessageUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserState {
+    #[serde(default)]
+    pub currently_visible_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageMeta {
+    /// Unix timestamp (milliseconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageState {
+    /// `complete` or `incomplete`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    File {
+        file_uri: String,
+        #[serde(default)]
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        provider: Option<String>,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    #[serde(rename_all = "camelCase")]
+    ToolResult {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_use_id: Option<String>,
+        /// A string per the schema, but older threads store structured
+        /// results
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+    },
+    /// A block type this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageUsage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credits: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadEnvironment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial: Option<InitialEnvironment>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InitialEnvironment {
+    #[serde(default)]
+    pub trees: Vec<WorkspaceTree>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<PlatformInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceTree {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_browser: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageLedger {
+    #[serde(default)]
+    pub events: Vec<UsageLedgerEvent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageLedgerEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// RFC 3339 date-time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credits: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Tokens>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_message_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_message_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Tokens {
+    #[serde(default)]
+    pub input: u64,
+    #[serde(default)]
+    pub output: u64,
+}
+
+impl Thread {
+    /// First workspace root of the thread, as a local path when it is a
+    /// `file://` URI
+    pub fn workspace(&self) -> Option<String> {
+        let tree = self.first_tree()?;
+        match tree.uri.as_deref() {
+            Some(uri) => Some(uri_to_path(uri)),
+            None => tree.display_name.clone(),
+        }
+    }
+
+    /// Display name of the first workspace root
+    pub fn workspace_name(&self) -> Option<String> {
+        let tree = self.first_tree()?;
+        tree.display_name.clone().or_else(|| {
+            let path = uri_to_path(tree.uri.as_deref()?);
+            path.rsplit('/').find(|s| !s.is_empty()).map(String::from)
+        })
+    }
+
+    fn first_tree(&self) -> Option<&WorkspaceTree> {
+        self.env.as_ref()?.initial.as_ref()?.trees.first()
+    }
+
+    /// Title, or the start of the first user message for untitled threads
+    pub fn display_title(&self) -> String {
+        if let Some(title) = self.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            return title.to_string();
+        }
+
+        let first_text = self.messages.iter().find_map(|m| match m {
+            Message::User(user) => user.content.iter().find_map(|block| match block {
+                ContentBlock::Text { text } if !text.trim().is_empty() => Some(text.as_str()),
+                _ => None,
+            }),
+            Message::Assistant(_) => None,
+        });
+
+        match first_text {
+            Some(text) => {
+                let line = text.trim().lines().next().unwrap_or_default();
+                let mut title: String = line.chars().take(80).collect();
+                if line.chars().count() > 80 {
+                    title.push('…');
+                }
+                title
+            },
+            None => "Untitled".to_string(),
+        }
+    }
+}
+
+/// `file:///a%20b` -> `/a b`; other URIs are returned unchanged
+pub fn uri_to_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREAD: &str = r#"{
+        "v": 3,
+        "id": "T-0b7e1f9c-2a3d-4e5f-8a9b-0c1d2e3f4a5b",
+        "created": 1730000000000,
+        "nextMessageId": 2,
+        "agentMode": "fast",
+        "messages": [
+            {
+                "role": "user",
+                "messageId": 0,
+                "content": [
+                    {"type": "text", "text": "Fix the parser\nplease"},
+                    {"type": "file", "fileUri": "file:///w/src/lib.rs", "text": "fn main() {}"}
+                ],
+                "meta": {"sentAt": 1730000000500}
+            },
+            {
+                "role": "assistant",
+                "messageId": 1,
+                "content": [
+                    {"type": "thinking", "thinking": "hmm"},
+                    {"type": "tool_use", "id": "tu_1", "name": "Read", "input": {"path": "/w/src/lib.rs"}},
+                    {"type": "redacted_thinking", "data": "..."}
+                ],
+                "state": {"type": "complete", "stopReason": "tool_use"},
+                "usage": {"model": "claude", "inputTokens": 10, "outputTokens": 5, "credits": 0.5}
+            }
+        ],
+        "env": {"initial": {"trees": [{"uri": "file:///home/me/my%20project"}]}},
+        "usageLedger": {"events": [{"model": "claude", "credits": 0.5, "tokens": {"input": 10, "output": 5}}]}
+    }"#;
+
+    #[test]
+    fn test_parse_thread() {
+        let thread: Thread = serde_json::from_str(THREAD).unwrap();
+
+        assert_eq!(thread.agent_mode, AgentMode::Fast);
+        assert_eq!(thread.messages.len(), 2);
+        assert_eq!(thread.messages[1].message_id(), Some(1));
+        assert!(matches!(
+            thread.messages[1].content()[1],
+            ContentBlock::ToolUse { ref name, .. } if name == "Read"
+        ));
+        assert!(matches!(
+            thread.messages[1].content()[2],
+            ContentBlock::Unknown
+        ));
+        assert_eq!(
+            thread.usage_ledger.unwrap().events[0]
+                .tokens
+                .as_ref()
+                .unwrap()
+                .input,
+            10
+        );
+    }
+
+    #[test]
+    fn test_derived_fields() {
+        let thread: Thread = serde_json::from_str(THREAD).unwrap();
+
+        assert_eq!(thread.display_title(), "Fix the parser");
+        assert_eq!(thread.workspace().as_deref(), Some("/home/me/my project"));
+        assert_eq!(thread.workspace_name().as_deref(), Some("my project"));
+    }
+
+    #[test]
+    fn test_unknown_agent_mode() {
+        let mode: AgentMode = serde_json::from_str("\"turbo\"").unwrap();
+        assert_eq!(mode, AgentMode::Other);
+    }
+}
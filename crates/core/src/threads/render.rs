@@ -0,0 +1,406 @@
+//! Markdown rendering of threads
+//!
+//! [`render`] turns a thread into Markdown lines for a read-only buffer.
+//! Thinking, tool calls, tool results and attached files are wrapped in
+//! `<details>` sections, whose line ranges are also returned as folds. The
+//! line spans of each message and the files a thread references let the Lua
+//! side jump between messages and open files.
+
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::model::{ContentBlock, Message, Thread};
+
+/// Keys of tool inputs that name a file
+pub(crate) const PATH_KEYS: &[&str] = &["path", "file_path", "filePath"];
+
+/// Keys of tool inputs shown in a tool call's summary line, by priority
+const SUMMARY_KEYS: &[&str] = &[
+    "path",
+    "file_path",
+    "filePath",
+    "cmd",
+    "command",
+    "query",
+    "pattern",
+    "url",
+];
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Include thinking blocks
+    pub thinking: bool,
+    /// Include tool results
+    pub tool_results: bool,
+    /// Truncate tool results longer than this many lines
+    pub max_tool_lines: Option<usize>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            thinking: true,
+            tool_results: true,
+            max_tool_lines: Some(50),
+        }
+    }
+}
+
+/// Lines of one message (1-based, inclusive)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageSpan {
+    pub message_id: u64,
+    pub role: &'static str,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A collapsible section (1-based, inclusive)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Fold {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A file referenced by a message, at the line where it appears
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileRef {
+    pub line: usize,
+    pub message_id: u64,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RenderedThread {
+    pub lines: Vec<String>,
+    pub messages: Vec<MessageSpan>,
+    pub folds: Vec<Fold>,
+    pub files: Vec<FileRef>,
+}
+
+impl RenderedThread {
+    /// Message shown at `line` (1-based)
+    pub fn message_at(&self, line: usize) -> Option<u64> {
+        self.messages
+            .iter()
+            .find(|m| (m.start_line..=m.end_line).contains(&line))
+            .map(|m| m.message_id)
+    }
+}
+
+/// Render `thread` as Markdown
+pub fn render(thread: &Thread, options: &RenderOptions) -> RenderedThread {
+    let mut out = Renderer::default();
+
+    out.line(format!("# {}", thread.display_title()));
+    out.blank();
+    out.line(format!("- **Thread:** {}", thread.id));
+    out.line(format!("- **Created:** {}", format_time(thread.created)));
+    out.line(format!("- **Agent mode:** {}", thread.agent_mode.as_str()));
+    if let Some(workspace) = thread.workspace() {
+        out.line(format!("- **Workspace:** {}", workspace));
+    }
+
+    for (position, message) in thread.messages.iter().enumerate() {
+        let message_id = message.message_id().unwrap_or(position as u64);
+        out.blank();
+        let start_line = out.next_line();
+        out.message(message, message_id, options);
+        while out.lines.last().is_some_and(|l| l.is_empty()) {
+            out.lines.pop();
+        }
+        out.rendered.messages.push(MessageSpan {
+            message_id,
+            role: match message {
+                Message::User(_) => "user",
+                Message::Assistant(_) => "assistant",
+            },
+            start_line,
+            end_line: out.lines.len(),
+        });
+    }
+
+    let mut rendered = out.rendered;
+    rendered.lines = out.lines;
+    rendered
+}
+
+#[derive(Default)]
+struct Renderer {
+    lines: Vec<String>,
+    rendered: RenderedThread,
+}
+
+impl Renderer {
+    fn next_line(&self) -> usize {
+        self.lines.len() + 1
+    }
+
+    fn line(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    fn blank(&mut self) {
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.lines.extend(text.trim_end().lines().map(String::from));
+    }
+
+    fn message(&mut self, message: &Message, message_id: u64, options: &RenderOptions) {
+        let header = match message {
+            Message::User(user) => {
+                let sent = user
+                    .meta
+                    .as_ref()
+                    .and_then(|m| m.sent_at)
+                    .map(|at| format!(" · {}", format_time(at)))
+                    .unwrap_or_default();
+                format!("## User · #{}{}", message_id, sent)
+            },
+            Message::Assistant(assistant) => {
+                let model = assistant
+                    .usage
+                    .as_ref()
+                    .and_then(|u| u.model.as_deref())
+                    .map(|m| format!(" · {}", m))
+                    .unwrap_or_default();
+                format!("## Assistant · #{}{}", message_id, model)
+            },
+        };
+        self.line(header);
+
+        for block in message.content() {
+            self.blank();
+            self.block(block, message_id, options);
+        }
+    }
+
+    fn block(&mut self, block: &ContentBlock, message_id: u64, options: &RenderOptions) {
+        match block {
+            ContentBlock::Text { text } => self.text(text),
+            ContentBlock::File { file_uri, text } => {
+                let path = super::model::uri_to_path(file_uri);
+                self.file_ref(&path, message_id);
+                self.details(&format!("File: `{}`", path), |r| {
+                    r.fenced(language(&path), text);
+                });
+            },
+            ContentBlock::Thinking { thinking, .. } if options.thinking => {
+                self.details("Thinking", |r| r.text(thinking));
+            },
+            ContentBlock::Thinking { .. } => {},
+            ContentBlock::ToolUse { id, name, input } => {
+                if let Some(path) = PATH_KEYS
+                    .iter()
+                    .find_map(|k| input.get(*k).and_then(|v| v.as_str()))
+                {
+                    self.file_ref(path, message_id);
+                }
+                let summary = match tool_summary(input) {
+                    Some(arg) => format!("Tool: {} `{}` ({})", name, arg, id),
+                    None => format!("Tool: {} ({})", name, id),
+                };
+                let input = serde_json::to_string_pretty(input).unwrap_or_default();
+                self.details(&summary, |r| r.fenced("json", &input));
+            },
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } if options.tool_results => {
+                let label = if is_error.unwrap_or(false) {
+                    "Error"
+                } else {
+                    "Result"
+                };
+                let summary = match tool_use_id {
+                    Some(id) => format!("{} ({})", label, id),
+                    None => label.to_string(),
+                };
+                let output = truncate_lines(&tool_output(content.as_ref()), options.max_tool_lines);
+                self.details(&summary, |r| r.fenced("", &output));
+            },
+            ContentBlock::ToolResult { .. } => {},
+            ContentBlock::Unknown => self.line("_(unsupported content)_"),
+        }
+    }
+
+    /// A `<details>` section, recorded as a fold
+    fn details(&mut self, summary: &str, body: impl FnOnce(&mut Self)) {
+        let start_line = self.next_line();
+        self.line(format!("<details><summary>{}</summary>", summary));
+        self.line("");
+        body(self);
+        self.line("");
+        self.line("</details>");
+        self.rendered.folds.push(Fold {
+            start_line,
+            end_line: self.lines.len(),
+        });
+    }
+
+    /// A fenced code block, with a fence longer than any backtick run inside
+    fn fenced(&mut self, info: &str, code: &str) {
+        let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        self.line(format!("{}{}", fence, info));
+        self.text(code);
+        self.line(fence);
+    }
+
+    fn file_ref(&mut self, path: &str, message_id: u64) {
+        self.rendered.files.push(FileRef {
+            line: self.next_line(),
+            message_id,
+            path: path.to_string(),
+        });
+    }
+}
+
+/// Short description of a tool call's main argument
+fn tool_summary(input: &Value) -> Option<String> {
+    let arg = SUMMARY_KEYS
+        .iter()
+        .find_map(|k| input.get(*k).and_then(|v| v.as_str()))?;
+    let first_line = arg.lines().next().unwrap_or_default();
+    let mut summary: String = first_line.chars().take(60).collect();
+    if summary.len() < arg.len() {
+        summary.push('…');
+    }
+    Some(summary.replace('`', "'"))
+}
+
+/// Tool result content as text
+pub(crate) fn tool_output(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => serde_json::to_string_pretty(other).unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+fn truncate_lines(text: &str, max: Option<usize>) -> String {
+    let Some(max) = max else {
+        return text.to_string();
+    };
+    let total = text.lines().count();
+    if total <= max {
+        return text.to_string();
+    }
+
+    let mut kept: Vec<&str> = text.lines().take(max).collect();
+    let note = format!("… ({} more lines)", total - max);
+    kept.push(&note);
+    kept.join("\n")
+}
+
+/// Fence info string for a file name
+fn language(path: &str) -> &str {
+    let ext = path.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "md" => "markdown",
+        "sh" | "bash" => "bash",
+        "yml" => "yaml",
+        other if other.contains('/') => "",
+        other => other,
+    }
+}
+
+/// `2024-11-05 14:03 UTC` for a Unix timestamp in milliseconds
+pub(crate) fn format_time(millis: i64) -> String {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn thread() -> Thread {
+        serde_json::from_value(json!({
+            "v": 1,
+            "id": "T-00000001-0000-4000-8000-000000000000",
+            "created": 1_730_000_000_000i64,
+            "nextMessageId": 2,
+            "agentMode": "smart",
+            "title": "Fix the parser",
+            "messages": [
+                {"role": "user", "messageId": 0, "content": [
+                    {"type": "text", "text": "Why does this fail?"},
+                    {"type": "file", "fileUri": "file:///w/src/lib.rs", "text": "let s = \"```\";"}
+                ]},
+                {"role": "assistant", "messageId": 1, "content": [
+                    {"type": "thinking", "thinking": "Look at the file."},
+                    {"type": "tool_use", "id": "t1", "name": "Read", "input": {"path": "/w/src/main.rs"}},
+                ]},
+                {"role": "user", "messageId": 2, "content": [
+                    {"type": "tool_result", "toolUseId": "t1", "content": "1\n2\n3\n4", "isError": true}
+                ]}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_sections_and_map() {
+        let rendered = render(&thread(), &RenderOptions::default());
+        let text = rendered.lines.join("\n");
+
+        assert!(text.starts_with("# Fix the parser\n"));
+        assert!(text.contains("## User · #0"));
+        assert!(text.contains("<details><summary>File: `/w/src/lib.rs`</summary>"));
+        // The fence outgrows the backticks in the file
+        assert!(text.contains("````rust\nlet s = \"```\";\n````"));
+        assert!(text.contains("<details><summary>Tool: Read `/w/src/main.rs` (t1)</summary>"));
+        assert!(text.contains("<details><summary>Error (t1)</summary>"));
+
+        assert_eq!(rendered.messages.len(), 3);
+        assert_eq!(rendered.folds.len(), 4);
+        for span in &rendered.messages {
+            assert!(rendered.lines[span.start_line - 1].starts_with("## "));
+            assert_eq!(rendered.message_at(span.end_line), Some(span.message_id));
+        }
+        assert_eq!(rendered.message_at(1), None);
+        let fold = &rendered.folds[0];
+        assert!(rendered.lines[fold.start_line - 1].starts_with("<details>"));
+        assert_eq!(rendered.lines[fold.end_line - 1], "</details>");
+
+        let files: Vec<_> = rendered.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(files, vec!["/w/src/lib.rs", "/w/src/main.rs"]);
+        assert_eq!(rendered.message_at(rendered.files[1].line), Some(1));
+    }
+
+    #[test]
+    fn test_render_options() {
+        let options = RenderOptions {
+            thinking: false,
+            tool_results: true,
+            max_tool_lines: Some(2),
+        };
+        let text = render(&thread(), &options).lines.join("\n");
+
+        assert!(!text.contains("Thinking"));
+        assert!(text.contains("1\n2\n… (2 more lines)"));
+
+        let no_results = RenderOptions {
+            tool_results: false,
+            ..Default::default()
+        };
+        assert!(!render(&thread(), &no_results)
+            .lines
+            .join("\n")
+            .contains("Error (t1)"));
+    }
+}
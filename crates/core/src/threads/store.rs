@@ -0,0 +1,354 @@
+//! Reading thread files
+//!
+//! [`list_threads`] summarizes every file of the thread directory. Parsed
+//! summaries are cached by path and reused while a file's modification time
+//! and size are unchanged, so listing a large directory again is cheap.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::model::{AgentMode, Thread};
+use crate::errors::{AmpError, Result};
+
+/// What `threads.list` shows for a thread
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadSummary {
+    pub id: String,
+    pub title: String,
+    /// Unix timestamp (milliseconds)
+    pub created: i64,
+    /// Last modification of the file, Unix timestamp (milliseconds)
+    pub updated: i64,
+    pub message_count: usize,
+    /// First workspace root (a path for local workspaces)
+    pub workspace: Option<String>,
+    pub workspace_name: Option<String>,
+    pub agent_mode: AgentMode,
+    pub path: PathBuf,
+}
+
+impl ThreadSummary {
+    pub fn new(thread: &Thread, path: &Path, updated: i64) -> Self {
+        Self {
+            id: thread.id.clone(),
+            title: thread.display_title(),
+            created: thread.created,
+            updated,
+            message_count: thread.messages.len(),
+            workspace: thread.workspace(),
+            workspace_name: thread.workspace_name(),
+            agent_mode: thread.agent_mode,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Order of [`list_threads`] results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThreadSort {
+    /// Newest first
+    #[default]
+    Created,
+    /// Most recently modified first
+    Updated,
+    /// By title, case-insensitively
+    Title,
+    /// Longest first
+    Messages,
+}
+
+impl ThreadSort {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "created" => Ok(ThreadSort::Created),
+            "updated" => Ok(ThreadSort::Updated),
+            "title" => Ok(ThreadSort::Title),
+            "messages" => Ok(ThreadSort::Messages),
+            other => Err(AmpError::ValidationError(format!(
+                "Unknown sort '{}' (expected created, updated, title or messages)",
+                other
+            ))),
+        }
+    }
+
+    /// Ties are broken newest first, then by id, so pages are stable
+    pub fn compare(self, a: &ThreadSummary, b: &ThreadSummary) -> Ordering {
+        let newest = |t: &ThreadSummary| (Reverse(t.created), t.id.clone());
+        match self {
+            ThreadSort::Created => newest(a).cmp(&newest(b)),
+            ThreadSort::Updated => Reverse(a.updated).cmp(&Reverse(b.updated)),
+            ThreadSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            ThreadSort::Messages => Reverse(a.message_count).cmp(&Reverse(b.message_count)),
+        }
+        .then_with(|| newest(a).cmp(&newest(b)))
+    }
+}
+
+/// Sort and page of [`list_threads`]
+#[derive(Debug, Clone, Default)]
+pub struct ThreadQuery {
+    pub sort: ThreadSort,
+    /// Number of threads to skip
+    pub offset: usize,
+    /// Page size (`None` for all threads)
+    pub limit: Option<usize>,
+}
+
+/// One page of threads
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThreadPage {
+    pub threads: Vec<ThreadSummary>,
+    /// Number of threads before paging
+    pub total: usize,
+    /// Files that could not be parsed, as `path: reason`
+    pub errors: Vec<String>,
+}
+
+struct CachedSummary {
+    /// Modification time and size when parsed
+    stamp: (SystemTime, u64),
+    summary: ThreadSummary,
+}
+
+static CACHE: Lazy<Mutex<HashMap<PathBuf, CachedSummary>>> = Lazy::new(Default::default);
+
+/// Path of the file of thread `id` in `dir`
+pub fn thread_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+/// Parse a thread file
+pub fn load_thread(path: &Path) -> Result<Thread> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text)
+        .map_err(|e| AmpError::ThreadParseError(format!("{}: {}", path.display(), e)))
+}
+
+/// Check that `id` has the `T-<uuid>` form of thread ids (lowercase hex),
+/// which also keeps it safe to use as a file name
+pub fn validate_thread_id(id: &str) -> Result<()> {
+    let valid = id.strip_prefix("T-").is_some_and(|uuid| {
+        uuid.len() == 36
+            && uuid.char_indices().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => matches!(c, '0'..='9' | 'a'..='f'),
+            })
+    });
+
+    if !valid {
+        return Err(AmpError::ValidationError(format!(
+            "Invalid thread id '{}' (expected T-<uuid>)",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// Load thread `id` from `dir`
+pub fn get_thread(dir: &Path, id: &str) -> Result<Thread> {
+    validate_thread_id(id)?;
+    let path = thread_path(dir, id);
+    if !path.is_file() {
+        return Err(AmpError::ValidationError(format!(
+            "Thread '{}' not found",
+            id
+        )));
+    }
+    load_thread(&path)
+}
+
+/// Thread files of `dir`, sorted by path (none if the directory is missing)
+pub fn thread_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Summaries of every thread in `dir`, in no particular order, and the
+/// files that failed to parse
+pub fn summaries(dir: &Path) -> Result<(Vec<ThreadSummary>, Vec<String>)> {
+    let paths = thread_files(dir)?;
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|path, _| !path.starts_with(dir) || paths.contains(path));
+
+    let mut threads = Vec::with_capacity(paths.len());
+    let mut errors = Vec::new();
+
+    for path in paths {
+        // Files can disappear while Amp rewrites them
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let modified = metadata.modified()?;
+        let stamp = (modified, metadata.len());
+        if let Some(cached) = cache.get(&path).filter(|c| c.stamp == stamp) {
+            threads.push(cached.summary.clone());
+            continue;
+        }
+
+        match load_thread(&path) {
+            Ok(thread) => {
+                let summary = ThreadSummary::new(&thread, &path, millis(modified));
+                threads.push(summary.clone());
+                cache.insert(path, CachedSummary { stamp, summary });
+            },
+            Err(e) => {
+                cache.remove(&path);
+                errors.push(e.to_string());
+            },
+        }
+    }
+
+    Ok((threads, errors))
+}
+
+/// List the threads of `dir`, sorted and paged by `query`
+pub fn list_threads(dir: &Path, query: &ThreadQuery) -> Result<ThreadPage> {
+    let (mut threads, errors) = summaries(dir)?;
+    threads.sort_by(|a, b| query.sort.compare(a, b));
+
+    let total = threads.len();
+    let threads = threads
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(ThreadPage {
+        threads,
+        total,
+        errors,
+    })
+}
+
+/// Unix timestamp in milliseconds
+pub fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Write a minimal thread file with `messages` user text messages
+    pub(crate) fn write_thread(dir: &Path, n: u32, title: &str, messages: usize) -> String {
+        let id = format!("T-{:08x}-0000-4000-8000-000000000000", n);
+        let messages: Vec<_> = (0..messages)
+            .map(|i| json!({"role": "user", "messageId": i, "content": [{"type": "text", "text": format!("message {}", i)}]}))
+            .collect();
+        let thread = json!({
+            "v": 1,
+            "id": id,
+            "created": 1_700_000_000_000i64 + n as i64,
+            "nextMessageId": messages.len(),
+            "agentMode": "smart",
+            "title": title,
+            "messages": messages,
+            "env": {"initial": {"trees": [{"displayName": "repo", "uri": "file:///src/repo"}]}},
+        });
+        fs::write(thread_path(dir, &id), thread.to_string()).unwrap();
+        id
+    }
+
+    fn titles(page: &ThreadPage) -> Vec<&str> {
+        page.threads.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_list_sorted_and_paged() {
+        let dir = tempfile::tempdir().unwrap();
+        write_thread(dir.path(), 1, "beta", 3);
+        write_thread(dir.path(), 2, "Alpha", 1);
+        write_thread(dir.path(), 3, "gamma", 2);
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+        fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let page = list_threads(dir.path(), &ThreadQuery::default()).unwrap();
+        assert_eq!(titles(&page), vec!["gamma", "Alpha", "beta"]);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.errors.len(), 1);
+        assert!(page.errors[0].contains("broken.json"));
+
+        let summary = &page.threads[0];
+        assert_eq!(summary.message_count, 2);
+        assert_eq!(summary.workspace.as_deref(), Some("/src/repo"));
+        assert_eq!(summary.workspace_name.as_deref(), Some("repo"));
+
+        let by_title = ThreadQuery {
+            sort: ThreadSort::Title,
+            offset: 1,
+            limit: Some(1),
+        };
+        let page = list_threads(dir.path(), &by_title).unwrap();
+        assert_eq!(titles(&page), vec!["beta"]);
+        assert_eq!(page.total, 3);
+
+        let by_messages = ThreadQuery {
+            sort: ThreadSort::Messages,
+            ..Default::default()
+        };
+        let page = list_threads(dir.path(), &by_messages).unwrap();
+        assert_eq!(titles(&page), vec!["beta", "gamma", "Alpha"]);
+    }
+
+    #[test]
+    fn test_cache_sees_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_thread(dir.path(), 1, "before", 1);
+        assert_eq!(
+            titles(&list_threads(dir.path(), &ThreadQuery::default()).unwrap()),
+            vec!["before"]
+        );
+
+        // Same file, different size
+        write_thread(dir.path(), 1, "after edit", 1);
+        let id = write_thread(dir.path(), 2, "new", 1);
+        let page = list_threads(dir.path(), &ThreadQuery::default()).unwrap();
+        assert_eq!(titles(&page), vec!["new", "after edit"]);
+
+        fs::remove_file(thread_path(dir.path(), &id)).unwrap();
+        let page = list_threads(dir.path(), &ThreadQuery::default()).unwrap();
+        assert_eq!(titles(&page), vec!["after edit"]);
+    }
+
+    #[test]
+    fn test_validate_thread_id() {
+        assert!(validate_thread_id("T-0b7e1f9c-2a3d-4e5f-8a9b-0c1d2e3f4a5b").is_ok());
+        assert!(validate_thread_id("T-0B7E1F9C-2A3D-4E5F-8A9B-0C1D2E3F4A5B").is_err());
+        assert!(validate_thread_id("0b7e1f9c-2a3d-4e5f-8a9b-0c1d2e3f4a5b").is_err());
+        assert!(validate_thread_id("T-../../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_missing_dir_and_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("nope");
+
+        let page = list_threads(&missing, &ThreadQuery::default()).unwrap();
+        assert_eq!(page.total, 0);
+
+        let err = get_thread(dir.path(), "T-missing").unwrap_err();
+        assert_eq!(err.category(), "validation");
+        let err = get_thread(dir.path(), "T-00000009-0000-4000-8000-000000000000").unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert!(ThreadSort::parse("size").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+//! Token and credit usage of threads
+//!
+//! Usage comes from a thread's `usageLedger` (`schemas/usage.json`); threads
+//! written before Amp kept a ledger fall back to the usage recorded on each
+//! assistant message, dated by the user message it answers. Records are
+//! summed into totals by day (UTC), workspace, agent mode and thread.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+
+use super::model::{AgentMode, Message, Thread};
+use super::store;
+use crate::errors::Result;
+
+/// Workspace key of threads without one
+const NO_WORKSPACE: &str = "(none)";
+
+/// Date range of a summary, Unix timestamps (milliseconds, `to` exclusive)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl UsageRange {
+    /// Range from `from` to `to` inclusive, both `YYYY-MM-DD` (UTC)
+    pub fn days(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        let millis = |date: NaiveDate| {
+            Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+                .timestamp_millis()
+        };
+        Self {
+            from: from.map(millis),
+            to: to.and_then(|d| d.succ_opt()).map(millis),
+        }
+    }
+
+    fn contains(&self, at: i64) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at < to)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub credits: f64,
+    /// Ledger events (or assistant messages) counted
+    pub events: usize,
+}
+
+impl UsageTotals {
+    pub(crate) fn add(&mut self, record: &UsageRecord) {
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        self.credits += record.credits;
+        self.events += 1;
+    }
+}
+
+/// Totals of one day, workspace, agent mode or thread
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageBucket {
+    pub key: String,
+    /// Thread title, for the per-thread buckets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageSummary {
+    pub total: UsageTotals,
+    /// Oldest day first
+    pub by_day: Vec<UsageBucket>,
+    /// Highest credits first (then most tokens)
+    pub by_workspace: Vec<UsageBucket>,
+    pub by_agent_mode: Vec<UsageBucket>,
+    pub by_thread: Vec<UsageBucket>,
+    /// Thread files that could not be parsed
+    pub errors: Vec<String>,
+}
+
+/// One ledger event or assistant message
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct UsageRecord {
+    /// Unix timestamp (milliseconds)
+    pub at: i64,
+    /// Last message the usage was for, when known
+    pub message_id: Option<u64>,
+    pub agent_mode: AgentMode,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub credits: f64,
+}
+
+/// Usage records of `thread`
+pub(crate) fn records(thread: &Thread) -> Vec<UsageRecord> {
+    // Agent mode and send time of each user message, in message order
+    let turns: Vec<(u64, AgentMode, Option<i64>)> = thread
+        .messages
+        .iter()
+        .filter_map(|m| match m {
+            Message::User(user) => Some((
+                user.message_id,
+                user.agent_mode.unwrap_or(thread.agent_mode),
+                user.meta.as_ref().and_then(|meta| meta.sent_at),
+            )),
+            Message::Assistant(_) => None,
+        })
+        .collect();
+    let mode_at = |message_id: Option<u64>| {
+        message_id
+            .and_then(|id| turns.iter().rev().find(|(user_id, ..)| *user_id <= id))
+            .map_or(thread.agent_mode, |(_, mode, _)| *mode)
+    };
+
+    let events = thread
+        .usage_ledger
+        .as_ref()
+        .map(|l| l.events.as_slice())
+        .unwrap_or_default();
+    if !events.is_empty() {
+        return events
+            .iter()
+            .map(|event| {
+                let tokens = event.tokens.clone().unwrap_or_default();
+                UsageRecord {
+                    at: event
+                        .timestamp
+                        .as_deref()
+                        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                        .map_or(thread.created, |t| t.timestamp_millis()),
+                    message_id: event.to_message_id.or(event.from_message_id),
+                    agent_mode: mode_at(event.from_message_id.or(event.to_message_id)),
+                    input_tokens: tokens.input,
+                    output_tokens: tokens.output,
+                    credits: event.credits.unwrap_or(0.0),
+                }
+            })
+            .collect();
+    }
+
+    let mut records = Vec::new();
+    let mut current = (thread.agent_mode, thread.created);
+    for message in &thread.messages {
+        match message {
+            Message::User(user) => {
+                current = (
+                    user.agent_mode.unwrap_or(thread.agent_mode),
+                    user.meta
+                        .as_ref()
+                        .and_then(|m| m.sent_at)
+                        .unwrap_or(current.1),
+                );
+            },
+            Message::Assistant(assistant) => {
+                let Some(usage) = &assistant.usage else {
+                    continue;
+                };
+                records.push(UsageRecord {
+                    at: current.1,
+                    message_id: assistant.message_id,
+                    agent_mode: current.0,
+                    input_tokens: usage.total_input_tokens.or(usage.input_tokens).unwrap_or(0),
+                    output_tokens: usage.output_tokens.unwrap_or(0),
+                    credits: usage.credits.unwrap_or(0.0),
+                });
+            },
+        }
+    }
+    records
+}
+
+#[derive(Default)]
+struct Buckets {
+    totals: BTreeMap<String, UsageTotals>,
+    titles: BTreeMap<String, String>,
+}
+
+impl Buckets {
+    fn add(&mut self, key: &str, record: &UsageRecord) {
+        self.totals.entry(key.to_string()).or_default().add(record);
+    }
+
+    fn into_vec(self, by_key: bool) -> Vec<UsageBucket> {
+        let mut titles = self.titles;
+        let mut buckets: Vec<UsageBucket> = self
+            .totals
+            .into_iter()
+            .map(|(key, totals)| UsageBucket {
+                title: titles.remove(&key),
+                key,
+                totals,
+            })
+            .collect();
+        if !by_key {
+            buckets.sort_by(|a, b| {
+                b.totals
+                    .credits
+                    .total_cmp(&a.totals.credits)
+                    .then_with(|| {
+                        let tokens = |t: &UsageTotals| t.input_tokens + t.output_tokens;
+                        tokens(&b.totals).cmp(&tokens(&a.totals))
+                    })
+                    .then_with(|| a.key.cmp(&b.key))
+            });
+        }
+        buckets
+    }
+}
+
+/// Sum the usage of the threads in `dirs` within `range`
+pub fn summarize(dirs: &[&Path], range: UsageRange) -> Result<UsageSummary> {
+    let mut summary = UsageSummary::default();
+    let mut by_day = Buckets::default();
+    let mut by_workspace = Buckets::default();
+    let mut by_agent_mode = Buckets::default();
+    let mut by_thread = Buckets::default();
+
+    for dir in dirs {
+        for path in store::thread_files(dir)? {
+            // Nothing in a file can be newer than the file
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(store::millis);
+            if matches!((modified, range.from), (Ok(m), Some(from)) if m < from) {
+                continue;
+            }
+
+            let thread = match store::load_thread(&path) {
+                Ok(thread) => thread,
+                Err(e) => {
+                    summary.errors.push(e.to_string());
+                    continue;
+                },
+            };
+            let workspace = thread
+                .workspace()
+                .unwrap_or_else(|| NO_WORKSPACE.to_string());
+
+            for record in records(&thread).iter().filter(|r| range.contains(r.at)) {
+                let day = Utc
+                    .timestamp_millis_opt(record.at)
+                    .single()
+                    .map(|t| t.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default();
+
+                summary.total.add(record);
+                by_day.add(&day, record);
+                by_workspace.add(&workspace, record);
+                by_agent_mode.add(record.agent_mode.as_str(), record);
+                by_thread.add(&thread.id, record);
+                by_thread
+                    .titles
+                    .entry(thread.id.clone())
+                    .or_insert_with(|| thread.display_title());
+            }
+        }
+    }
+
+    summary.by_day = by_day.into_vec(true);
+    summary.by_workspace = by_workspace.into_vec(false);
+    summary.by_agent_mode = by_agent_mode.into_vec(false);
+    summary.by_thread = by_thread.into_vec(false);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(dir: &Path, thread: serde_json::Value) {
+        let id = thread["id"].as_str().unwrap().to_string();
+        fs::write(store::thread_path(dir, &id), thread.to_string()).unwrap();
+    }
+
+    fn ledger_thread() -> serde_json::Value {
+        json!({
+            "v": 1,
+            "id": "T-00000001-0000-4000-8000-000000000000",
+            "created": 1_730_000_000_000i64,
+            "agentMode": "smart",
+            "title": "Ledger",
+            "env": {"initial": {"trees": [{"displayName": "repo", "uri": "file:///src/repo"}]}},
+            "messages": [
+                {"role": "user", "messageId": 0, "content": []},
+                {"role": "user", "messageId": 2, "agentMode": "fast", "content": []}
+            ],
+            "usageLedger": {"events": [
+                {"timestamp": "2024-10-27T10:00:00Z", "credits": 1.5,
+                 "tokens": {"input": 100, "output": 10}, "fromMessageId": 0, "toMessageId": 1},
+                {"timestamp": "2024-10-28T10:00:00Z", "credits": 0.5,
+                 "tokens": {"input": 50, "output": 5}, "fromMessageId": 2, "toMessageId": 3}
+            ]}
+        })
+    }
+
+    fn message_thread() -> serde_json::Value {
+        json!({
+            "v": 1,
+            "id": "T-00000002-0000-4000-8000-000000000000",
+            "created": 1_730_000_000_000i64,
+            "agentMode": "smart",
+            "messages": [
+                {"role": "user", "messageId": 0, "content": [{"type": "text", "text": "Hi"}],
+                 "meta": {"sentAt": 1_730_030_000_000i64}},
+                {"role": "assistant", "messageId": 1, "content": [],
+                 "usage": {"inputTokens": 20, "totalInputTokens": 30, "outputTokens": 3, "credits": 0.25}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_summary_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), ledger_thread());
+        write(dir.path(), message_thread());
+        fs::write(dir.path().join("T-broken.json"), "{").unwrap();
+
+        let summary = summarize(&[dir.path()], UsageRange::default()).unwrap();
+        assert_eq!(summary.total.input_tokens, 180);
+        assert_eq!(summary.total.output_tokens, 18);
+        assert_eq!(summary.total.credits, 2.25);
+        assert_eq!(summary.total.events, 3);
+        assert_eq!(summary.errors.len(), 1);
+
+        let days: Vec<_> = summary.by_day.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(days, vec!["2024-10-27", "2024-10-28"]);
+        assert_eq!(summary.by_day[0].totals.events, 2);
+
+        let modes: Vec<_> = summary
+            .by_agent_mode
+            .iter()
+            .map(|b| (b.key.as_str(), b.totals.credits))
+            .collect();
+        assert_eq!(modes, vec![("smart", 1.75), ("fast", 0.5)]);
+
+        assert_eq!(summary.by_workspace[0].key, "/src/repo");
+        assert_eq!(summary.by_workspace[1].key, NO_WORKSPACE);
+        assert_eq!(summary.by_thread[0].title.as_deref(), Some("Ledger"));
+        assert_eq!(summary.by_thread[1].title.as_deref(), Some("Hi"));
+    }
+
+    #[test]
+    fn test_summary_range() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), ledger_thread());
+        write(dir.path(), message_thread());
+
+        let day = NaiveDate::from_ymd_opt(2024, 10, 28);
+        let summary = summarize(&[dir.path()], UsageRange::days(day, day)).unwrap();
+        assert_eq!(summary.total.events, 1);
+        assert_eq!(summary.total.credits, 0.5);
+        assert_eq!(summary.by_agent_mode[0].key, "fast");
+    }
+}
@@ -0,0 +1,283 @@
+//! Live watcher on the thread directory
+//!
+//! File system notifications are debounced on the global runtime (Amp
+//! rewrites a thread file several times per message) and turned into
+//! [`ThreadEvent`]s, which queue up until taken with [`take_events`]. The
+//! `notify` callback given to [`watch`] runs after each batch; the FFI layer
+//! uses it to wake Neovim, which then fires the `User AmpThreadsChanged`
+//! autocommand with the events.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::store;
+use crate::errors::Result;
+use crate::runtime;
+
+/// How long to wait for a burst of notifications to settle
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Events kept when nobody takes them (oldest are dropped first)
+const MAX_PENDING: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadEventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A change to one thread file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadEvent {
+    pub kind: ThreadEventKind,
+    /// File stem, the thread id for files written by Amp
+    pub thread_id: String,
+    pub path: PathBuf,
+    /// Unix timestamp (milliseconds)
+    pub at: i64,
+}
+
+/// A running watcher; dropping it stops watching
+pub struct ThreadWatcher {
+    dir: PathBuf,
+    pending: Arc<Mutex<Vec<ThreadEvent>>>,
+    // Dropping the notify watcher closes the channel, which ends the task
+    _watcher: RecommendedWatcher,
+    _task: JoinHandle<()>,
+}
+
+impl ThreadWatcher {
+    /// Watch `dir`, calling `notify` after each batch of events
+    pub fn start<F>(dir: &Path, notify: F) -> Result<Self>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        // Notifications carry resolved paths (e.g. /private/var on macOS)
+        let dir = dir.canonicalize()?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to create thread watcher: {}", e))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| anyhow::anyhow!("Failed to watch {}: {}", dir.display(), e))?;
+
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let known: HashSet<PathBuf> = store::thread_files(&dir)?.into_iter().collect();
+        let task = runtime::spawn(run(rx, known, pending.clone(), notify));
+
+        Ok(Self {
+            dir,
+            pending,
+            _watcher: watcher,
+            _task: task,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Take the events queued since the last call
+    pub fn take_events(&self) -> Vec<ThreadEvent> {
+        std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// The watcher started by [`watch`]
+static WATCHER: Lazy<Mutex<Option<ThreadWatcher>>> = Lazy::new(Default::default);
+
+/// Start (or restart) the global watcher on `dir`
+///
+/// A missing directory is not an error: there is nothing to watch until Amp
+/// creates it, and `false` is returned.
+pub fn watch<F>(dir: &Path, notify: F) -> Result<bool>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let mut current = WATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    *current = None;
+
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+    *current = Some(ThreadWatcher::start(dir, notify)?);
+    Ok(true)
+}
+
+/// Stop the global watcher
+pub fn stop() {
+    *WATCHER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Directory watched by the global watcher, if running
+pub fn watched_dir() -> Option<PathBuf> {
+    let current = WATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    current.as_ref().map(|w| w.dir().to_path_buf())
+}
+
+/// Take the events queued by the global watcher
+pub fn take_events() -> Vec<ThreadEvent> {
+    let current = WATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    current
+        .as_ref()
+        .map(|w| w.take_events())
+        .unwrap_or_default()
+}
+
+async fn run<F>(
+    mut rx: UnboundedReceiver<notify::Result<Event>>,
+    mut known: HashSet<PathBuf>,
+    pending: Arc<Mutex<Vec<ThreadEvent>>>,
+    notify: F,
+) where
+    F: Fn() + Send + Sync + 'static,
+{
+    while let Some(first) = rx.recv().await {
+        let mut changed = BTreeSet::new();
+        collect(&mut changed, first);
+
+        let deadline = Instant::now() + DEBOUNCE;
+        while let Ok(Some(event)) = tokio::time::timeout_at(deadline, rx.recv()).await {
+            collect(&mut changed, event);
+        }
+
+        let events = classify(changed, &mut known);
+        if events.is_empty() {
+            continue;
+        }
+
+        {
+            let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.extend(events);
+            let overflow = pending.len().saturating_sub(MAX_PENDING);
+            pending.drain(..overflow);
+        }
+        notify();
+    }
+}
+
+/// Remember the thread files an event touched
+fn collect(changed: &mut BTreeSet<PathBuf>, event: notify::Result<Event>) {
+    let Ok(event) = event else {
+        return;
+    };
+    if event.kind.is_access() {
+        return;
+    }
+    changed.extend(
+        event
+            .paths
+            .into_iter()
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json")),
+    );
+}
+
+/// Turn touched paths into events by comparing with the files known before
+fn classify(changed: BTreeSet<PathBuf>, known: &mut HashSet<PathBuf>) -> Vec<ThreadEvent> {
+    let at = Utc::now().timestamp_millis();
+
+    changed
+        .into_iter()
+        .filter_map(|path| {
+            let kind = match (path.is_file(), known.contains(&path)) {
+                (true, true) => ThreadEventKind::Updated,
+                (true, false) => {
+                    known.insert(path.clone());
+                    ThreadEventKind::Created
+                },
+                (false, true) => {
+                    known.remove(&path);
+                    ThreadEventKind::Deleted
+                },
+                // Created and removed within one batch
+                (false, false) => return None,
+            };
+            let thread_id = path.file_stem()?.to_string_lossy().into_owned();
+            Some(ThreadEvent {
+                kind,
+                thread_id,
+                path,
+                at,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::threads::store::tests::write_thread;
+
+    /// Wait for the watcher to report `count` events
+    fn wait_for(watcher: &ThreadWatcher, count: usize) -> Vec<ThreadEvent> {
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            events.extend(watcher.take_events());
+            if events.len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        events
+    }
+
+    #[test]
+    fn test_created_updated_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = write_thread(dir.path(), 1, "existing", 1);
+
+        let batches = Arc::new(AtomicUsize::new(0));
+        let counter = batches.clone();
+        let watcher = ThreadWatcher::start(dir.path(), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+
+        let created = write_thread(dir.path(), 2, "new", 1);
+        let events = wait_for(&watcher, 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ThreadEventKind::Created);
+        assert_eq!(events[0].thread_id, created);
+
+        write_thread(dir.path(), 1, "existing, edited", 2);
+        let events = wait_for(&watcher, 1);
+        assert_eq!(events[0].kind, ThreadEventKind::Updated);
+        assert_eq!(events[0].thread_id, existing);
+
+        fs::remove_file(store::thread_path(dir.path(), &created)).unwrap();
+        // Other files are ignored
+        fs::write(dir.path().join("notes.txt"), "x").unwrap();
+        let events = wait_for(&watcher, 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ThreadEventKind::Deleted);
+
+        assert!(batches.load(Ordering::SeqCst) >= 3);
+    }
+
+    #[test]
+    fn test_classify_within_one_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let gone = dir.path().join("T-gone.json");
+        let mut known = HashSet::new();
+
+        let events = classify(BTreeSet::from([gone]), &mut known);
+        assert!(events.is_empty());
+    }
+}
@@ -8,33 +8,64 @@ local M = {}
 ---@field description string?
 ---@field content string
 ---@field tags string[]?
+---@field variables string? JSON array of variable declarations
 ---@field usage_count number
 ---@field last_used_at number?
 ---@field created_at number
 ---@field updated_at number
+---@field deleted_at number? When the prompt was moved to the trash
+---@field frecency number? Decayed usage score (only with `sort = "frecency"`)
+---@field scope "global"|"project"
+---@field path string? File of a project prompt
+---@field collection_id string? Collection the prompt is filed in
+---@field collection_position number Order within the collection
 
----List all prompts
+---List global and project prompts, optionally filtered by tags (names match
+---case-insensitively)
+---@param filter? { tag?: string, any_tags?: string[], all_tags?: string[], collection_id?: string, unfiled?: boolean, cwd?: string, sort?: "updated"|"frecency"|"most_used"|"recent"|"alphabetical"|"manual" }
 ---@return Prompt[]
-function M.list_prompts()
-  local result = ffi.call("prompts.list", {})
+function M.list_prompts(filter)
+  local result = ffi.call("prompts.list", filter or {})
   if result.error then
     error(result.message)
   end
   return result.prompts
 end
 
+---@class PromptSearchHit : Prompt
+---@field rank number
+---@field title_highlight string
+---@field snippet string
+
+---Full-text search over prompts (best matches first)
+---@param query string
+---@param opts? { limit?: number, highlight_start?: string, highlight_end?: string, raw?: boolean }
+---@return PromptSearchHit[]
+function M.search_prompts(query, opts)
+  local args = vim.tbl_extend("force", opts or {}, { query = query })
+  local result = ffi.call("prompts.search", args)
+  if result.error then
+    error(result.message)
+  end
+  return result.results
+end
+
 ---Create a new prompt
 ---@param title string
 ---@param description string?
 ---@param content string
 ---@param tags string[]?
+---@param scope? "global"|"project" Project prompts need `project_prompts.mode = "write_through"`
+---@param collection_id? string Collection to file a global prompt in
 ---@return Prompt
-function M.create_prompt(title, description, content, tags)
+function M.create_prompt(title, description, content, tags, scope, collection_id)
   local result = ffi.call("prompts.create", {
     title = title,
     description = description,
     content = content,
     tags = tags,
+    scope = scope,
+    collection_id = collection_id,
   })
   if result.error then
     error(result.message)
@@ -62,7 +93,7 @@ function M.update_prompt(id, title, description, content, tags)
   return true
 end
 
----Delete a prompt
+---Move a prompt to the trash
 ---@param id string
 function M.delete_prompt(id)
   local result = ffi.call("prompts.delete", { id = id })
@@ -72,6 +103,37 @@ function M.delete_prompt(id)
   return true
 end
 
+---List trashed prompts (most recently deleted first)
+---@return Prompt[]
+function M.list_trash()
+  local result = ffi.call("prompts.trash", {})
+  if result.error then
+    error(result.message)
+  end
+  return result.prompts
+end
+
+---Take a prompt out of the trash
+---@param id string
+function M.restore_deleted(id)
+  local result = ffi.call("prompts.restore_deleted", { id = id })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
+---Permanently delete trashed prompts
+---@param older_than_days? number Only purge prompts trashed at least this long ago
+---@return number purged
+function M.purge_trash(older_than_days)
+  local result = ffi.call("prompts.purge", { older_than_days = older_than_days })
+  if result.error then
+    error(result.message)
+  end
+  return result.purged
+end
+
 ---Record usage of a prompt
 ---@param id string
 function M.use_prompt(id)
@@ -82,4 +144,274 @@ function M.use_prompt(id)
   return true
 end
 
+---@class PromptContext
+---@field filename string?
+---@field filetype string?
+---@field selection string?
+---@field diagnostics { lnum: number, col: number, severity: number?, message: string, source: string? }[]?
+
+---Render a prompt template with variable values and editor context
+---@param id string
+---@param variables? table<string, any>
+---@param context? PromptContext
+---@return string
+function M.render_prompt(id, variables, context)
+  local result = ffi.call("prompts.render", {
+    id = id,
+    variables = variables or vim.empty_dict(),
+    context = context,
+  })
+  if result.error then
+    error(result.message)
+  end
+  return result.text
+end
+
+---Describe the variables a prompt template needs
+---@param id string
+---@return { declared: table[], undeclared: string[], context: string[] }
+function M.prompt_variables(id)
+  local result = ffi.call("prompts.variables", { id = id })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---@class PromptRevision
+---@field id number
+---@field prompt_id string
+---@field revision number
+---@field title string
+---@field description string?
+---@field content string
+---@field tags string?
+---@field variables string?
+---@field created_at number
+
+---List the revisions of a prompt (newest first)
+---@param id string
+---@return PromptRevision[]
+function M.prompt_history(id)
+  local result = ffi.call("prompts.history", { id = id })
+  if result.error then
+    error(result.message)
+  end
+  return result.revisions
+end
+
+---Line diff between two revisions (defaults to latest vs. previous)
+---@param id string
+---@param from? number
+---@param to? number
+---@return { from: number, to: number, title_changed: boolean, insertions: number, deletions: number, lines: table[], unified: string }
+function M.diff_prompt(id, from, to)
+  local result = ffi.call("prompts.diff", { id = id, from = from, to = to })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Restore a prompt to an earlier revision
+---@param id string
+---@param revision number
+---@return Prompt
+function M.restore_prompt(id, revision)
+  local result = ffi.call("prompts.restore", { id = id, revision = revision })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Export prompts to a file (json/csv) or a directory of Markdown files
+---Without `opts.path` the export is returned as `content`.
+---@param opts { format?: "json"|"markdown"|"csv", path?: string, tag?: string, any_tags?: string[], all_tags?: string[] }
+---@return { count: number, path?: string, content?: string }
+function M.export_prompts(opts)
+  local result = ffi.call("prompts.export", opts or {})
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Import prompts from a path or inline content
+---@param opts { path?: string, content?: string, format?: "json"|"markdown"|"csv", strategy?: "skip"|"overwrite"|"duplicate" }
+---@return { created: number, overwritten: number, restored: number, skipped: number, duplicated: number }
+function M.import_prompts(opts)
+  local result = ffi.call("prompts.import", opts or {})
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Per-day usage counts, newest day first
+---@param opts? { id?: string, days?: number }
+---@return { day: string, prompt_id: string, count: number }[]
+function M.prompt_usage(opts)
+  local result = ffi.call("prompts.usage", opts or {})
+  if result.error then
+    error(result.message)
+  end
+  return result.usage
+end
+
+---@class PromptTag
+---@field id number
+---@field name string
+---@field prompt_count number
+
+---List tags with prompt counts
+---@return PromptTag[]
+function M.list_tags()
+  local result = ffi.call("tags.list", {})
+  if result.error then
+    error(result.message)
+  end
+  return result.tags
+end
+
+---Rename a tag across all prompts
+---@param from string
+---@param to string
+---@return number affected
+function M.rename_tag(from, to)
+  local result = ffi.call("tags.rename", { from = from, to = to })
+  if result.error then
+    error(result.message)
+  end
+  return result.affected
+end
+
+---Merge tags into a target tag
+---@param sources string[]
+---@param target string
+---@return number affected
+function M.merge_tags(sources, target)
+  local result = ffi.call("tags.merge", { sources = sources, target = target })
+  if result.error then
+    error(result.message)
+  end
+  return result.affected
+end
+
+---Delete a tag from all prompts
+---@param name string
+---@return number affected
+function M.delete_tag(name)
+  local result = ffi.call("tags.delete", { name = name })
+  if result.error then
+    error(result.message)
+  end
+  return result.affected
+end
+
+---@class PromptCollection
+---@field id string
+---@field name string
+---@field parent_id string?
+---@field position number
+---@field prompt_count number
+---@field created_at number
+---@field updated_at number
+---@field children PromptCollection[]? Only with `tree = true`
+
+---List collections (flat, parents first) or as a tree of top-level nodes
+---@param tree? boolean
+---@return PromptCollection[]
+function M.list_collections(tree)
+  local result = ffi.call("collections.list", { tree = tree })
+  if result.error then
+    error(result.message)
+  end
+  return result.collections
+end
+
+---Create a collection at the end of its siblings
+---@param name string
+---@param parent_id? string
+---@return PromptCollection
+function M.create_collection(name, parent_id)
+  local result = ffi.call("collections.create", { name = name, parent_id = parent_id })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Rename a collection
+---@param id string
+---@param name string
+function M.rename_collection(id, name)
+  local result = ffi.call("collections.rename", { id = id, name = name })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
+---Move a collection under another one (top level without `parent_id`)
+---@param id string
+---@param parent_id? string
+---@param position? number 0-based position among the new siblings (default: last)
+function M.move_collection(id, parent_id, position)
+  local result = ffi.call("collections.move", { id = id, parent_id = parent_id, position = position })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
+---Delete a collection, moving its sub-collections and prompts to its parent
+---@param id string
+---@return number moved_prompts
+function M.delete_collection(id)
+  local result = ffi.call("collections.delete", { id = id })
+  if result.error then
+    error(result.message)
+  end
+  return result.moved_prompts
+end
+
+---Set the order of the sub-collections of `parent_id` (top level if nil)
+---@param parent_id string?
+---@param ids string[] Every child, in the new order
+function M.reorder_collections(parent_id, ids)
+  local result = ffi.call("collections.reorder", { parent_id = parent_id, ids = ids })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
+---File a prompt in a collection (unfiled without `collection_id`)
+---@param prompt_id string
+---@param collection_id? string
+---@param position? number 0-based position in the collection (default: last)
+function M.move_prompt(prompt_id, collection_id, position)
+  local result = ffi.call("collections.move_prompt", {
+    prompt_id = prompt_id,
+    collection_id = collection_id,
+    position = position,
+  })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
+---Set the order of the prompts in a collection (unfiled prompts if nil)
+---@param collection_id string?
+---@param ids string[] Every prompt of the collection, in the new order
+function M.reorder_prompts(collection_id, ids)
+  local result = ffi.call("collections.reorder_prompts", { collection_id = collection_id, ids = ids })
+  if result.error then
+    error(result.message)
+  end
+  return true
+end
+
 return M
@@ -0,0 +1,90 @@
+local ffi = require("amp_extras.ffi")
+
+local M = {}
+
+---@class McpServerConfig
+---@field command string? Program of a local server
+---@field args string[]?
+---@field env table<string, string>?
+---@field url string? Endpoint of a remote server
+---@field headers table<string, string>?
+
+---@class McpVariable
+---@field name string
+---@field set boolean
+---@field secret boolean Masked in the preview
+
+---@class McpServerEntry
+---@field name string
+---@field enabled boolean Disabled servers are left out of `amp.mcpServers`
+---@field kind "local"|"remote"? nil if the config is invalid
+---@field config McpServerConfig As written
+---@field error string?
+---@field preview { config: McpServerConfig, variables: McpVariable[] }? `${VAR}` expanded
+---@field permission { index: number, action: "allow"|"reject" }? First applying rule
+
+---@class McpServers
+---@field servers McpServerEntry[] Sorted by name
+---@field permissions table[] `amp.mcpPermissions` as written
+---@field permission_errors string[]
+---@field path string amp-extras disabled servers
+---@field settings_path string Amp settings file
+
+local function call(command, args)
+  local result = ffi.call(command, args)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---List all MCP servers, disabled ones included
+---@return McpServers
+function M.list_servers()
+  return call("mcp.list", {})
+end
+
+---Add a server, optionally with a rule allowing or rejecting exactly it
+---@param name string
+---@param server McpServerConfig
+---@param opts? { enabled: boolean?, permission: "allow"|"reject"? }
+---@return McpServers
+function M.add_server(name, server, opts)
+  opts = opts or {}
+  return call("mcp.add", {
+    name = name,
+    server = server,
+    enabled = opts.enabled,
+    permission = opts.permission,
+  })
+end
+
+---Set fields of a server (`vim.NIL` removes one)
+---@param name string
+---@param patch table
+---@param permission? "allow"|"reject"|userdata Rule for exactly this server; `vim.NIL` drops it
+---@return McpServers
+function M.update_server(name, patch, permission)
+  return call("mcp.update", { name = name, patch = patch, permission = permission })
+end
+
+---Remove a server and the rules written for exactly it
+---@param name string
+---@return McpServers
+function M.remove_server(name)
+  return call("mcp.remove", { name = name })
+end
+
+---@param name string
+---@return McpServers
+function M.enable_server(name)
+  return call("mcp.enable", { name = name })
+end
+
+---@param name string
+---@return McpServers
+function M.disable_server(name)
+  return call("mcp.disable", { name = name })
+end
+
+return M
@@ -0,0 +1,157 @@
+local ffi = require("amp_extras.ffi")
+
+local M = {}
+
+---@class PermissionRule
+---@field tool string Tool name or glob
+---@field matches table<string, any>?
+---@field action "allow"|"reject"|"ask"|"delegate"
+---@field to string? Delegate program
+---@field context "thread"|"subagent"?
+---@field enabled boolean Disabled rules are left out of `amp.permissions`
+---@field note string?
+
+---@class PermissionRules
+---@field rules PermissionRule[] In evaluation order; indices passed back are 0-based
+---@field path string amp-extras rule list
+---@field settings_path string Amp settings file
+
+local function call(command, args)
+  local result = ffi.call(command, args)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---List all permission rules, disabled ones included
+---@return PermissionRules
+function M.list_rules()
+  return call("permissions.list", {})
+end
+
+---Add a rule, at 0-based `index` or at the end
+---@param rule PermissionRule
+---@param index? number
+---@return PermissionRules
+function M.add_rule(rule, index)
+  return call("permissions.add", { rule = rule, index = index })
+end
+
+---Set fields of a rule (`vim.NIL` removes one)
+---@param index number 0-based
+---@param patch table
+---@return PermissionRules
+function M.update_rule(index, patch)
+  return call("permissions.update", { index = index, patch = patch })
+end
+
+---@param index number 0-based
+---@return PermissionRules
+function M.remove_rule(index)
+  return call("permissions.remove", { index = index })
+end
+
+---Reorder rules
+---@param order number[] Every current 0-based index, in the new order
+---@return PermissionRules
+function M.reorder_rules(order)
+  return call("permissions.reorder", { order = order })
+end
+
+---Enable or disable a rule (flipped if `enabled` is nil)
+---@param index number 0-based
+---@param enabled? boolean
+---@return PermissionRules
+function M.toggle_rule(index, enabled)
+  return call("permissions.toggle", { index = index, enabled = enabled })
+end
+
+---@class PermissionTraceStep
+---@field index number 0-based rule index
+---@field applies boolean
+---@field reason string
+
+---@class PermissionEvaluation
+---@field tool string
+---@field context "thread"|"subagent"
+---@field index number? Deciding rule (0-based)
+---@field action "allow"|"reject"|"ask"|"delegate"|nil nil when no rule applies
+---@field to string?
+---@field rule PermissionRule?
+---@field trace PermissionTraceStep[]
+
+---What Amp would do with a tool call
+---@param tool string
+---@param args? table Tool input
+---@param opts? { context?: "thread"|"subagent", rules?: PermissionRule[] }
+---@return PermissionEvaluation
+function M.evaluate(tool, args, opts)
+  local params = vim.tbl_extend("force", opts or {}, { tool = tool, args = args or vim.empty_dict() })
+  return call("permissions.evaluate", params)
+end
+
+---@class PermissionLintIssue
+---@field index number 0-based rule index
+---@field kind "duplicate"|"shadowed"|"invalid_regex"|"delegate_not_found"|"unknown_tool"
+---@field severity "error"|"warning"
+---@field message string
+---@field related number? Earlier rule that duplicates or shadows this one
+---@field file string? Where the rule is written
+---@field line number?
+---@field col number?
+
+---@class PermissionLint
+---@field issues PermissionLintIssue[]
+---@field tools_checked boolean
+---@field tools_error string? Why `amp tools list` failed
+---@field path string
+---@field settings_path string
+
+---Find duplicate, shadowed and broken rules
+---@param opts? { rules?: PermissionRule[], tools?: string[], check_tools?: boolean }
+---@return PermissionLint
+function M.lint(opts)
+  return call("permissions.lint", opts or {})
+end
+
+---Quickfix items for lint issues (issues without a position are listed
+---without a file)
+---@param issues PermissionLintIssue[]
+---@return table[]
+function M.lint_quickfix(issues)
+  return vim.tbl_map(function(issue)
+    return {
+      filename = issue.file,
+      lnum = issue.line or 0,
+      col = issue.col or 0,
+      type = issue.severity == "error" and "E" or "W",
+      text = string.format("Rule %d: %s", issue.index, issue.message),
+    }
+  end, issues)
+end
+
+---@class PermissionSuggestion
+---@field rule PermissionRule
+---@field guard PermissionRule? Add ahead of `rule`: asks about chained commands its glob also matches
+---@field count number Past calls the rule would have covered
+---@field threads number Threads those calls are in
+---@field examples string[]
+
+---@class PermissionSuggestions
+---@field suggestions PermissionSuggestion[] Most used first
+---@field threads number Threads read
+---@field calls number Tool calls read
+---@field decided number Calls the configured rules already decide
+---@field skipped number Shell commands too complex to generalize
+---@field errors string[]
+
+---Propose rules from the tool calls in thread history (add one with
+---`add_rule(suggestion.guard)`, if any, then `add_rule(suggestion.rule)`)
+---@param opts? { min_count?: number, limit?: number, include_archived?: boolean }
+---@return PermissionSuggestions
+function M.suggest(opts)
+  return call("permissions.suggest", opts or {})
+end
+
+return M
@@ -0,0 +1,355 @@
+local ffi = require("amp_extras.ffi")
+
+local M = {}
+
+---@class ThreadSummary
+---@field id string
+---@field title string Title, or the start of the first message for untitled threads
+---@field created number Unix timestamp (milliseconds)
+---@field updated number Last modification of the thread file (milliseconds)
+---@field message_count number
+---@field workspace string? First workspace root
+---@field workspace_name string?
+---@field agent_mode "smart"|"fast"|"other"
+---@field path string
+
+---@class ThreadPage
+---@field threads ThreadSummary[]
+---@field total number Number of threads before paging
+---@field offset number
+---@field limit number
+---@field dir string Thread directory
+---@field errors string[] Files that could not be parsed
+
+---List local Amp threads, one page at a time
+---@param opts? { area?: "threads"|"archive"|"trash", sort?: "created"|"updated"|"title"|"messages", offset?: number, limit?: number }
+---@return ThreadPage
+function M.list_threads(opts)
+  local result = ffi.call("threads.list", opts or {})
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---@class ThreadSearchHit
+---@field thread_id string
+---@field message_id number
+---@field role "user"|"assistant"
+---@field title string
+---@field rank number
+---@field snippet string
+
+---Full-text search over thread messages, tool inputs and tool results
+---@param query string
+---@param opts? { limit?: number, highlight_start?: string, highlight_end?: string, raw?: boolean, refresh?: boolean }
+---@return ThreadSearchHit[]
+function M.search_threads(query, opts)
+  local args = vim.tbl_extend("force", opts or {}, { query = query })
+  local result = ffi.call("threads.search", args)
+  if result.error then
+    error(result.message)
+  end
+  return result.results
+end
+
+---@class FileThread
+---@field thread_id string
+---@field title string
+---@field last_at number Latest reference (milliseconds)
+---@field message_ids number[] Messages referencing the file
+---@field access ("read"|"write"|"attached")[]
+
+---Threads that read, edited or attached a file, most recent first
+---@param path string
+---@param opts? { limit?: number, refresh?: boolean }
+---@return FileThread[]
+function M.threads_for_file(path, opts)
+  local args = vim.tbl_extend("force", opts or {}, { path = path })
+  local result = ffi.call("threads.for_file", args)
+  if result.error then
+    error(result.message)
+  end
+  return result.threads
+end
+
+---Bring the thread search index up to date (everything with `full`)
+---@param full? boolean
+---@return { indexed: number, removed: number, unchanged: number, errors: string[] }
+function M.reindex_threads(full)
+  local result = ffi.call("threads.reindex", { full = full })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---@class RenderedThread
+---@field id string
+---@field title string
+---@field lines string[] Markdown, one entry per buffer line
+---@field messages { message_id: number, role: "user"|"assistant", start_line: number, end_line: number }[] Line span of each message (1-based, inclusive)
+---@field folds { start_line: number, end_line: number }[] `<details>` sections
+---@field files { line: number, message_id: number, path: string }[] Files referenced by the thread
+
+---Render a thread as Markdown
+---@param id string
+---@param opts? { area?: "threads"|"archive"|"trash", thinking?: boolean, tool_results?: boolean, max_tool_lines?: number }
+---@return RenderedThread
+function M.render_thread(id, opts)
+  local args = vim.tbl_extend("force", opts or {}, { id = id })
+  local result = ffi.call("threads.render", args)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Export a thread as Markdown, a self-contained HTML page or JSONL (`amp --stream-json` messages)
+---
+---Writes to `opts.path` when given (the format defaults to a guess from its
+---extension), else returns the export as a string.
+---@param id string
+---@param opts? { area?: "threads"|"archive"|"trash", format?: "markdown"|"html"|"jsonl", path?: string, redact_thinking?: boolean, redact_tool_output?: boolean }
+---@return string? content
+function M.export_thread(id, opts)
+  local args = vim.tbl_extend("force", opts or {}, { id = id })
+  local result = ffi.call("threads.export", args)
+  if result.error then
+    error(result.message)
+  end
+  return result.content
+end
+
+---@class UsageTotals
+---@field input_tokens number
+---@field output_tokens number
+---@field credits number
+---@field events number Ledger events (or assistant messages) counted
+
+---@class UsageBucket: UsageTotals
+---@field key string Day (`YYYY-MM-DD`), workspace, agent mode or thread id
+---@field title string? Thread title, for `by_thread`
+
+---@class UsageSummary
+---@field total UsageTotals
+---@field by_day UsageBucket[] Oldest first
+---@field by_workspace UsageBucket[] Highest credits first
+---@field by_agent_mode UsageBucket[]
+---@field by_thread UsageBucket[]
+---@field errors string[] Files that could not be parsed
+
+---Token and credit usage of local threads over a date range (UTC days, inclusive)
+---@param opts? { from?: string, to?: string, include_archived?: boolean }
+---@return UsageSummary
+function M.usage_summary(opts)
+  local result = ffi.call("usage.summary", opts or {})
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---@class ThreadCompareSide
+---@field id string
+---@field from? number First message id (inclusive)
+---@field to? number Last message id (inclusive)
+---@field area? "threads"|"archive"|"trash"
+
+---@class ThreadDigest
+---@field thread_id string
+---@field title string
+---@field range { from: number?, to: number? }
+---@field message_count number
+---@field tools table<string, number> Tool calls by name
+---@field files_changed string[]
+---@field files_read string[]
+---@field usage UsageTotals
+---@field result string? Text of the last assistant message
+
+---@class ThreadMessageDigest
+---@field message_id number
+---@field role "user"|"assistant"
+---@field summary string
+---@field tools string[]
+
+---@class ThreadComparison
+---@field left ThreadDigest
+---@field right ThreadDigest
+---@field messages { turn: number, left: ThreadMessageDigest?, right: ThreadMessageDigest? }[] Side-by-side rows, paired by turn
+---@field tools { name: string, left: number, right: number }[]
+---@field files_changed { both: string[], only_left: string[], only_right: string[] }
+---@field usage_delta { input_tokens: number, output_tokens: number, credits: number } Right minus left
+---@field result_diff { kind: "equal"|"insert"|"delete", old_lnum: number?, new_lnum: number?, text: string }[]
+
+---Compare two threads, or two message ranges of one thread
+---@param left string|ThreadCompareSide Thread id or side
+---@param right string|ThreadCompareSide
+---@return ThreadComparison
+function M.compare_threads(left, right)
+  local result = ffi.call("threads.compare", { left = left, right = right })
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---@class AmpProcess
+---@field id number Registry id (not the OS pid)
+---@field action "continue"|"fork"
+---@field thread_id string
+---@field pid number?
+---@field program string
+---@field args string[]
+---@field cwd string?
+---@field started_at number Unix timestamp (milliseconds)
+---@field ended_at number?
+---@field status { state: "running"|"exited"|"killed"|"failed", code?: number, message?: string }
+---@field output string[] Last lines of stdout and stderr
+
+---Add a message to a thread with the Amp CLI (tracked in the background)
+---@param id string Thread id or URL
+---@param message string
+---@param opts? { cwd?: string }
+---@return AmpProcess
+function M.continue_thread(id, message, opts)
+  local args = vim.tbl_extend("force", opts or {}, { id = id, message = message })
+  local result = ffi.call("threads.continue", args)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Fork a thread and add a message to the fork with the Amp CLI
+---@param id string Thread id or URL
+---@param message string
+---@param opts? { cwd?: string }
+---@return AmpProcess
+function M.fork_thread(id, message, opts)
+  local args = vim.tbl_extend("force", opts or {}, { id = id, message = message })
+  local result = ffi.call("threads.fork", args)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Amp CLI processes started by continue_thread and fork_thread, oldest first
+---@return AmpProcess[]
+function M.thread_processes()
+  local result = ffi.call("threads.processes", {})
+  if result.error then
+    error(result.message)
+  end
+  return result.processes
+end
+
+---Stop a running Amp CLI process
+---@param id number Registry id
+---@return boolean killed
+function M.kill_thread_process(id)
+  local result = ffi.call("threads.kill", { id = id })
+  if result.error then
+    error(result.message)
+  end
+  return result.killed
+end
+
+---@class ThreadSelection
+---@field id? string
+---@field ids? string[]
+---@field filter? { older_than_days?: number, workspace?: string } Bulk selection (at least one criterion)
+---@field dry_run? boolean Only report the matching threads
+
+---@class ThreadBulkResult
+---@field ids string[] Threads changed (or that would be, in a dry run)
+---@field failed { id: string, message: string }[]
+---@field dry_run boolean
+
+local function bulk(command, selection)
+  local result = ffi.call(command, selection)
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Move threads to the archive
+---@param selection ThreadSelection
+---@return ThreadBulkResult
+function M.archive_threads(selection)
+  return bulk("threads.archive", selection)
+end
+
+---Move archived threads back to the Amp thread directory
+---@param selection ThreadSelection
+---@return ThreadBulkResult
+function M.unarchive_threads(selection)
+  return bulk("threads.unarchive", selection)
+end
+
+---Move threads (active or archived) to the trash
+---@param selection ThreadSelection
+---@return ThreadBulkResult
+function M.delete_threads(selection)
+  return bulk("threads.delete", selection)
+end
+
+---Move trashed threads back to where they were deleted from
+---@param selection ThreadSelection
+---@return ThreadBulkResult
+function M.restore_threads(selection)
+  return bulk("threads.restore", selection)
+end
+
+---Permanently delete trashed threads
+---@param selection ThreadSelection
+---@return ThreadBulkResult
+function M.purge_threads(selection)
+  return bulk("threads.purge", selection)
+end
+
+---@class TrashedThread : ThreadSummary
+---@field deleted_at number? Unix timestamp (milliseconds)
+---@field origin "threads"|"archive"
+
+---List trashed threads (most recently deleted first)
+---@return TrashedThread[]
+function M.list_thread_trash()
+  local result = ffi.call("threads.trash", {})
+  if result.error then
+    error(result.message)
+  end
+  return result.threads
+end
+
+---@class ThreadEvent
+---@field kind "created"|"updated"|"deleted"
+---@field thread_id string
+---@field path string
+---@field at number Unix timestamp (milliseconds)
+
+---Take the change events queued by the thread watcher
+---@return { watching: string?, events: ThreadEvent[] }
+function M.thread_events()
+  local result = ffi.call("threads.events", {})
+  if result.error then
+    error(result.message)
+  end
+  return result
+end
+
+---Subscribe to thread file changes (`User AmpThreadsChanged`)
+---@param callback fun(events: ThreadEvent[])
+---@return number autocmd_id
+function M.on_threads_changed(callback)
+  return vim.api.nvim_create_autocmd("User", {
+    pattern = "AmpThreadsChanged",
+    callback = function(ev)
+      callback(ev.data and ev.data.events or {})
+    end,
+  })
+end
+
+return M
@@ -0,0 +1,119 @@
+local api = require("amp_extras.commands.threads.api")
+
+local M = {}
+
+---Message span containing `line`
+---@param rendered RenderedThread
+---@param line number
+local function message_at(rendered, line)
+  for i, span in ipairs(rendered.messages) do
+    if line >= span.start_line and line <= span.end_line then
+      return i, span
+    end
+  end
+end
+
+---Jump to the next (`1`) or previous (`-1`) message
+---@param rendered RenderedThread
+---@param direction 1|-1
+local function jump(rendered, direction)
+  local line = vim.api.nvim_win_get_cursor(0)[1]
+  local target
+  if direction > 0 then
+    for _, span in ipairs(rendered.messages) do
+      if span.start_line > line then
+        target = span
+        break
+      end
+    end
+  else
+    local i = message_at(rendered, line)
+    for j = (i or #rendered.messages + 1) - 1, 1, -1 do
+      local span = rendered.messages[j]
+      if span.start_line < line then
+        target = span
+        break
+      end
+    end
+    -- Inside a message: go to its header first
+    if i and rendered.messages[i].start_line < line then
+      target = rendered.messages[i]
+    end
+  end
+  if target then
+    vim.api.nvim_win_set_cursor(0, { target.start_line, 0 })
+  end
+end
+
+---Open the file referenced at (or above) the cursor within the same message
+---@param rendered RenderedThread
+local function open_file(rendered)
+  local line = vim.api.nvim_win_get_cursor(0)[1]
+  local _, span = message_at(rendered, line)
+  local found
+  for _, file in ipairs(rendered.files) do
+    if file.line <= line and (not span or file.message_id == span.message_id) then
+      found = file
+    end
+  end
+  if not found then
+    vim.notify("No file referenced here", vim.log.levels.INFO)
+    return
+  end
+  vim.cmd("wincmd p")
+  vim.cmd("edit " .. vim.fn.fnameescape(found.path))
+end
+
+---Show a thread as Markdown in a read-only split
+---
+---`]]`/`[[` jump between messages, `gf` opens the file referenced at the
+---cursor and `<details>` sections start folded.
+---@param id string
+---@param opts? { area?: "threads"|"archive"|"trash", thinking?: boolean, tool_results?: boolean, max_tool_lines?: number }
+function M.open(id, opts)
+  local rendered = api.render_thread(id, opts)
+
+  -- A thread already open is refreshed in place: the name can only be
+  -- used by one buffer
+  local name = "amp://thread/" .. rendered.id
+  local buf = vim.fn.bufnr("^" .. name .. "$")
+  if buf == -1 then
+    buf = vim.api.nvim_create_buf(false, true)
+    vim.api.nvim_buf_set_name(buf, name)
+    vim.bo[buf].buftype = "nofile"
+    vim.bo[buf].bufhidden = "wipe"
+    vim.bo[buf].filetype = "markdown"
+  end
+  vim.bo[buf].modifiable = true
+  vim.api.nvim_buf_set_lines(buf, 0, -1, false, rendered.lines)
+  vim.bo[buf].modifiable = false
+
+  local win = vim.fn.bufwinid(buf)
+  if win ~= -1 then
+    vim.api.nvim_set_current_win(win)
+  else
+    vim.cmd("vsplit")
+    vim.api.nvim_set_current_buf(buf)
+  end
+  vim.wo.foldmethod = "manual"
+  vim.cmd("normal! zE")
+  for _, fold in ipairs(rendered.folds) do
+    vim.cmd(string.format("%d,%dfold", fold.start_line, fold.end_line))
+  end
+
+  local map = function(lhs, fn, desc)
+    vim.keymap.set("n", lhs, fn, { buffer = buf, desc = desc })
+  end
+  map("]]", function()
+    jump(rendered, 1)
+  end, "Next message")
+  map("[[", function()
+    jump(rendered, -1)
+  end, "Previous message")
+  map("gf", function()
+    open_file(rendered)
+  end, "Open referenced file")
+  map("q", "<cmd>close<cr>", "Close")
+end
+
+return M
@@ -793,7 +793,7 @@ function M.show(opts)
 
           if selected_count > 0 then
             local choice =
-              vim.fn.confirm("Delete " .. selected_count .. " prompts?", "&Yes\n&No", 2)
+              vim.fn.confirm("Move " .. selected_count .. " prompts to trash?", "&Yes\n&No", 2)
             if choice == 1 then
               for id, _ in pairs(_state.selected_ids) do
                 pcall(api.delete_prompt, id)
@@ -808,7 +808,7 @@ function M.show(opts)
           local node = _state.nodes[_state.selected_index]
           if node and node._prompt then
             local choice =
-              vim.fn.confirm("Delete prompt '" .. node._prompt.title .. "'?", "&Yes\n&No", 2)
+              vim.fn.confirm("Move prompt '" .. node._prompt.title .. "' to trash?", "&Yes\n&No", 2)
             if choice == 1 then
               pcall(api.delete_prompt, node._prompt.id)
               fetch_data()
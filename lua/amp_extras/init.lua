@@ -31,6 +31,19 @@ local defaults = {
   -- Map action name to specific key string (e.g., send_selection = "<leader>x")
   -- or set to false to disable specific keymap even if feature is enabled
   keymaps = {},
+
+  -- Checked-in team prompts (Markdown files) merged with the personal library
+  project_prompts = {
+    enabled = true,
+    dir = ".amp/prompts", -- Relative to the workspace root
+    mode = "read_only", -- "read_only" or "write_through" (edits rewrite the files)
+  },
+
+  -- Local Amp threads
+  threads = {
+    dir = nil, -- Defaults to ~/.local/share/amp/threads
+    watch = true, -- Fire `User AmpThreadsChanged` when thread files change
+  },
 }
 
 -- ============================================================================
@@ -210,13 +223,19 @@ function M.setup(opts)
   M.config = vim.tbl_deep_extend("force", defaults, opts)
 
   -- Call Rust FFI setup
-  local setup_result = ffi.setup({})
+  local setup_result = ffi.setup({
+    project_prompts = M.config.project_prompts,
+    threads = M.config.threads,
+  })
   if setup_result and setup_result.error then
     vim.notify(
       "amp-extras: FFI setup failed: " .. (setup_result.message or "unknown error"),
       vim.log.levels.ERROR
     )
   end
+  for _, warning in ipairs(setup_result and setup_result.warnings or {}) do
+    vim.notify("amp-extras: " .. warning, vim.log.levels.WARN)
+  end
 
   -- Setup keymaps
   setup_keymaps(M.config)